@@ -1,18 +1,59 @@
+#![allow(dead_code)]
+
+pub mod accel;
+#[cfg(test)]
+pub mod build;
+pub mod expr;
+pub mod names;
+
+#[cfg(feature = "zlib")]
+use crate::inflate;
 use crate::leb::*;
 use crate::macho;
+use crate::reader::Reader;
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::CStr;
-use std::fmt::{Display, Formatter};
+use std::fmt::{Display, Formatter, Write};
+use std::path::PathBuf;
 use std::str::{from_utf8, Utf8Error};
 
+pub const DEFAULT_MAX_DUMP_BYTES: usize = 256;
+
 #[derive(Debug)]
 pub struct File {
     pub sections: Vec<Section>,
+
+    // The size in bytes of each entry in `sections`: the Mach-O section
+    // header's, or for a compressed section, its size decompressed.
+    pub section_sizes: Vec<usize>,
+
+    // How many leading bytes of each unrecognized section to hexdump
+    // when displaying the file. Zero disables the dump.
+    pub hexdump_len: usize,
+
+    // The most bytes any one dump may show, whatever hexdump_len says.
+    // What's left out is noted after the dump.
+    pub max_dump_bytes: usize,
+
+    // Things the parser passed over without understanding, like tags and
+    // attributes it has no name for.
+    pub warnings: Vec<String>,
 }
 
 impl File {
-    pub fn from(segment: macho::Segment64, bytes: &[u8]) -> Result<File, String> {
-        let mut sections: Vec<Section> = segment.sections.iter()
+    pub fn from(
+        macho: &macho::File, segment: &macho::Segment64, bytes: &[u8]
+    ) -> Result<File, String> {
+        let bytes = &Self::relocate(macho, segment, bytes)?;
+        // In object files the DWARF shares its segment with code and data,
+        // including zerofill sections with no bytes in the file at all.
+        let dwarf_sections: Vec<&macho::Section64> = segment.sections.iter()
+            .filter(|sec| is_dwarf_section(&sec.sectname))
+            .collect();
+
+        let mut sections: Vec<Section> = dwarf_sections.iter()
             .map(|sec| Section::Unrecognized {
                 name: sec.sectname.clone(),
                 contents: vec![],
@@ -20,48 +61,529 @@ impl File {
             .collect();
 
         // Parse the __debug_abbrev section first,
-        // so that it can be used by __debug_info.
-        let (i, debug_abbrev) = segment.sections.iter()
+        // so that it can be used by __debug_info. Without it the units
+        // can't be read, but the other sections still can.
+        let debug_abbrev = dwarf_sections.iter()
             .enumerate()
-            .find(|(_, sec)| sec.sectname.as_str() == "__debug_abbrev")
-            .ok_or("missing __debug_abbrev section")?;
-        sections[i] =
-            Self::macho_section_to_dwarf(&debug_abbrev, &bytes, &sections)?;
+            .find(|(_, sec)| canonical_section(&sec.sectname) == Some(DwarfSectionKind::Abbrev));
+        if let Some((i, debug_abbrev)) = debug_abbrev {
+            sections[i] =
+                Self::macho_section_to_dwarf(debug_abbrev, bytes, &sections)?;
+        }
 
         // Parse the __debug_str section next,
         // so that it can be used by __debug_info.
-        let (i, debug_abbrev) = segment.sections.iter()
+        let (i, debug_str) = dwarf_sections.iter()
             .enumerate()
-            .find(|(_, sec)| sec.sectname.as_str() == "__debug_str")
+            .find(|(_, sec)| canonical_section(&sec.sectname) == Some(DwarfSectionKind::Str))
             .ok_or("missing __debug_str section")?;
         sections[i] =
-            Self::macho_section_to_dwarf(&debug_abbrev, &bytes, &sections)?;
+            Self::macho_section_to_dwarf(debug_str, bytes, &sections)?;
 
-        for (i, sec) in segment.sections.iter().enumerate() {
-            let start = sec.offset as usize;
-            let end = start + sec.size as usize;
-            let sec = Section::from(
-                sec.sectname.as_str(), &bytes[start .. end], &sections)?;
-            sections[i] = sec;
+        // DWARF 5 line tables refer into __debug_line_str, if there is one.
+        if let Some((i, debug_line_str)) = dwarf_sections.iter()
+            .enumerate()
+            .find(|(_, sec)| canonical_section(&sec.sectname) == Some(DwarfSectionKind::LineStr))
+        {
+            sections[i] =
+                Self::macho_section_to_dwarf(debug_line_str, bytes, &sections)?;
         }
-        Ok(File {
+
+        let mut warnings = vec![];
+        let mut section_sizes = vec![0; dwarf_sections.len()];
+        for (i, sec) in dwarf_sections.iter().enumerate() {
+            let contents = section_contents(sec, bytes)?;
+            section_sizes[i] = contents.len();
+            let kind = canonical_section(&sec.sectname);
+            if debug_abbrev.is_none() && matches!(kind, Some(DwarfSectionKind::Info | DwarfSectionKind::Types)) {
+                warnings.push(format!("{} left unparsed, as there's no __debug_abbrev", sec.sectname));
+                sections[i] = Section::Unrecognized {
+                    name: sec.sectname.clone(),
+                    contents: contents.into_owned(),
+                };
+                continue;
+            }
+            sections[i] = Section::from(&sec.sectname, &contents, &sections)?;
+        }
+        let mut file = File {
             sections,
+            section_sizes,
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        warnings.extend(file.find_warnings());
+        file.warnings = warnings;
+        Ok(file)
+    }
+
+    // Counts the tags and attribute names that parsed without being
+    // recognized. Forms the parser can't read stop it instead, since
+    // their size is unknown.
+    fn find_warnings(&self) -> Vec<String> {
+        let mut tags: BTreeMap<u64, usize> = BTreeMap::new();
+        let mut names: BTreeMap<u64, usize> = BTreeMap::new();
+        for die in self.dies() {
+            if let DIETag::Unknown(code) = die.tag {
+                *tags.entry(code).or_default() += 1;
+            }
+            for attr in die.attrs.iter() {
+                if let AttrName::Unrecognized(code) = attr.name {
+                    *names.entry(code).or_default() += 1;
+                }
+            }
+        }
+        let tags = tags.into_iter()
+            .map(|(code, count)| format!("unrecognized tag {:#x} on {} DIEs", code, count));
+        let names = names.into_iter()
+            .map(|(code, count)| format!("unrecognized attribute {:#x} on {} DIEs", code, count));
+        tags.chain(names).collect()
+    }
+
+    // In object files, addresses in the DWARF are left for the linker to
+    // fill in, so resolve them on a copy of the file first.
+    fn relocate<'a>(
+        macho: &macho::File, segment: &macho::Segment64, bytes: &'a [u8]
+    ) -> Result<Cow<'a, [u8]>, String> {
+        if !matches!(macho.header.file_type, macho::FileType::RelocatableObj) {
+            return Ok(Cow::Borrowed(bytes));
+        }
+        let mut copy = bytes.to_vec();
+        macho.apply_relocations(segment, &mut copy)?;
+        Ok(Cow::Owned(copy))
+    }
+
+    // Parses just the unit starting `offset` bytes into __debug_info, along
+    // with the abbrevs and strings it needs. This gets at a unit that
+    // follows a corrupt one, which would stop `from`.
+    pub fn unit_at(
+        macho: &macho::File, segment: &macho::Segment64, bytes: &[u8], offset: usize
+    ) -> Result<CompilationUnit, String> {
+        let bytes = &Self::relocate(macho, segment, bytes)?;
+        let find = |kind, name| segment.sections.iter()
+            .find(|sec| canonical_section(&sec.sectname) == Some(kind))
+            .ok_or(format!("missing {} section", name));
+        let mut others = vec![];
+        for (kind, name) in [(DwarfSectionKind::Abbrev, "__debug_abbrev"), (DwarfSectionKind::Str, "__debug_str")] {
+            let section = Self::macho_section_to_dwarf(find(kind, name)?, bytes, &others)?;
+            others.push(section);
+        }
+        let info = &section_contents(find(DwarfSectionKind::Info, "__debug_info")?, bytes)?;
+        if offset >= info.len() {
+            return Err(format!("offset {:#x} is past the end of __debug_info ({:#x} bytes)",
+                offset, info.len()));
+        }
+        CompilationUnit::from(info, offset, &Section::find_abbrevs(&others)?, Section::find_strs(&others)?)
+    }
+
+    // Parses just the `index`th unit in __debug_info, counting from 0. The
+    // units before it are stepped over by their headers' unit_length,
+    // without reading their DIEs.
+    pub fn nth_unit(
+        macho: &macho::File, segment: &macho::Segment64, bytes: &[u8], index: usize
+    ) -> Result<CompilationUnit, String> {
+        let info = segment.sections.iter()
+            .find(|sec| canonical_section(&sec.sectname) == Some(DwarfSectionKind::Info))
+            .ok_or("missing __debug_info section")?;
+        let units = CompilationUnit::index(&section_contents(info, bytes)?)?;
+        let entry = units.get(index)
+            .ok_or_else(|| format!("__debug_info has only {} units", units.len()))?;
+        Self::unit_at(macho, segment, bytes, entry.offset)
+    }
+
+    // Parses only __debug_abbrev, leaving the rest of the segment alone, so
+    // the abbrevs can be inspected even when other sections are broken.
+    pub fn abbrevs_only(segment: &macho::Segment64, bytes: &[u8]) -> Result<File, String> {
+        let debug_abbrev = segment.sections.iter()
+            .find(|sec| canonical_section(&sec.sectname) == Some(DwarfSectionKind::Abbrev))
+            .ok_or("missing __debug_abbrev section")?;
+        let contents = section_contents(debug_abbrev, bytes)?;
+        Ok(File {
+            sections: vec![Section::from(&debug_abbrev.sectname, &contents, &[])?],
+            section_sizes: vec![contents.len()],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        })
+    }
+
+    // Iterates depth-first over every DIE in every unit.
+    pub fn dies(&self) -> DIEIter<'_> {
+        DIEIter::new(self.roots())
+    }
+
+    // The compilation units in __debug_info.
+    pub fn units(&self) -> impl Iterator<Item = &CompilationUnit> {
+        self.sections.iter()
+            .flat_map(|sec| match sec {
+                Section::DebugInfo { units } => &units[..],
+                _ => &[],
+            })
+    }
+
+    // The directory the first unit was compiled in, which the paths in
+    // line_table are relative to.
+    pub fn comp_dir(&self) -> Option<&str> {
+        self.units().next()?.comp_dir()
+    }
+
+    // The rows of the __debug_line program, if there is one.
+    pub fn line_table(&self) -> Option<&CompiledDebugLine> {
+        self.sections.iter().find_map(|sec| match sec {
+            Section::DebugLine { compiled, .. } => Some(compiled),
+            _ => None,
+        })
+    }
+
+    // The offsets in __debug_info of the DIEs that .debug_names lists
+    // under `name`. Empty if there's no .debug_names.
+    pub fn names_lookup(&self, name: &str) -> Vec<u64> {
+        self.sections.iter()
+            .flat_map(|sec| match sec {
+                Section::DebugNames { indexes } => &indexes[..],
+                _ => &[],
+            })
+            .flat_map(|index| index.lookup(name))
+            .collect()
+    }
+
+    // The DIE starting `offset` bytes into __debug_info.
+    pub fn die_at_offset(&self, offset: usize) -> Option<&DIE> {
+        let unit = self.units()
+            .find(|unit| (unit.offset..unit.offset + unit.header.unit_size()).contains(&offset))?;
+        unit.die_at((offset - unit.offset) as u64)
+    }
+
+    // The offset in __debug_info of the unit whose __debug_aranges ranges
+    // cover `address`. None if there's no __debug_aranges, or it doesn't.
+    pub fn cu_for_address(&self, address: u64) -> Option<u64> {
+        self.sections.iter()
+            .flat_map(|sec| match sec {
+                Section::DebugAranges { sets } => &sets[..],
+                _ => &[],
+            })
+            .find(|set| set.tuples.iter()
+                .any(|tuple| tuple.addr <= address && address - tuple.addr < tuple.length))
+            .map(ArangeSet::debug_info_offset)
+    }
+
+    // The subprogram whose low_pc..high_pc covers `address`, and the unit
+    // it's in. __debug_aranges, if there is one, says which unit to look
+    // in first.
+    pub fn subprogram_at(&self, address: u64) -> Option<(&CompilationUnit, &DIE)> {
+        let likely = self.cu_for_address(address)
+            .and_then(|offset| self.units().find(|unit| unit.offset as u64 == offset));
+        likely.into_iter().chain(self.units()).find_map(|unit| {
+            let die = unit.dies().find(|die| {
+                die.tag == DIETag::Subprogram
+                    && die.attr(&AttrName::LowPc).and_then(AttrValue::as_u64)
+                        .is_some_and(|low_pc| low_pc <= address)
+                    && unit.high_pc(die).is_some_and(|high_pc| address < high_pc)
+            })?;
+            Some((unit, die))
         })
     }
 
+    // The top-level DIE of each unit, in __debug_info and __debug_types.
+    fn roots(&self) -> Vec<&DIE> {
+        self.sections.iter()
+            .flat_map(|sec| match sec {
+                Section::DebugInfo { units } =>
+                    units.iter().flat_map(|unit| unit.dies.iter()).collect(),
+                Section::DebugTypes { units } =>
+                    units.iter().flat_map(|unit| unit.dies.iter()).collect(),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    // Reads `unit`'s location list at `offset` in __debug_loc, returning
+    // the (begin, end, expression) of each entry. Addresses in the list are
+    // the unit's address size, and relative to `base_addr`, usually its
+    // DW_AT_low_pc, until a base address selection entry replaces it.
+    pub fn location_list(
+        &self, unit: &CompilationUnit, offset: u64, base_addr: u64
+    ) -> Result<Vec<(u64, u64, Vec<u8>)>, String> {
+        let contents = self.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugLoc { contents } => Some(contents),
+                _ => None,
+            })
+            .ok_or("missing __debug_loc section")?;
+        let address_size = unit.header.address_size;
+        let rest = contents.get(offset as usize..)
+            .ok_or_else(|| format!("location list offset {:#x} is past end of __debug_loc", offset))?;
+        let mut reader = Reader::new(rest);
+        let read_address = |reader: &mut Reader| match address_size {
+            4 => reader.u32().map(|x| x as u64),
+            _ => reader.u64(),
+        };
+        let max_address = if address_size == 4 { u32::MAX as u64 } else { u64::MAX };
+
+        let mut base = base_addr;
+        let mut entries = vec![];
+        loop {
+            let begin = read_address(&mut reader)?;
+            let end = read_address(&mut reader)?;
+            if begin == 0 && end == 0 { break; }
+            if begin == max_address {
+                base = end;
+                continue;
+            }
+            let len = reader.u16()? as usize;
+            let expr = reader.bytes(len)?.to_vec();
+            // Offsets wrap around the address space, as the target's
+            // address arithmetic would.
+            entries.push((base.wrapping_add(begin), base.wrapping_add(end), expr));
+        }
+        Ok(entries)
+    }
+
+    // Reads `unit`'s range list with the given index (as in
+    // DW_FORM_rnglistx) from __debug_rnglists, returning the [begin, end)
+    // of each range. The index counts from the unit's DW_AT_rnglists_base.
+    // `base_addr` is the unit's base address, usually its DW_AT_low_pc.
+    pub fn rnglist(
+        &self, unit: &CompilationUnit, index: u64, base_addr: u64
+    ) -> Result<Vec<(u64, u64)>, String> {
+        let contents = self.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugRngLists { contents, .. } => Some(contents),
+                _ => None,
+            })
+            .ok_or("missing __debug_rnglists section")?;
+        let (header, offset) = ListsHeader::find_list(contents, unit, AttrName::RnglistsBase, index)?;
+        let entries = read_list_entries(
+            &contents[offset..], header.address_size, base_addr, false)?;
+        Ok(entries.into_iter().map(|(begin, end, _)| (begin, end)).collect())
+    }
+
+    // Reads `unit`'s location list with the given index (as in
+    // DW_FORM_loclistx) from __debug_loclists, counting from the unit's
+    // DW_AT_loclists_base, like `location_list` does for __debug_loc.
+    pub fn loclist(
+        &self, unit: &CompilationUnit, index: u64, base_addr: u64
+    ) -> Result<Vec<(u64, u64, Vec<u8>)>, String> {
+        let contents = self.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugLocLists { contents, .. } => Some(contents),
+                _ => None,
+            })
+            .ok_or("missing __debug_loclists section")?;
+        let (header, offset) = ListsHeader::find_list(contents, unit, AttrName::LoclistsBase, index)?;
+        read_list_entries(&contents[offset..], header.address_size, base_addr, true)
+    }
+
+    // Looks up `unit`'s string with the given index (as in DW_FORM_strx)
+    // by way of __debug_str_offs. The index counts from the unit's
+    // DW_AT_str_offsets_base, or from just past the first header if the
+    // unit doesn't have one.
+    pub fn indexed_str(&self, unit: &CompilationUnit, index: u64) -> Result<&str, String> {
+        let offsets = self.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugStrOffsets { contents } => Some(contents),
+                _ => None,
+            })
+            .ok_or("missing __debug_str_offs section")?;
+        let strs = self.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugStr(DebugStr { bytes }) => Some(bytes),
+                _ => None,
+            })
+            .ok_or("missing __debug_str section")?;
+        let format = unit.header.format;
+        let header_size = match format {
+            Format::Dwarf32 => 8,
+            Format::Dwarf64 => 16,
+        };
+        let base = unit.dies.first()
+            .and_then(|root| root.attr(&AttrName::StrOffsetsBase))
+            .and_then(AttrValue::as_u64)
+            .unwrap_or(header_size);
+        let entry = index.checked_mul(format.offset_size() as u64)
+            .and_then(|offset| offset.checked_add(base))
+            .and_then(|entry| usize::try_from(entry).ok())
+            .filter(|&entry| entry.checked_add(format.offset_size()).is_some_and(|end| end <= offsets.len()))
+            .ok_or_else(|| format!("string index {} is past end of __debug_str_offs", index))?;
+        let offset = read_offset(&offsets[entry..], format);
+        let string = usize::try_from(offset).ok()
+            .and_then(|offset| strs.get(offset..))
+            .ok_or_else(|| format!("string offset {:#x} is past end of __debug_str", offset))?;
+        CStr::from_bytes_until_nul(string)
+            .map_err(|e| e.to_string())?
+            .to_str()
+            .map_err(|e| e.to_string())
+    }
+
+    // Checks that parsing accounted for every byte of __debug_info: that
+    // the units fill the section, and each unit's DIEs fill the unit.
+    // Returns a description of each place that didn't add up, which
+    // usually means the parser lost its place.
+    pub fn verify(&self) -> Vec<String> {
+        let mut problems = vec![];
+        for (section, &size) in self.sections.iter().zip(self.section_sizes.iter()) {
+            let Section::DebugInfo { units } = section else { continue };
+            for unit in units {
+                let unit_size = unit.header.unit_size();
+                if unit.dies_end != unit_size {
+                    problems.push(format!(
+                        "__debug_info: unit at {:#x} is {:#x} bytes, but its DIEs end at {:#x}, leaving {} bytes unread",
+                        unit.offset, unit_size, unit.dies_end, unit_size.saturating_sub(unit.dies_end)));
+                }
+            }
+            let end = units.last().map_or(0, |unit| unit.offset + unit.header.unit_size());
+            if end != size {
+                problems.push(format!("__debug_info: units end at {:#x}, but the section is {:#x} bytes",
+                    end, size));
+            }
+        }
+        problems
+    }
+
+    // The human-readable strings, each with where it came from: the
+    // DW_AT_name, DW_AT_producer and DW_AT_comp_dir of every DIE, then
+    // everything in __debug_str. Empty strings and ones that aren't UTF-8
+    // are left out.
+    pub fn strings(&self) -> Vec<(&'static str, &str)> {
+        let attrs = [
+            (AttrName::Name, "DW_AT_name"),
+            (AttrName::Producer, "DW_AT_producer"),
+            (AttrName::CompDir, "DW_AT_comp_dir"),
+        ];
+        let mut strings: Vec<(&'static str, &str)> = self.dies()
+            .flat_map(|die| attrs.iter()
+                .filter_map(|(name, label)| Some((*label, die.attr(name)?.as_str()?))))
+            .collect();
+        for sec in self.sections.iter() {
+            if let Section::DebugStr(DebugStr { bytes }) = sec {
+                strings.extend(bytes.split(|&b| b == 0)
+                    .filter_map(|s| from_utf8(s).ok())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| ("__debug_str", s)));
+            }
+        }
+        strings
+    }
+
+    // Counts the DIEs with each tag.
+    pub fn tag_histogram(&self) -> BTreeMap<DIETag, usize> {
+        let mut histogram = BTreeMap::new();
+        for die in self.dies() {
+            *histogram.entry(die.tag).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    // Returns the name and size in bytes of each section.
+    pub fn listing(&self) -> Vec<(&str, usize)> {
+        self.sections.iter()
+            .map(|sec| sec.name())
+            .zip(self.section_sizes.iter().copied())
+            .collect()
+    }
+
+    // Compares the units and DIEs of this file against `other`.
+    //
+    // Units are matched by tag and DW_AT_name. Within a matched pair of
+    // units, DIEs are matched the same way, ignoring where in the tree they
+    // sit; DIEs without a name are skipped, and if several share a tag and
+    // name only the first is compared.
+    pub fn diff(&self, other: &File) -> Vec<Difference> {
+        let ours = units_by_name(self);
+        let theirs = units_by_name(other);
+        let mut diffs = vec![];
+        for (key, unit) in ours.iter() {
+            match theirs.get(key) {
+                Some(other_unit) => diff_units(unit, other_unit, &mut diffs),
+                None => diffs.push(Difference::RemovedUnit(key.1.to_string())),
+            }
+        }
+        for key in theirs.keys().filter(|key| !ours.contains_key(key)) {
+            diffs.push(Difference::AddedUnit(key.1.to_string()));
+        }
+        diffs
+    }
+
     fn macho_section_to_dwarf(
-        sec: &macho::Section64, bytes: &[u8], others: &Vec<Section>
+        sec: &macho::Section64, bytes: &[u8], others: &[Section]
     ) -> Result<Section, String> {
-        let start = sec.offset as usize;
-        let end = start + sec.size as usize;
-        Section::from(sec.sectname.as_str(), &bytes[start .. end], others)
+        Section::from(sec.sectname.as_str(), &section_contents(sec, bytes)?, others)
+    }
+}
+
+// The contents of `sec`, decompressed if it's a __zdebug_* section. Those
+// start with GNU's "ZLIB" and the big-endian size uncompressed. ELF instead
+// marks compressed .debug_* sections with SHF_COMPRESSED, which Mach-O has
+// no equivalent of, so every other section is read as it is. The GNU
+// header has no field naming the algorithm, so there's no zstd to handle.
+fn section_contents<'a>(sec: &macho::Section64, bytes: &'a [u8]) -> Result<Cow<'a, [u8]>, String> {
+    let contents = sec.contents(bytes)?;
+    let name = &sec.sectname;
+    if !name.starts_with("__zdebug_") && !name.starts_with(".zdebug_") {
+        return Ok(Cow::Borrowed(contents));
+    }
+    let rest = contents.strip_prefix(b"ZLIB")
+        .ok_or_else(|| format!("{} doesn't start with a ZLIB compression header", name))?;
+    let size = rest.get(..8)
+        .ok_or_else(|| format!("{} is too short for its compression header", name))?;
+    let size = u64::from_be_bytes(size.try_into().unwrap());
+    match decompress(&rest[8..], size) {
+        Ok(contents) if contents.len() as u64 == size => Ok(Cow::Owned(contents)),
+        Ok(contents) => Err(format!("{} decompressed to {:#x} bytes, not the {:#x} its header gives",
+            name, contents.len(), size)),
+        Err(e) => Err(format!("decompressing {}: {}", name, e)),
     }
 }
 
+#[cfg(feature = "zlib")]
+fn decompress(stream: &[u8], size: u64) -> Result<Vec<u8>, String> {
+    // Stopping at the declared size keeps a corrupt or hostile stream from
+    // expanding without bound.
+    inflate::zlib_decompress(stream, usize::try_from(size).unwrap_or(usize::MAX))
+}
+
+#[cfg(not(feature = "zlib"))]
+fn decompress(_stream: &[u8], _size: u64) -> Result<Vec<u8>, String> {
+    Err("this build leaves out zlib support".to_string())
+}
+
 impl Display for File {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         for sec in self.sections.iter() {
             write!(f, "{}", sec)?;
+            if let Section::Unrecognized { contents, .. } = sec {
+                if self.hexdump_len > 0 {
+                    let len = contents.len().min(self.hexdump_len).min(self.max_dump_bytes);
+                    write!(f, "{}", HexDump(&contents[..len]))?;
+                    if len < contents.len() {
+                        writeln!(f, "... ({} more bytes)", contents.len() - len)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Displays bytes as rows of offset | hex | ascii, 16 bytes per row.
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl Display for HexDump<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        for (row, chunk) in self.0.chunks(16).enumerate() {
+            write!(f, "{:08x} |", row * 16)?;
+            for b in chunk {
+                write!(f, " {:02x}", b)?;
+            }
+            for _ in chunk.len()..16 {
+                write!(f, "   ")?;
+            }
+            write!(f, " | ")?;
+            for b in chunk {
+                let c = if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
         }
         Ok(())
     }
@@ -75,20 +597,60 @@ pub enum Section {
     },
 
     DebugInfo {
-        header: CUHeader,
-        dies: Vec<DIE>,
+        units: Vec<CompilationUnit>,
+    },
+
+    DebugTypes {
+        units: Vec<TypeUnit>,
     },
 
     DebugAbbrev {
         abbrevs: Vec<AbbrevDecl>,
+        // Where each table starts in the section, and the index in
+        // `abbrevs` of its first declaration. Units pick theirs by their
+        // header's debug_abbrev_offset.
+        tables: Vec<(u64, usize)>,
     },
 
     DebugStr(DebugStr),
 
+    // Holds the paths referred to by DWARF 5 line program headers.
+    DebugLineStr(DebugStr),
+
+    // Location lists, read on demand by File::location_list.
+    DebugLoc {
+        contents: Vec<u8>,
+    },
+
+    // DWARF 5 string offsets, for DW_FORM_strx.
+    DebugStrOffsets {
+        contents: Vec<u8>,
+    },
+
+    // DWARF 5 range and location lists, read on demand by File::rnglist
+    // and File::loclist. `contents` is the whole section, header included.
+    DebugRngLists {
+        header: ListsHeader,
+        contents: Vec<u8>,
+    },
+
+    DebugLocLists {
+        header: ListsHeader,
+        contents: Vec<u8>,
+    },
+
     DebugAranges {
-        header: CUHeader,
-        segment_size: u8,
-        tuples: Vec<ArangeTuple>,
+        sets: Vec<ArangeSet>,
+    },
+
+    DebugNames {
+        indexes: Vec<names::NameIndex>,
+    },
+
+    // __apple_names, __apple_types, __apple_namespac or __apple_objc.
+    AppleAccel {
+        name: String,
+        table: accel::AppleAccelTable,
     },
 
     Unrecognized {
@@ -97,75 +659,212 @@ pub enum Section {
     },
 }
 
+// The DWARF sections, whatever the object format calls them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwarfSectionKind {
+    Abbrev,
+    Addr,
+    Aranges,
+    Frame,
+    Info,
+    Line,
+    LineStr,
+    Loc,
+    LocLists,
+    MacInfo,
+    Macro,
+    Names,
+    PubNames,
+    PubTypes,
+    GnuPubNames,
+    GnuPubTypes,
+    Ranges,
+    RngLists,
+    Str,
+    StrOffsets,
+    Types,
+    AppleNames,
+    AppleTypes,
+    AppleNamespace,
+    AppleObjC,
+}
+
+// Works out which DWARF section `name` is, from its Mach-O spelling
+// (__debug_info) or its ELF one (.debug_info). Mach-O section names hold
+// at most 16 characters, so the longer names are also matched cut short,
+// like __debug_str_offs.
+// Whether `name` is a DWARF or accelerator table section, known or not.
+pub fn is_dwarf_section(name: &str) -> bool {
+    ["__debug_", ".debug_", "__zdebug_", ".zdebug_", "__apple_", ".apple_"].iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+pub fn canonical_section(name: &str) -> Option<DwarfSectionKind> {
+    let base = ["__debug_", ".debug_", "__zdebug_", ".zdebug_"].iter()
+        .find_map(|prefix| name.strip_prefix(prefix));
+    if let Some(base) = base {
+        return Some(match base {
+            "abbrev"   => DwarfSectionKind::Abbrev,
+            "addr"     => DwarfSectionKind::Addr,
+            "aranges"  => DwarfSectionKind::Aranges,
+            "frame"    => DwarfSectionKind::Frame,
+            "info"     => DwarfSectionKind::Info,
+            "line"     => DwarfSectionKind::Line,
+            "line_str" => DwarfSectionKind::LineStr,
+            "loc"      => DwarfSectionKind::Loc,
+            "loclists" => DwarfSectionKind::LocLists,
+            "macinfo"  => DwarfSectionKind::MacInfo,
+            "macro"    => DwarfSectionKind::Macro,
+            "names"    => DwarfSectionKind::Names,
+            "pubnames" => DwarfSectionKind::PubNames,
+            "pubtypes" => DwarfSectionKind::PubTypes,
+            "gnu_pubnames" | "gnu_pubn" => DwarfSectionKind::GnuPubNames,
+            "gnu_pubtypes" | "gnu_pubt" => DwarfSectionKind::GnuPubTypes,
+            "ranges"   => DwarfSectionKind::Ranges,
+            "rnglists" => DwarfSectionKind::RngLists,
+            "str"      => DwarfSectionKind::Str,
+            "str_offsets" | "str_offs" => DwarfSectionKind::StrOffsets,
+            "types"    => DwarfSectionKind::Types,
+            _ => return None,
+        });
+    }
+    let base = name.strip_prefix("__apple_").or_else(|| name.strip_prefix(".apple_"))?;
+    match base {
+        "names" => Some(DwarfSectionKind::AppleNames),
+        "types" => Some(DwarfSectionKind::AppleTypes),
+        "namespace" | "namespac" => Some(DwarfSectionKind::AppleNamespace),
+        "objc"  => Some(DwarfSectionKind::AppleObjC),
+        _ => None,
+    }
+}
+
 impl Section {
+    // The Mach-O name of the section this was parsed from.
+    pub fn name(&self) -> &str {
+        match self {
+            Section::DebugLine { .. }    => "__debug_line",
+            Section::DebugInfo { .. }    => "__debug_info",
+            Section::DebugTypes { .. }   => "__debug_types",
+            Section::DebugAbbrev { .. }  => "__debug_abbrev",
+            Section::DebugStr(_)         => "__debug_str",
+            Section::DebugLineStr(_)     => "__debug_line_str",
+            Section::DebugLoc { .. }     => "__debug_loc",
+            Section::DebugStrOffsets { .. } => "__debug_str_offs",
+            Section::DebugRngLists { .. } => "__debug_rnglists",
+            Section::DebugLocLists { .. } => "__debug_loclists",
+            Section::DebugAranges { .. } => "__debug_aranges",
+            Section::DebugNames { .. }   => "__debug_names",
+            Section::AppleAccel { name, .. } => name,
+            Section::Unrecognized { name, .. } => name,
+        }
+    }
+
     pub fn from(
-        name: &str, bytes: &[u8], others: &Vec<Section>
+        name: &str, bytes: &[u8], others: &[Section]
     ) -> Result<Section, String> {
-        match name {
-            "__debug_info" => {
-                let header = CUHeader::from(&bytes[0..11]);
-                let offset = 11;
-                let debug_abbrev = others.iter().filter_map(|sect|
-                    match &sect {
-                        Section::DebugAbbrev { abbrevs } => Some(abbrevs),
-                        _ => None,
-                    }
-                ).next().ok_or("haven't parsed __debug_abbrev yet")?;
-                let strs = others.iter().filter_map(|sect|
-                    match &sect {
-                        Section::DebugStr(DebugStr{ bytes }) => Some(bytes),
-                        _ => None,
-                    }
-                ).next().ok_or("haven't parsed __debug_str yet")?;
-                // TODO: How do we know if there are multiple compilation units?
-                let (die, _) = DIE::from(&bytes[offset..], debug_abbrev, strs)?;
-                Ok(Section::DebugInfo {
-                    header,
-                    dies: vec![die],
-                })
+        match canonical_section(name) {
+            Some(DwarfSectionKind::Info) => {
+                let debug_abbrev = Self::find_abbrevs(others)?;
+                let strs = Self::find_strs(others)?;
+                let offsets = CompilationUnit::offsets(bytes)?;
+                #[cfg(feature = "parallel")]
+                let units = CompilationUnit::parse_all_parallel(bytes, &offsets, &debug_abbrev, strs)?;
+                #[cfg(not(feature = "parallel"))]
+                let units = CompilationUnit::parse_all(bytes, &offsets, &debug_abbrev, strs)?;
+                Ok(Section::DebugInfo { units })
             },
 
-            "__debug_abbrev" => {
-                let mut abbrevs = vec![];
+            Some(DwarfSectionKind::Types) => {
+                let debug_abbrev = Self::find_abbrevs(others)?;
+                let strs = Self::find_strs(others)?;
+                let mut units = vec![];
                 let mut offset = 0;
+                while offset < bytes.len() {
+                    let unit = TypeUnit::from(bytes, offset, &debug_abbrev, strs)?;
+                    offset += unit.header.header.unit_size();
+                    units.push(unit);
+                }
+                Ok(Section::DebugTypes { units })
+            },
+
+            Some(DwarfSectionKind::Abbrev) => {
+                let mut abbrevs = vec![];
+                let mut tables = vec![];
+                let mut reader = Reader::new(bytes);
+                // Each unit can have its own table; they follow one another,
+                // each ending with a null entry.
                 loop {
-                    let (code, _) = uleb128_decode(&bytes[offset..])?;
-                    if code == 0 { break; }
-                    let (abbr, size) = AbbrevDecl::from(&bytes[offset..])?;
-                    offset += size;
-                    abbrevs.push(abbr);
+                    tables.push((reader.position() as u64, abbrevs.len()));
+                    let mut codes = HashSet::new();
+                    loop {
+                        if reader.is_empty() {
+                            return Err("__debug_abbrev ends without a null entry".to_string());
+                        }
+                        let code = reader.uleb()?;
+                        if code == 0 { break; }
+                        // DIEs could only ever use one of the declarations.
+                        if !codes.insert(code) {
+                            return Err(format!("__debug_abbrev declares code {:#x} more than once", code));
+                        }
+                        abbrevs.push(AbbrevDecl::read_after_code(code, &mut reader)?);
+                    }
+                    if reader.is_empty() { break; }
                 }
                 Ok(Section::DebugAbbrev {
                     abbrevs,
+                    tables,
                 })
             },
 
-            "__debug_str" =>
+            Some(DwarfSectionKind::Str) =>
                 Ok(Section::DebugStr(DebugStr { bytes: bytes.to_vec() })),
 
-            "__debug_aranges" => {
-                let header = CUHeader::from(&bytes[0..11]);
-                let segment_size = bytes[11];
-                let tuple_size = (segment_size + 2*header.address_size) as usize;
-                // This is 16, not 12, because the header structs must be
-                // aligned to a power of two.
-                let mut offset = 16;
-                let mut tuples = vec![];
-                loop {
-                    let tuple = ArangeTuple::from(
-                        &bytes[offset..offset+tuple_size],
-                        segment_size as u64,
-                        header.address_size as u64);
-                    if tuple.is_zero() { break; }
-                    tuples.push(tuple);
-                    offset += tuple_size;
+            Some(DwarfSectionKind::LineStr) =>
+                Ok(Section::DebugLineStr(DebugStr { bytes: bytes.to_vec() })),
+
+            Some(DwarfSectionKind::Names) => Ok(Section::DebugNames {
+                indexes: names::NameIndex::parse_all(bytes, Self::find_strs(others)?)?,
+            }),
+
+            Some(DwarfSectionKind::AppleNames | DwarfSectionKind::AppleTypes
+                | DwarfSectionKind::AppleNamespace | DwarfSectionKind::AppleObjC) =>
+                Ok(Section::AppleAccel {
+                    name: name.to_string(),
+                    table: accel::AppleAccelTable::from(bytes, Self::find_strs(others)?)
+                        .map_err(|e| format!("{}: {}", name, e))?,
+                }),
+
+            Some(DwarfSectionKind::Loc) => Ok(Section::DebugLoc { contents: bytes.to_vec() }),
+
+            Some(DwarfSectionKind::StrOffsets) => Ok(Section::DebugStrOffsets { contents: bytes.to_vec() }),
+
+            Some(DwarfSectionKind::RngLists) => Ok(Section::DebugRngLists {
+                header: ListsHeader::from(bytes)?,
+                contents: bytes.to_vec(),
+            }),
+
+            Some(DwarfSectionKind::LocLists) => Ok(Section::DebugLocLists {
+                header: ListsHeader::from(bytes)?,
+                contents: bytes.to_vec(),
+            }),
+
+            Some(DwarfSectionKind::Aranges) => {
+                let mut sets = vec![];
+                let mut offset = 0;
+                while offset < bytes.len() {
+                    let set = ArangeSet::from(&bytes[offset..])
+                        .map_err(|e| format!("address range set at {:#x} in __debug_aranges: {}", offset, e))?;
+                    offset += set.header.unit_size();
+                    sets.push(set);
                 }
-                Ok(Section::DebugAranges { header, segment_size, tuples })
+                Ok(Section::DebugAranges { sets })
             }
 
-            "__debug_line" => {
-                let raw = RawDebugLine::from(bytes)?;
-                let compiled = CompiledDebugLine::from(&raw);
+            Some(DwarfSectionKind::Line) => {
+                let strs = Self::find_strs(others).unwrap_or(&[]);
+                let line_strs = Self::find_line_strs(others).unwrap_or(&[]);
+                let raw = RawDebugLine::from(bytes, strs, line_strs)?;
+                let compiled = CompiledDebugLine::from(&raw)?;
                 Ok(Section::DebugLine { raw, compiled })
             },
 
@@ -175,56 +874,121 @@ impl Section {
             }),
         }
     }
+
+    fn find_abbrevs(others: &[Section]) -> Result<AbbrevTables<'_>, String> {
+        others.iter().find_map(|sect|
+            match &sect {
+                Section::DebugAbbrev { abbrevs, tables } => Some(AbbrevTables::new(abbrevs, tables)),
+                _ => None,
+            }
+        ).ok_or("haven't parsed __debug_abbrev yet".to_string())
+    }
+
+    fn find_strs(others: &[Section]) -> Result<&[u8], String> {
+        others.iter().find_map(|sect|
+            match &sect {
+                Section::DebugStr(DebugStr{ bytes }) => Some(&bytes[..]),
+                _ => None,
+            }
+        ).ok_or("haven't parsed __debug_str yet".to_string())
+    }
+
+    fn find_line_strs(others: &[Section]) -> Result<&[u8], String> {
+        others.iter().find_map(|sect|
+            match &sect {
+                Section::DebugLineStr(DebugStr{ bytes }) => Some(&bytes[..]),
+                _ => None,
+            }
+        ).ok_or("haven't parsed __debug_line_str yet".to_string())
+    }
 }
 
 impl Display for Section {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
-            Section::DebugAbbrev { abbrevs } => {
-                write!(f, ".debug_abbrev contents:\n")?;
+            Section::DebugAbbrev { abbrevs, .. } => {
+                writeln!(f, ".debug_abbrev contents:")?;
                 for abbr in abbrevs {
-                    write!(f, "[{}] {:?} DW_CHILDREN={}\n",
+                    writeln!(f, "[{}] {:?} DW_CHILDREN={}",
                         abbr.abbrev_code, abbr.tag, abbr.has_children)?;
                     for spec in abbr.attr_specs.iter() {
                         let name = format!("{:x?}", spec.name);
-                        let form = format!("{:x?}", spec.form);
-                        write!(f, "\t{:<20} {:<20}\n", name, form)?;
+                        let form = match spec.implicit_const {
+                            Some(n) => format!("{:x?} ({})", spec.form, n),
+                            None => format!("{:x?}", spec.form),
+                        };
+                        writeln!(f, "\t{:<20} {:<20}", name, form)?;
                     }
-                    write!(f, "\n")?;
+                    writeln!(f)?;
                 }
             },
 
             Section::Unrecognized { name, contents } =>
-                println!("Unrecognized {:16} {:#x} bytes", name, contents.len()),
+                writeln!(f, "Unrecognized {:16} {:#x} bytes", name, contents.len())?,
+
+            Section::DebugLoc { contents } =>
+                writeln!(f, ".debug_loc contents: {:#x} bytes\n", contents.len())?,
+
+            Section::DebugNames { indexes } => {
+                let names: usize = indexes.iter().map(|index| index.name_count()).sum();
+                writeln!(f, ".debug_names contents: {} indexes, {} names\n", indexes.len(), names)?
+            },
+
+            Section::AppleAccel { name, table } =>
+                writeln!(f, "{} contents: {} buckets, {} hashes\n",
+                    name, table.bucket_count(), table.hash_count())?,
+
+            Section::DebugStrOffsets { contents } =>
+                writeln!(f, ".debug_str_offsets contents: {:#x} bytes\n", contents.len())?,
 
-            Section::DebugInfo { header, dies } => {
-                write!(f, ".debug_info contents:\n")?;
-                write!(f, "{}\n\n", header)?;
-                for die in dies.iter() {
-                    write!(f, "{}\n", die)?;
+            Section::DebugRngLists { header, .. } =>
+                writeln!(f, ".debug_rnglists contents:\n{}\n", header)?,
+
+            Section::DebugLocLists { header, .. } =>
+                writeln!(f, ".debug_loclists contents:\n{}\n", header)?,
+
+            Section::DebugInfo { units } => {
+                writeln!(f, ".debug_info contents:")?;
+                for unit in units.iter() {
+                    write!(f, "{}", unit)?;
+                }
+            },
+
+            Section::DebugTypes { units } => {
+                writeln!(f, ".debug_types contents:")?;
+                for unit in units.iter() {
+                    writeln!(f, "{}\n", unit.header)?;
+                    for die in unit.dies.iter() {
+                        writeln!(f, "{}", die)?;
+                    }
                 }
             },
 
-            Section::DebugStr(debug_str) => {
-                write!(f, ".debug_str contents:\n")?;
+            Section::DebugStr(debug_str) | Section::DebugLineStr(debug_str) => {
+                match self {
+                    Section::DebugStr(_) => writeln!(f, ".debug_str contents:")?,
+                    _ => writeln!(f, ".debug_line_str contents:")?,
+                }
                 match debug_str.strs() {
                     Ok(strs) => for (offset, s) in strs {
-                        write!(f, "{:#010x?}: \"{}\"\n", offset, s)?;
+                        writeln!(f, "{:#010x?}: \"{}\"", offset, s)?;
                     },
                     // TODO: Rework error checking to only break per-string.
-                    Err(err) => write!(f, "bad utf-8: {}\n", err)?,
+                    Err(err) => writeln!(f, "bad utf-8: {}", err)?,
                 }
-                write!(f, "\n")?;
+                writeln!(f)?;
             },
 
-            Section::DebugAranges{header, segment_size, tuples} => {
-                write!(f, ".debug_aranges contents:\n")?;
-                write!(f, "Address Range Header: {}, seg_size = {:#04x?}\n",
-                    header, segment_size)?;
-                for tuple in tuples.iter() {
-                    write!(f, "{}\n", tuple)?;
+            Section::DebugAranges { sets } => {
+                writeln!(f, ".debug_aranges contents:")?;
+                for set in sets.iter() {
+                    writeln!(f, "Address Range Header: {}, seg_size = {:#04x?}",
+                        set.header, set.segment_size)?;
+                    for tuple in set.tuples.iter() {
+                        writeln!(f, "{}", tuple)?;
+                    }
                 }
-                write!(f, "\n")?;
+                writeln!(f)?;
             },
 
             _ => write!(f, "{:#x?}", self)?,
@@ -239,7 +1003,7 @@ pub struct DebugStr {
 }
 impl DebugStr {
     // Returns a list of offset-string pairs.
-    pub fn strs<'a>(&'a self) -> Result<Vec<(usize, &'a str)>, Utf8Error> {
+    pub fn strs(&self) -> Result<Vec<(usize, &str)>, Utf8Error> {
         let mut strs = vec![];
         let mut start = 0;
         for (i, b) in self.bytes.iter().enumerate() {
@@ -249,32 +1013,70 @@ impl DebugStr {
                 start = i+1;
             }
         }
-        return Ok(strs);
+        Ok(strs)
     }
 }
 
+// Whether a unit uses 4-byte (32-bit DWARF) or 8-byte (64-bit DWARF)
+// lengths and section offsets. See Section 7.4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Dwarf32,
+    Dwarf64,
+}
+
+impl Format {
+    // The size in bytes of section offsets and lengths in this format.
+    pub fn offset_size(self) -> usize {
+        match self {
+            Format::Dwarf32 => 4,
+            Format::Dwarf64 => 8,
+        }
+    }
+}
+
+// Reads a 4- or 8-byte offset, depending on the format.
+fn read_offset(bytes: &[u8], format: Format) -> u64 {
+    match format {
+        Format::Dwarf32 => u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as u64,
+        Format::Dwarf64 => u64::from_ne_bytes(bytes[0..8].try_into().unwrap()),
+    }
+}
+
+// Version 5 unit types, from the unit_type header field.
+const DW_UT_COMPILE: u8 = 0x01;
+const DW_UT_PARTIAL: u8 = 0x03;
+
 // Compile Unit Header
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct CUHeader {
+    // Whether the unit is in the 32-bit or 64-bit DWARF format, as
+    // determined by the first four bytes of unit_length.
+    pub format: Format,
+
     // A 4-byte or 12-byte unsigned integer representing the length of the
     // .debug_info contribution for that compilation unit, not including the
     // length field itself. In the 32-bit DWARF format, this is a 4-byte
     // unsigned integer (which must be less than 0xfffffff0); in the 64-bit
     // DWARF format, this consists of the 4-byte value 0xffffffff followed by an
     // 8- byte unsigned integer that gives the actual length (see Section 7.4).
-    pub unit_length: u32, // NOTE: In DWARF64, this would be 0xffffffff plus 8 bytes.
+    pub unit_length: u64,
 
     // A 2-byte unsigned integer representing the version of the DWARF
-    // information for the compilation unit (see Appendix F). The value in this
-    // field is 4.
+    // information for the compilation unit (see Appendix F). See
+    // SUPPORTED_VERSIONS for the values this parser accepts.
     pub version: u16,
 
+    // The DW_UT_* kind of unit. Only version 5 headers have this field;
+    // it's None for earlier versions.
+    pub unit_type: Option<u8>,
+
     // A 4-byte or 8-byte unsigned offset into the .debug_abbrev section. This
     // offset associates the compilation unit with a particular set of debugging
     // information entry abbreviations. In the 32-bit DWARF format, this is a
     // 4-byte unsigned length; in the 64-bit DWARF format, this is an 8-byte
     // unsigned length (see Section 7.4).
-    pub debug_abbrev_offset: u32, // NOTE: In DWARF64, this would be 0xffffffff plus 8 bytes.
+    pub debug_abbrev_offset: u64,
 
     // A 1-byte unsigned integer representing the size in bytes of an address on
     // the target architecture. If the system uses segmented addressing, this
@@ -283,18 +1085,79 @@ pub struct CUHeader {
 }
 
 impl CUHeader {
-    // Consumes 11 bytes.
-    pub fn from(bytes: &[u8]) -> CUHeader {
-        let unit_length         = u32::from_ne_bytes(bytes[ 0.. 4].try_into().unwrap());
-        let version             = u16::from_ne_bytes(bytes[ 4.. 6].try_into().unwrap());
-        let debug_abbrev_offset = u32::from_ne_bytes(bytes[ 6.. 10].try_into().unwrap());
-        let address_size        = bytes[10];
-        CUHeader {
+    // The DWARF versions whose layouts this parser understands. The header
+    // itself changed shape in version 5, so a later one can't be read at all.
+    pub const SUPPORTED_VERSIONS: std::ops::RangeInclusive<u16> = 2..=5;
+
+    // Consumes 11 bytes in 32-bit DWARF, or 23 bytes in 64-bit DWARF. Version
+    // 5 headers are a byte longer.
+    pub fn from(bytes: &[u8]) -> Result<CUHeader, String> {
+        if bytes.len() < 4 {
+            return Err("ran out of bytes reading unit_length".to_string());
+        }
+        let (format, length_size) = match u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) {
+            0xffffffff => (Format::Dwarf64, 12),
+            n @ 0xfffffff0..=0xfffffffe =>
+                return Err(format!("unit_length {:#010x} is reserved", n)),
+            _ => (Format::Dwarf32, 4),
+        };
+        let mut size = length_size + 2 + format.offset_size() + 1;
+        if bytes.len() < length_size + 2 {
+            return Err(format!("unit header needs {} bytes, but only {} remain", size, bytes.len()));
+        }
+        let version = u16::from_ne_bytes(bytes[length_size..length_size+2].try_into().unwrap());
+        if !Self::SUPPORTED_VERSIONS.contains(&version) {
+            return Err(format!("DWARF version {} isn't supported (only versions {} to {} are)",
+                version, Self::SUPPORTED_VERSIONS.start(), Self::SUPPORTED_VERSIONS.end()));
+        }
+        if version >= 5 {
+            size += 1;
+        }
+        if bytes.len() < size {
+            return Err(format!("unit header needs {} bytes, but only {} remain", size, bytes.len()));
+        }
+        let unit_length = match format {
+            Format::Dwarf32 => u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as u64,
+            Format::Dwarf64 => u64::from_ne_bytes(bytes[4..12].try_into().unwrap()),
+        };
+        let bytes = &bytes[length_size+2..];
+        // Version 5 adds unit_type, and moves address_size ahead of
+        // debug_abbrev_offset.
+        let (unit_type, debug_abbrev_offset, address_size) = if version >= 5 {
+            (Some(bytes[0]), read_offset(&bytes[2..], format), bytes[1])
+        } else {
+            (None, read_offset(bytes, format), bytes[format.offset_size()])
+        };
+        match address_size {
+            1 | 2 | 4 | 8 => {},
+            _ => return Err(format!("bad address_size in unit header: {}", address_size)),
+        }
+        Ok(CUHeader {
+            format,
             unit_length,
             version,
+            unit_type,
             debug_abbrev_offset,
             address_size,
-        }
+        })
+    }
+
+    // The size in bytes of the header itself.
+    pub fn size(&self) -> usize {
+        let size = match self.format {
+            Format::Dwarf32 => 11,
+            Format::Dwarf64 => 23,
+        };
+        size + self.unit_type.is_some() as usize
+    }
+
+    // The size in bytes of the whole unit, including the unit_length field.
+    pub fn unit_size(&self) -> usize {
+        let length_size: usize = match self.format {
+            Format::Dwarf32 => 4,
+            Format::Dwarf64 => 12,
+        };
+        length_size.saturating_add(self.unit_length as usize)
     }
 }
 
@@ -305,30 +1168,412 @@ impl Display for CUHeader {
     }
 }
 
-// Debugging Information Entry
+// Type Unit Header, found at the start of each unit in .debug_types.
 #[derive(Debug)]
-pub struct DIE {
-    pub tag: DIETag,
-    pub attrs: Vec<DIEAttribute>,
-    pub children: Vec<DIE>,
-}
+pub struct TypeUnitHeader {
+    // The fields shared with compilation unit headers.
+    pub header: CUHeader,
 
-impl DIE {
-    pub fn from(
-        bytes: &[u8],
-        abbrev_decls: &Vec<AbbrevDecl>,
-        strdata: &[u8],
-    ) -> Result<(DIE, usize), String> {
-        let (abbr_code, size) = uleb128_decode(bytes)?;
-        let decl = abbrev_decls.iter().find(|decl| decl.abbrev_code == abbr_code)
-            .ok_or_else(|| format!("found no abbrev matching code: {:#x?}", abbr_code))?;
-        let mut offset = size;
+    // A unique 8-byte signature of the type described in this type unit,
+    // used by DW_FORM_ref_sig8 references.
+    pub type_signature: u64,
 
-        // TODO: Parse the attributes of this DIE.
-        let mut attrs: Vec<DIEAttribute> = vec![];
-        for spec in decl.attr_specs.iter() {
-            let (value, size) = AttrValue::from(&bytes[offset..], spec.form.clone(), strdata)?;
-            offset += size;
+    // The offset, relative to the start of the type unit header, of the
+    // DIE that describes the type. In the 32-bit DWARF format this is 4
+    // bytes; in the 64-bit DWARF format it is 8 bytes.
+    pub type_offset: u64,
+}
+
+impl TypeUnitHeader {
+    // Consumes 23 bytes in 32-bit DWARF, or 39 bytes in 64-bit DWARF.
+    pub fn from(bytes: &[u8]) -> Result<TypeUnitHeader, String> {
+        let header = CUHeader::from(bytes)?;
+        let offset = header.size();
+        if bytes.len() < offset + 8 + header.format.offset_size() {
+            return Err("__debug_types is too short for a type unit header".to_string());
+        }
+        let type_signature = u64::from_ne_bytes(bytes[offset..offset+8].try_into().unwrap());
+        let type_offset    = read_offset(&bytes[offset+8..], header.format);
+        Ok(TypeUnitHeader {
+            header,
+            type_signature,
+            type_offset,
+        })
+    }
+
+    // The size in bytes of the header itself.
+    pub fn size(&self) -> usize {
+        self.header.size() + 8 + self.header.format.offset_size()
+    }
+}
+
+// A unit from __debug_types: its header and the tree of DIEs describing
+// the type.
+#[derive(Debug)]
+pub struct TypeUnit {
+    // Where the unit starts in __debug_types.
+    pub offset: usize,
+    pub header: TypeUnitHeader,
+    pub dies: Vec<DIE>,
+}
+
+impl TypeUnit {
+    // Parses the unit starting `offset` bytes into `section`, as
+    // `CompilationUnit::from` does for __debug_info.
+    pub fn from(
+        section: &[u8],
+        offset: usize,
+        abbrevs: &AbbrevTables,
+        strdata: &[u8],
+    ) -> Result<TypeUnit, String> {
+        let header = TypeUnitHeader::from(section.get(offset..).unwrap_or(&[]))?;
+        let unit_size = header.header.unit_size();
+        if unit_size < header.size() {
+            return Err(format!("type unit at {:#x} is {:#x} bytes, too short for its {:#x}-byte header",
+                offset, unit_size, header.size()));
+        }
+        let end = offset.checked_add(unit_size)
+            .filter(|&end| end <= section.len())
+            .ok_or_else(|| format!("type unit at {:#x} runs past end of __debug_types ({:#x} bytes from {:#x})",
+                offset, unit_size, section.len()))?;
+        let bytes = &section[offset..end];
+        let abbrevs = abbrevs.get(header.header.debug_abbrev_offset)?;
+        let (die, _) = DIE::from_at_offset(&bytes[header.size()..], header.size(), header.header.format,
+            header.header.address_size, abbrevs, strdata)?;
+        Ok(TypeUnit { offset, header, dies: vec![die] })
+    }
+}
+
+impl Display for TypeUnitHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}, type_signature = {:#018x?}, type_offset = {:#010x?}",
+            self.header, self.type_signature, self.type_offset)
+    }
+}
+
+// One difference found by `File::diff`. DIEs are identified by their tag
+// and name, along with the name of the unit they're in.
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    AddedUnit(String),
+    RemovedUnit(String),
+    AddedDIE { unit: String, tag: DIETag, name: String },
+    RemovedDIE { unit: String, tag: DIETag, name: String },
+    ChangedAttr {
+        unit: String,
+        tag: DIETag,
+        name: String,
+        attr: AttrName,
+        old: Option<AttrValue>,
+        new: Option<AttrValue>,
+    },
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Difference::AddedUnit(name) => write!(f, "+ unit {}", name),
+            Difference::RemovedUnit(name) => write!(f, "- unit {}", name),
+            Difference::AddedDIE { unit, tag, name } =>
+                write!(f, "+ {}: {} {}", unit, tag, name),
+            Difference::RemovedDIE { unit, tag, name } =>
+                write!(f, "- {}: {} {}", unit, tag, name),
+            Difference::ChangedAttr { unit, tag, name, attr, old, new } =>
+                write!(f, "~ {}: {} {}: {} {:x?} -> {:x?}",
+                    unit, tag, name, attr, old, new),
+        }
+    }
+}
+
+type DIEKey<'a> = (DIETag, &'a str);
+
+fn die_key(die: &DIE) -> Option<DIEKey<'_>> {
+    let name = die.attr(&AttrName::Name)?.as_str()?;
+    Some((die.tag, name))
+}
+
+fn units_by_name(file: &File) -> BTreeMap<DIEKey<'_>, &DIE> {
+    let mut units = BTreeMap::new();
+    for unit in file.roots() {
+        let key = die_key(unit).unwrap_or((unit.tag, ""));
+        units.entry(key).or_insert(unit);
+    }
+    units
+}
+
+fn named_dies(unit: &DIE) -> BTreeMap<DIEKey<'_>, &DIE> {
+    let mut dies = BTreeMap::new();
+    for die in DIEIter::new(&unit.children) {
+        if let Some(key) = die_key(die) {
+            dies.entry(key).or_insert(die);
+        }
+    }
+    dies
+}
+
+fn diff_units(unit: &DIE, other: &DIE, diffs: &mut Vec<Difference>) {
+    let unit_name = die_key(unit).map_or("", |(_, name)| name);
+    diff_attrs(unit_name, unit, other, diffs);
+    let ours = named_dies(unit);
+    let theirs = named_dies(other);
+    for (&(tag, name), die) in ours.iter() {
+        match theirs.get(&(tag, name)) {
+            Some(other_die) => diff_attrs(unit_name, die, other_die, diffs),
+            None => diffs.push(Difference::RemovedDIE {
+                unit: unit_name.to_string(),
+                tag,
+                name: name.to_string(),
+            }),
+        }
+    }
+    for &(tag, name) in theirs.keys().filter(|key| !ours.contains_key(key)) {
+        diffs.push(Difference::AddedDIE {
+            unit: unit_name.to_string(),
+            tag,
+            name: name.to_string(),
+        });
+    }
+}
+
+fn diff_attrs(unit_name: &str, die: &DIE, other: &DIE, diffs: &mut Vec<Difference>) {
+    let (tag, name) = die_key(die).unwrap_or((die.tag, ""));
+    let changed = |attr: &AttrName, old: Option<&AttrValue>, new: Option<&AttrValue>| {
+        Difference::ChangedAttr {
+            unit: unit_name.to_string(),
+            tag,
+            name: name.to_string(),
+            attr: attr.clone(),
+            old: old.cloned(),
+            new: new.cloned(),
+        }
+    };
+    for attr in die.attrs.iter() {
+        let new = other.attr(&attr.name);
+        if new != Some(&attr.value) {
+            diffs.push(changed(&attr.name, Some(&attr.value), new));
+        }
+    }
+    for attr in other.attrs.iter().filter(|attr| die.attr(&attr.name).is_none()) {
+        diffs.push(changed(&attr.name, None, Some(&attr.value)));
+    }
+}
+
+// The header of a DWARF 5 .debug_rnglists or .debug_loclists section.
+#[derive(Debug)]
+pub struct ListsHeader {
+    pub format: Format,
+
+    // The length of this contribution, not including the length field.
+    pub unit_length: u64,
+
+    pub version: u16,
+    pub address_size: u8,
+    pub segment_selector_size: u8,
+
+    // The number of entries in the offsets array that follows the header.
+    // Each is the offset of a list, from the start of the array.
+    pub offset_entry_count: u32,
+}
+
+impl ListsHeader {
+    pub fn from(bytes: &[u8]) -> Result<ListsHeader, String> {
+        let mut reader = Reader::new(bytes);
+        let (format, unit_length) = match reader.u32()? {
+            0xffffffff => (Format::Dwarf64, reader.u64()?),
+            n @ 0xfffffff0..=0xfffffffe =>
+                return Err(format!("unit_length {:#010x} is reserved", n)),
+            n => (Format::Dwarf32, n as u64),
+        };
+        Ok(ListsHeader {
+            format,
+            unit_length,
+            version: reader.u16()?,
+            address_size: reader.u8()?,
+            segment_selector_size: reader.u8()?,
+            offset_entry_count: reader.u32()?,
+        })
+    }
+
+    // The size in bytes of the header itself.
+    pub fn size(&self) -> usize {
+        match self.format {
+            Format::Dwarf32 => 12,
+            Format::Dwarf64 => 20,
+        }
+    }
+
+    // Finds `unit`'s list `index` in `contents`, returning the header of
+    // the contribution it's in and where the list starts. `base_attr`, the
+    // unit's DW_AT_rnglists_base or DW_AT_loclists_base, is the offset of
+    // the contribution's offsets array; without one, the unit uses the
+    // first contribution.
+    fn find_list(
+        contents: &[u8], unit: &CompilationUnit, base_attr: AttrName, index: u64,
+    ) -> Result<(ListsHeader, usize), String> {
+        let header_size = match unit.header.format {
+            Format::Dwarf32 => 12,
+            Format::Dwarf64 => 20,
+        };
+        let base = unit.dies.first()
+            .and_then(|root| root.attr(&base_attr))
+            .and_then(AttrValue::as_u64)
+            .unwrap_or(header_size as u64);
+        let start = usize::try_from(base).ok()
+            .and_then(|base| base.checked_sub(header_size))
+            .ok_or_else(|| format!("list base {:#x} leaves no room for a header", base))?;
+        let header = ListsHeader::from(contents.get(start..).unwrap_or(&[]))
+            .map_err(|e| format!("lists header at {:#x}: {}", start, e))?;
+        let offset = header.list_offset(contents, start, index)?;
+        Ok((header, offset))
+    }
+
+    // Finds where list `index` starts in `contents` by way of the offsets
+    // array after the header, which is `start` bytes in.
+    fn list_offset(&self, contents: &[u8], start: usize, index: u64) -> Result<usize, String> {
+        if index >= self.offset_entry_count as u64 {
+            return Err(format!("list index {} is out of range; there are {} offsets",
+                index, self.offset_entry_count));
+        }
+        let base = start + self.size();
+        let entry = base + index as usize * self.format.offset_size();
+        if entry + self.format.offset_size() > contents.len() {
+            return Err(format!("offset entry {} runs past end of section", index));
+        }
+        let offset = usize::try_from(read_offset(&contents[entry..], self.format)).ok()
+            .and_then(|offset| offset.checked_add(base))
+            .filter(|&offset| offset < contents.len())
+            .ok_or_else(|| format!("list {} is past end of section", index))?;
+        Ok(offset)
+    }
+}
+
+impl Display for ListsHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "length = {:#010x?}, version = {:#06x?}, address_size = {:#04x?}, offset_entry_count = {}",
+            self.unit_length, self.version, self.address_size, self.offset_entry_count)
+    }
+}
+
+// Reads the entries of one range list (DW_RLE_*) or location list
+// (DW_LLE_*), until the end_of_list entry. Range entries come back with an
+// empty expression. Entries that index into .debug_addr aren't supported.
+// Offsets wrap around the address space, as in `location_list`.
+fn read_list_entries(
+    bytes: &[u8], address_size: u8, base_addr: u64, locations: bool
+) -> Result<Vec<(u64, u64, Vec<u8>)>, String> {
+    let mut reader = Reader::new(bytes);
+    let read_address = |reader: &mut Reader| match address_size {
+        4 => reader.u32().map(|x| x as u64),
+        _ => reader.u64(),
+    };
+    let mut base = base_addr;
+    let mut entries = vec![];
+    loop {
+        let code = reader.u8()?;
+        // Location lists have DW_LLE_default_location at 5, which pushes
+        // the kinds after it up by one relative to range lists.
+        let kind = match (locations, code) {
+            (true, 5) => {
+                let len = reader.uleb()? as usize;
+                entries.push((0, u64::MAX, reader.bytes(len)?.to_vec()));
+                continue;
+            },
+            (true, 6..) => code - 1,
+            _ => code,
+        };
+        let (begin, end) = match kind {
+            0 => break,
+            1..=3 => return Err(format!(
+                "list entry kind {:#x} needs .debug_addr, which isn't supported", code)),
+            4 => {
+                let begin = reader.uleb()?;
+                let end = reader.uleb()?;
+                (base.wrapping_add(begin), base.wrapping_add(end))
+            },
+            5 => {
+                base = read_address(&mut reader)?;
+                continue;
+            },
+            6 => (read_address(&mut reader)?, read_address(&mut reader)?),
+            7 => {
+                let begin = read_address(&mut reader)?;
+                (begin, begin.wrapping_add(reader.uleb()?))
+            },
+            _ => return Err(format!("unknown list entry kind {:#x}", code)),
+        };
+        let expr = if locations {
+            let len = reader.uleb()? as usize;
+            reader.bytes(len)?.to_vec()
+        } else {
+            vec![]
+        };
+        entries.push((begin, end, expr));
+    }
+    Ok(entries)
+}
+
+// Debugging Information Entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct DIE {
+    // Where the DIE starts, relative to the start of its unit. References
+    // like DW_FORM_ref4 are given as these offsets.
+    pub offset: usize,
+    pub tag: DIETag,
+    pub attrs: Vec<DIEAttribute>,
+    pub children: Vec<DIE>,
+}
+
+impl DIE {
+    // How deeply DIEs may nest before parsing gives up. Real trees are
+    // rarely more than a few dozen levels deep; this keeps a malformed
+    // one from overflowing the stack.
+    pub const MAX_DEPTH: usize = 256;
+
+    pub fn from(
+        bytes: &[u8],
+        format: Format,
+        address_size: u8,
+        abbrevs: &AbbrevTable,
+        strdata: &[u8],
+    ) -> Result<(DIE, usize), String> {
+        Self::from_at_depth(bytes, 0, format, address_size, abbrevs, strdata, 0)
+    }
+
+    // Like `from`, for a DIE that starts `offset` bytes into its unit.
+    pub fn from_at_offset(
+        bytes: &[u8],
+        offset: usize,
+        format: Format,
+        address_size: u8,
+        abbrevs: &AbbrevTable,
+        strdata: &[u8],
+    ) -> Result<(DIE, usize), String> {
+        Self::from_at_depth(bytes, offset, format, address_size, abbrevs, strdata, 0)
+    }
+
+    fn from_at_depth(
+        bytes: &[u8],
+        offset: usize,
+        format: Format,
+        address_size: u8,
+        abbrevs: &AbbrevTable,
+        strdata: &[u8],
+        depth: usize,
+    ) -> Result<(DIE, usize), String> {
+        if depth > Self::MAX_DEPTH {
+            return Err(format!("DIE tree nested deeper than {} levels", Self::MAX_DEPTH));
+        }
+        let mut reader = Reader::new(bytes);
+        let abbr_code = reader.uleb()?;
+        let decl = abbrevs.get(abbr_code)
+            .ok_or_else(|| format!("found no abbrev matching code: {:#x?}", abbr_code))?;
+
+        let mut attrs: Vec<DIEAttribute> = vec![];
+        for spec in decl.attr_specs.iter() {
+            let value = match spec.implicit_const {
+                Some(n) => AttrValue::ImplicitConst(n),
+                None => AttrValue::read(&mut reader, spec.form.clone(), format, address_size, strdata)?,
+            };
             attrs.push(DIEAttribute {
                 name: spec.name.clone(),
                 value,
@@ -336,24 +1581,122 @@ impl DIE {
         }
 
         let children = if decl.has_children {
-            let (children, size) = Self::nfrom(&bytes[offset..], abbrev_decls, strdata)?;
-            offset += size;
+            let (children, size) = Self::nfrom_at_depth(
+                reader.rest(), offset + reader.position(), format, address_size, abbrevs, strdata, depth + 1)?;
+            reader.skip(size)?;
             children
         } else { vec![] };
         Ok((
             DIE {
+                offset,
                 tag: decl.tag,
                 attrs,
                 children,
             },
-            offset,
+            reader.position(),
         ))
     }
 
+    // The value of the first attribute called `name`, if there is one.
+    pub fn attr(&self, name: &AttrName) -> Option<&AttrValue> {
+        self.attrs.iter()
+            .find(|attr| attr.name == *name)
+            .map(|attr| &attr.value)
+    }
+
+    // Like `attr`, but when the DIE lacks the attribute, looks for it on
+    // the declaration named by DW_AT_specification or DW_AT_abstract_origin,
+    // and so on up the chain. Out-of-line definitions and inlined copies
+    // keep things like the name there.
+    pub fn resolved_attr<'a>(&'a self, cu: &'a CompilationUnit, name: &AttrName) -> Option<&'a AttrValue> {
+        let mut die = self;
+        let mut visited = vec![];
+        loop {
+            if let Some(value) = die.attr(name) {
+                return Some(value);
+            }
+            // Stop on a reference cycle.
+            if visited.contains(&die.offset) {
+                return None;
+            }
+            visited.push(die.offset);
+            let origin = die.attr(&AttrName::Specification)
+                .or_else(|| die.attr(&AttrName::AbstractOrigin))?;
+            die = cu.resolve(origin)?;
+        }
+    }
+
+    // The DW_AT_address_class of a pointer, reference, subprogram or
+    // variable. On targets with segmented addressing this says what kind
+    // of address the entity uses; the unit header's address_size covers
+    // only the offset part, and any segment selector comes from DW_AT_segment.
+    // The meaning of each class is up to the target's ABI, with 0 (DW_ADDR_none)
+    // being a plain flat address.
+    pub fn address_class(&self) -> Option<u64> {
+        self.attr(&AttrName::AddressClass)?.as_u64()
+    }
+
+    // The DW_AT_segment selector, when it's a constant.
+    pub fn segment(&self) -> Option<u64> {
+        self.attr(&AttrName::Segment)?.as_u64()
+    }
+
+    // Where a member starts within its struct or class, from
+    // DW_AT_data_member_location. That's either a constant or, as older
+    // compilers write it, an expression adding the offset to the
+    // struct's address. Other expressions, like those for virtual base
+    // classes, give None.
+    pub fn member_offset(&self) -> Option<u64> {
+        let location = self.attr(&AttrName::DataMemberLocation)?;
+        if let Some(offset) = location.as_u64() {
+            return Some(offset);
+        }
+        // DW_OP_plus_uconst doesn't read an address, so the size is moot.
+        match location.as_expression(8)?.ok()?[..] {
+            [expr::Op::PlusUconst(offset)] => Some(offset),
+            _ => None,
+        }
+    }
+
+    // The source language of a compile unit.
+    pub fn language(&self) -> Option<DwLang> {
+        if self.tag != DIETag::CompileUnit {
+            return None;
+        }
+        Some(DwLang::from(self.attr(&AttrName::Language)?.as_u64()?))
+    }
+
+    // How a base type's value is encoded. Together with byte_size, this is
+    // enough to tell e.g. an i32 from a u32 or an f32.
+    pub fn encoding(&self) -> Option<DwAte> {
+        if self.tag != DIETag::BaseType {
+            return None;
+        }
+        Some(DwAte::from(self.attr(&AttrName::Encoding)?.as_u64()?))
+    }
+
+    pub fn byte_size(&self) -> Option<u64> {
+        self.attr(&AttrName::ByteSize)?.as_u64()
+    }
+
     pub fn nfrom(
         bytes: &[u8],
-        abbrev_decls: &Vec<AbbrevDecl>,
+        format: Format,
+        address_size: u8,
+        abbrevs: &AbbrevTable,
+        strdata: &[u8],
+    ) -> Result<(Vec<DIE>, usize), String> {
+        Self::nfrom_at_depth(bytes, 0, format, address_size, abbrevs, strdata, 0)
+    }
+
+    fn nfrom_at_depth(
+        bytes: &[u8],
+        base: usize,
+        format: Format,
+        address_size: u8,
+        abbrevs: &AbbrevTable,
         strdata: &[u8],
+        depth: usize,
     ) -> Result<(Vec<DIE>, usize), String> {
         let mut dies = vec![];
         let mut offset = 0;
@@ -363,7 +1706,8 @@ impl DIE {
                 offset += size;
                 break;
             }
-            let (die, size) = Self::from(&bytes[offset..], abbrev_decls, strdata)?;
+            let (die, size) = Self::from_at_depth(
+                &bytes[offset..], base + offset, format, address_size, abbrevs, strdata, depth)?;
             dies.push(die);
             offset += size;
         }
@@ -371,13 +1715,294 @@ impl DIE {
     }
 }
 
-impl Display for DIE {
+// A unit from __debug_info: its header and the tree of DIEs after it.
+#[derive(Debug, PartialEq)]
+pub struct CompilationUnit {
+    // Where the unit starts in __debug_info.
+    pub offset: usize,
+    pub header: CUHeader,
+    pub dies: Vec<DIE>,
+    // Where, relative to `offset`, parsing the DIEs stopped. Anything
+    // between here and the end of the unit went unread.
+    pub dies_end: usize,
+}
+
+// Where a unit sits in __debug_info, as read from its header alone.
+#[derive(Debug, PartialEq)]
+pub struct CuIndexEntry {
+    pub offset: usize,
+    // The header's unit_length, which doesn't count the length field.
+    pub length: u64,
+    pub version: u16,
+}
+
+impl CompilationUnit {
+    // Parses the unit starting `offset` bytes into `section`.
+    pub fn from(
+        section: &[u8],
+        offset: usize,
+        abbrevs: &AbbrevTables,
+        strdata: &[u8],
+    ) -> Result<CompilationUnit, String> {
+        let header = CUHeader::from(section.get(offset..).unwrap_or(&[]))?;
+        // Other kinds of version 5 unit have more header fields, which
+        // aren't read yet.
+        match header.unit_type {
+            None | Some(DW_UT_COMPILE) | Some(DW_UT_PARTIAL) => {},
+            Some(unit_type) =>
+                return Err(format!("unit at {:#x} has unsupported unit_type {:#x}", offset, unit_type)),
+        }
+        if header.unit_size() < header.size() {
+            return Err(format!("unit at {:#x} is {:#x} bytes, too short for its {:#x}-byte header",
+                offset, header.unit_size(), header.size()));
+        }
+        let end = offset.checked_add(header.unit_size())
+            .filter(|&end| end <= section.len())
+            .ok_or_else(|| format!("unit at {:#x} runs past end of __debug_info ({:#x} bytes from {:#x})",
+                offset, header.unit_size(), section.len()))?;
+        let bytes = &section[offset..end];
+        let abbrevs = abbrevs.get(header.debug_abbrev_offset)?;
+        let (die, size) =
+            DIE::from_at_offset(
+                &bytes[header.size()..], header.size(), header.format, header.address_size, abbrevs, strdata)?;
+        let dies_end = header.size() + size;
+        Ok(CompilationUnit { offset, header, dies: vec![die], dies_end })
+    }
+
+    // Reads each unit's header in `section`, stepping from one to the next
+    // by unit_length, without decoding any DIEs.
+    pub fn index(section: &[u8]) -> Result<Vec<CuIndexEntry>, String> {
+        let mut entries = vec![];
+        let mut offset = 0;
+        while offset < section.len() {
+            let header = CUHeader::from(&section[offset..])
+                .map_err(|e| format!("unit at {:#x} in __debug_info: {}", offset, e))?;
+            entries.push(CuIndexEntry { offset, length: header.unit_length, version: header.version });
+            offset += header.unit_size();
+        }
+        Ok(entries)
+    }
+
+    // Finds where each unit in `section` starts, reading only their headers.
+    pub fn offsets(section: &[u8]) -> Result<Vec<usize>, String> {
+        Ok(Self::index(section)?.into_iter().map(|entry| entry.offset).collect())
+    }
+
+    // Parses the units starting at each of `offsets`, in turn.
+    pub fn parse_all(
+        section: &[u8],
+        offsets: &[usize],
+        abbrevs: &AbbrevTables,
+        strdata: &[u8],
+    ) -> Result<Vec<CompilationUnit>, String> {
+        offsets.iter()
+            .map(|&offset| CompilationUnit::from(section, offset, abbrevs, strdata))
+            .collect()
+    }
+
+    // Like `parse_all`, but splits the units between threads. Units only
+    // read the sections they share, so the threads don't contend; the
+    // results, and the first error, come back in order.
+    pub fn parse_all_parallel(
+        section: &[u8],
+        offsets: &[usize],
+        abbrevs: &AbbrevTables,
+        strdata: &[u8],
+    ) -> Result<Vec<CompilationUnit>, String> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = offsets.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = offsets.chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || Self::parse_all(section, chunk, abbrevs, strdata)))
+                .collect();
+            let mut units = Vec::with_capacity(offsets.len());
+            for handle in handles {
+                units.extend(handle.join().map_err(|_| "unit parsing thread panicked".to_string())??);
+            }
+            Ok(units)
+        })
+    }
+
+    pub fn version(&self) -> u16 {
+        self.header.version
+    }
+
+    // The path of the unit's primary source file, from DW_AT_name.
+    pub fn name(&self) -> Option<&str> {
+        self.root_str(AttrName::Name)
+    }
+
+    // The directory the unit was compiled in, from DW_AT_comp_dir.
+    // Relative paths elsewhere in the unit are relative to it.
+    pub fn comp_dir(&self) -> Option<&str> {
+        self.root_str(AttrName::CompDir)
+    }
+
+    // A string attribute of the unit's root DIE.
+    fn root_str(&self, name: AttrName) -> Option<&str> {
+        let root = self.dies.first()?;
+        if !matches!(root.tag, DIETag::CompileUnit | DIETag::PartialUnit) {
+            return None;
+        }
+        root.attr(&name)?.as_str()
+    }
+
+    // The address just past the end of a DIE's code. Before version 4,
+    // DW_AT_high_pc is always an address; from version 4 on, a constant
+    // is an offset from DW_AT_low_pc.
+    pub fn high_pc(&self, die: &DIE) -> Option<u64> {
+        match die.attr(&AttrName::HighPc)? {
+            AttrValue::Address(x) => Some(*x),
+            AttrValue::Constant(x) if self.version() >= 4 =>
+                die.attr(&AttrName::LowPc)?.as_u64()?.checked_add(*x),
+            AttrValue::Constant(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    // Iterates depth-first over the unit's DIEs.
+    pub fn dies(&self) -> DIEIter<'_> {
+        DIEIter::new(&self.dies)
+    }
+
+    // Finds the DIE at a unit-relative offset.
+    fn die_at(&self, offset: u64) -> Option<&DIE> {
+        self.dies().find(|die| die.offset as u64 == offset)
+    }
+
+    // Finds the DIE a reference attribute points at. Only references
+    // within this unit (DW_FORM_ref1 through ref8 and ref_udata) resolve.
+    pub fn resolve(&self, reference: &AttrValue) -> Option<&DIE> {
+        match reference {
+            AttrValue::OffsetReference(offset) => self.die_at(*offset),
+            _ => None,
+        }
+    }
+
+    // Renders the type of `die` as C would spell it, such as
+    // `const char *`. If `die` is itself a type, that type is named;
+    // otherwise the type in its DW_AT_type is. Returns None if a type
+    // along the way can't be found or named.
+    pub fn type_name(&self, die: &DIE) -> Option<String> {
+        if Self::is_type(die.tag) {
+            self.name_type(Some(die), 0)
+        } else {
+            self.name_type(Some(self.type_of(die)?), 0)
+        }
+    }
+
+    // How many DW_AT_type links type_name follows before deciding the
+    // types form a cycle.
+    const MAX_TYPE_DEPTH: usize = 64;
+
+    fn is_type(tag: DIETag) -> bool {
+        matches!(tag,
+            DIETag::BaseType | DIETag::PointerType | DIETag::ReferenceType
+            | DIETag::RvalueReferenceType | DIETag::ConstType | DIETag::VolatileType
+            | DIETag::Typedef | DIETag::StructureType | DIETag::ClassType
+            | DIETag::UnionType | DIETag::EnumerationType | DIETag::ArrayType
+            | DIETag::SubroutineType | DIETag::UnspecifiedType)
+    }
+
+    // The DIE named by `die`'s DW_AT_type, if any.
+    fn type_of(&self, die: &DIE) -> Option<&DIE> {
+        self.resolve(die.attr(&AttrName::Type)?)
+    }
+
+    // Names a type, where None stands for void.
+    fn name_type(&self, die: Option<&DIE>, depth: usize) -> Option<String> {
+        if depth > Self::MAX_TYPE_DEPTH {
+            return None;
+        }
+        let Some(die) = die else { return Some("void".to_string()) };
+        let name = || die.attr(&AttrName::Name).and_then(|name| name.as_str());
+        let inner = || self.name_type(
+            if die.attr(&AttrName::Type).is_some() { Some(self.type_of(die)?) } else { None },
+            depth + 1);
+        Some(match die.tag {
+            DIETag::BaseType | DIETag::Typedef | DIETag::UnspecifiedType =>
+                name()?.to_string(),
+            DIETag::StructureType => format!("struct {}", name().unwrap_or("<anonymous>")),
+            DIETag::ClassType => format!("class {}", name().unwrap_or("<anonymous>")),
+            DIETag::UnionType => format!("union {}", name().unwrap_or("<anonymous>")),
+            DIETag::EnumerationType => format!("enum {}", name().unwrap_or("<anonymous>")),
+            DIETag::PointerType => format!("{} *", inner()?),
+            DIETag::ReferenceType => format!("{} &", inner()?),
+            DIETag::RvalueReferenceType => format!("{} &&", inner()?),
+            DIETag::ArrayType => format!("{}[]", inner()?),
+            DIETag::SubroutineType => format!("{} ()", inner()?),
+            DIETag::ConstType | DIETag::VolatileType => {
+                let qualifier = if die.tag == DIETag::ConstType { "const" } else { "volatile" };
+                let inner = inner()?;
+                // Qualifiers on a pointer go after the `*`.
+                if inner.ends_with('*') {
+                    format!("{} {}", inner, qualifier)
+                } else {
+                    format!("{} {}", qualifier, inner)
+                }
+            },
+            _ => return None,
+        })
+    }
+}
+
+impl Display for CompilationUnit {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "DW_TAG_{:?}\n", self.tag)?;
+        writeln!(f, "{}\n", self.header)?;
+        for die in self.dies.iter() {
+            writeln!(f, "{}", die)?;
+        }
+        Ok(())
+    }
+}
+
+// Walks a forest of DIEs depth-first, parents before their children.
+pub struct DIEIter<'a> {
+    stack: Vec<&'a DIE>,
+}
+
+impl<'a> DIEIter<'a> {
+    pub fn new(roots: impl IntoIterator<Item = &'a DIE>) -> DIEIter<'a> {
+        let mut stack: Vec<&DIE> = roots.into_iter().collect();
+        stack.reverse();
+        DIEIter { stack }
+    }
+}
+
+impl<'a> Iterator for DIEIter<'a> {
+    type Item = &'a DIE;
+
+    fn next(&mut self) -> Option<&'a DIE> {
+        let die = self.stack.pop()?;
+        self.stack.extend(die.children.iter().rev());
+        Some(die)
+    }
+}
+
+impl DIE {
+    // The DIE's tag and then its attributes, a line each, leaving out its
+    // children.
+    pub fn describe(&self) -> String {
+        let mut out = format!("{}\n", self.tag);
         for attr in self.attrs.iter() {
-            let name = format!("{:x?}", attr.name);
-            write!(f, "\tDW_AT_{:<20} {:x?}\n", name, attr.value)?;
+            let name = attr.name.to_string();
+            match &attr.value {
+                // Expressions read better as the bytes a disassembler
+                // would show; as_expression decodes them.
+                AttrValue::ExprLoc(bytes) => {
+                    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                    writeln!(out, "\t{:<26} {}", name, hex.join(" ")).unwrap();
+                },
+                value => writeln!(out, "\t{:<26} {:x?}", name, value).unwrap(),
+            }
         }
+        out
+    }
+}
+
+impl Display for DIE {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.describe())?;
         for child in self.children.iter() {
             write!(f, "\n{}", child)?;
         }
@@ -385,7 +2010,7 @@ impl Display for DIE {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum DIETag {
     ArrayType,
     ClassType,
@@ -449,157 +2074,451 @@ pub enum DIETag {
     TemplateAlias,
     LoUser,
     HiUser,
+    // A tag this parser doesn't know: vendor-defined, between LoUser and
+    // HiUser, or from a later version of DWARF.
+    Unknown(u64),
 }
 
 impl DIETag {
+    // Looks up a tag by the name the spec gives it, with or without the
+    // DW_TAG_ prefix and in any case: "subprogram" or "DW_TAG_base_type".
+    pub fn from_name(name: &str) -> Option<DIETag> {
+        let name = strip_prefix_ignore_case(name, "DW_TAG_");
+        (0..=0xffff)
+            .map(DIETag::from)
+            .filter(|tag| !matches!(tag, DIETag::Unknown(_)))
+            .find(|tag| snake_case(&format!("{:?}", tag)).eq_ignore_ascii_case(name))
+    }
+
     // TODO: this u16 is the output of LEB128 decoding. Arguably should be
     // size-invariant.
-    pub fn from(value: u64) -> Result<DIETag, String> {
+    pub fn from(value: u64) -> DIETag {
         match value {
-           0x01   => Ok(DIETag::ArrayType),
-           0x02   => Ok(DIETag::ClassType),
-           0x03   => Ok(DIETag::EntryPoint),
-           0x04   => Ok(DIETag::EnumerationType),
-           0x05   => Ok(DIETag::FormalParameter),
-           0x08   => Ok(DIETag::ImportedDeclaration),
-           0x0a   => Ok(DIETag::Label),
-           0x0b   => Ok(DIETag::LexicalBlock),
-           0x0d   => Ok(DIETag::Member),
-           0x0f   => Ok(DIETag::PointerType),
-           0x10   => Ok(DIETag::ReferenceType),
-           0x11   => Ok(DIETag::CompileUnit),
-           0x12   => Ok(DIETag::StringType),
-           0x13   => Ok(DIETag::StructureType),
-           0x15   => Ok(DIETag::SubroutineType),
-           0x16   => Ok(DIETag::Typedef),
-           0x17   => Ok(DIETag::UnionType),
-           0x18   => Ok(DIETag::UnspecifiedParameters),
-           0x19   => Ok(DIETag::Variant),
-           0x1a   => Ok(DIETag::CommonBlock),
-           0x1b   => Ok(DIETag::CommonInclusion ),
-           0x1c   => Ok(DIETag::Inheritance),
-           0x1d   => Ok(DIETag::InlinedSubroutine),
-           0x1e   => Ok(DIETag::Module),
-           0x1f   => Ok(DIETag::PtrToMemberType),
-           0x20   => Ok(DIETag::SetType),
-           0x21   => Ok(DIETag::SubrangeType),
-           0x22   => Ok(DIETag::WithStmt),
-           0x23   => Ok(DIETag::AccessDeclaration),
-           0x24   => Ok(DIETag::BaseType),
-           0x25   => Ok(DIETag::CatchBlock),
-           0x26   => Ok(DIETag::ConstType),
-           0x27   => Ok(DIETag::Constant),
-           0x28   => Ok(DIETag::Enumerator),
-           0x29   => Ok(DIETag::FileType),
-           0x2a   => Ok(DIETag::Friend),
-           0x2b   => Ok(DIETag::Namelist),
-           0x2c   => Ok(DIETag::NamelistItem),
-           0x2d   => Ok(DIETag::PackedType),
-           0x2e   => Ok(DIETag::Subprogram),
-           0x2f   => Ok(DIETag::TemplateTypeParameter),
-           0x30   => Ok(DIETag::TemplateValueParameter),
-           0x31   => Ok(DIETag::ThrownType),
-           0x32   => Ok(DIETag::TryBlock),
-           0x33   => Ok(DIETag::VariantPart),
-           0x34   => Ok(DIETag::Variable),
-           0x35   => Ok(DIETag::VolatileType),
-           0x36   => Ok(DIETag::DwarfProcedure),
-           0x37   => Ok(DIETag::RestrictType),
-           0x38   => Ok(DIETag::InterfaceType),
-           0x39   => Ok(DIETag::Namespace),
-           0x3a   => Ok(DIETag::ImportedModule),
-           0x3b   => Ok(DIETag::UnspecifiedType),
-           0x3c   => Ok(DIETag::PartialUnit),
-           0x3d   => Ok(DIETag::ImportedUnit),
-           0x3f   => Ok(DIETag::Condition),
-           0x40   => Ok(DIETag::SharedType),
-           0x41   => Ok(DIETag::TypeUnit),
-           0x42   => Ok(DIETag::RvalueReferenceType),
-           0x43   => Ok(DIETag::TemplateAlias),
-           0x4080 => Ok(DIETag::LoUser),
-           0xffff => Ok(DIETag::HiUser),
-           _ => Err(format!("bad DIE tag {:#x}", value)),
-        }
-    }
-}
-
-#[derive(Clone, Debug)]
-pub struct DIEAttribute {
-    pub name: AttrName,
-    pub value: AttrValue,
+           0x01   => DIETag::ArrayType,
+           0x02   => DIETag::ClassType,
+           0x03   => DIETag::EntryPoint,
+           0x04   => DIETag::EnumerationType,
+           0x05   => DIETag::FormalParameter,
+           0x08   => DIETag::ImportedDeclaration,
+           0x0a   => DIETag::Label,
+           0x0b   => DIETag::LexicalBlock,
+           0x0d   => DIETag::Member,
+           0x0f   => DIETag::PointerType,
+           0x10   => DIETag::ReferenceType,
+           0x11   => DIETag::CompileUnit,
+           0x12   => DIETag::StringType,
+           0x13   => DIETag::StructureType,
+           0x15   => DIETag::SubroutineType,
+           0x16   => DIETag::Typedef,
+           0x17   => DIETag::UnionType,
+           0x18   => DIETag::UnspecifiedParameters,
+           0x19   => DIETag::Variant,
+           0x1a   => DIETag::CommonBlock,
+           0x1b   => DIETag::CommonInclusion,
+           0x1c   => DIETag::Inheritance,
+           0x1d   => DIETag::InlinedSubroutine,
+           0x1e   => DIETag::Module,
+           0x1f   => DIETag::PtrToMemberType,
+           0x20   => DIETag::SetType,
+           0x21   => DIETag::SubrangeType,
+           0x22   => DIETag::WithStmt,
+           0x23   => DIETag::AccessDeclaration,
+           0x24   => DIETag::BaseType,
+           0x25   => DIETag::CatchBlock,
+           0x26   => DIETag::ConstType,
+           0x27   => DIETag::Constant,
+           0x28   => DIETag::Enumerator,
+           0x29   => DIETag::FileType,
+           0x2a   => DIETag::Friend,
+           0x2b   => DIETag::Namelist,
+           0x2c   => DIETag::NamelistItem,
+           0x2d   => DIETag::PackedType,
+           0x2e   => DIETag::Subprogram,
+           0x2f   => DIETag::TemplateTypeParameter,
+           0x30   => DIETag::TemplateValueParameter,
+           0x31   => DIETag::ThrownType,
+           0x32   => DIETag::TryBlock,
+           0x33   => DIETag::VariantPart,
+           0x34   => DIETag::Variable,
+           0x35   => DIETag::VolatileType,
+           0x36   => DIETag::DwarfProcedure,
+           0x37   => DIETag::RestrictType,
+           0x38   => DIETag::InterfaceType,
+           0x39   => DIETag::Namespace,
+           0x3a   => DIETag::ImportedModule,
+           0x3b   => DIETag::UnspecifiedType,
+           0x3c   => DIETag::PartialUnit,
+           0x3d   => DIETag::ImportedUnit,
+           0x3f   => DIETag::Condition,
+           0x40   => DIETag::SharedType,
+           0x41   => DIETag::TypeUnit,
+           0x42   => DIETag::RvalueReferenceType,
+           0x43   => DIETag::TemplateAlias,
+           0x4080 => DIETag::LoUser,
+           0xffff => DIETag::HiUser,
+           _      => DIETag::Unknown(value),
+        }
+    }
 }
 
-impl DIEAttribute {
+// Known tags keep their variant names; unknown ones are shown in hex, like
+// unrecognized attributes.
+impl Display for DIETag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            DIETag::Unknown(n) => write!(f, "DW_TAG_unknown_{:#x}", n),
+            tag => write!(f, "DW_TAG_{:?}", tag),
+        }
+    }
 }
 
-#[derive(Clone, Debug)]
-pub enum AttrValue {
-    Address(u64),
-    Constant(u64),
-    ExprLoc(Vec<u8>), // Holds an expression or location description.
-    Flag(bool),
-    MacPtr(u64),
-    OffsetReference(u64),
-    StrP(String),
-    Unimplemented(AttrForm),
+// Base type encodings, as given by DW_AT_encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwAte {
+    Address,
+    Boolean,
+    ComplexFloat,
+    Float,
+    Signed,
+    SignedChar,
+    Unsigned,
+    UnsignedChar,
+    ImaginaryFloat,
+    PackedDecimal,
+    NumericString,
+    Edited,
+    SignedFixed,
+    UnsignedFixed,
+    DecimalFloat,
+    UTF,
+    UCS,
+    ASCII,
+    Unrecognized(u64),
 }
 
-impl AttrValue {
+impl DwAte {
+    pub fn from(value: u64) -> DwAte {
+        match value {
+            0x01 => DwAte::Address,
+            0x02 => DwAte::Boolean,
+            0x03 => DwAte::ComplexFloat,
+            0x04 => DwAte::Float,
+            0x05 => DwAte::Signed,
+            0x06 => DwAte::SignedChar,
+            0x07 => DwAte::Unsigned,
+            0x08 => DwAte::UnsignedChar,
+            0x09 => DwAte::ImaginaryFloat,
+            0x0a => DwAte::PackedDecimal,
+            0x0b => DwAte::NumericString,
+            0x0c => DwAte::Edited,
+            0x0d => DwAte::SignedFixed,
+            0x0e => DwAte::UnsignedFixed,
+            0x0f => DwAte::DecimalFloat,
+            0x10 => DwAte::UTF,
+            0x11 => DwAte::UCS,
+            0x12 => DwAte::ASCII,
+            n => DwAte::Unrecognized(n),
+        }
+    }
+}
+
+// Source languages, as given by DW_AT_language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DwLang {
+    C89,
+    C,
+    Ada83,
+    CPlusPlus,
+    Cobol74,
+    Cobol85,
+    Fortran77,
+    Fortran90,
+    Pascal83,
+    Modula2,
+    Java,
+    C99,
+    Ada95,
+    Fortran95,
+    PLI,
+    ObjC,
+    ObjCPlusPlus,
+    UPC,
+    D,
+    Python,
+    OpenCL,
+    Go,
+    Modula3,
+    Haskell,
+    CPlusPlus03,
+    CPlusPlus11,
+    OCaml,
+    Rust,
+    C11,
+    Swift,
+    Julia,
+    Dylan,
+    CPlusPlus14,
+    Fortran03,
+    Fortran08,
+    RenderScript,
+    BLISS,
+    MipsAssembler,
+    Unrecognized(u64),
+}
+
+impl DwLang {
+    pub fn from(value: u64) -> DwLang {
+        match value {
+            0x0001 => DwLang::C89,
+            0x0002 => DwLang::C,
+            0x0003 => DwLang::Ada83,
+            0x0004 => DwLang::CPlusPlus,
+            0x0005 => DwLang::Cobol74,
+            0x0006 => DwLang::Cobol85,
+            0x0007 => DwLang::Fortran77,
+            0x0008 => DwLang::Fortran90,
+            0x0009 => DwLang::Pascal83,
+            0x000a => DwLang::Modula2,
+            0x000b => DwLang::Java,
+            0x000c => DwLang::C99,
+            0x000d => DwLang::Ada95,
+            0x000e => DwLang::Fortran95,
+            0x000f => DwLang::PLI,
+            0x0010 => DwLang::ObjC,
+            0x0011 => DwLang::ObjCPlusPlus,
+            0x0012 => DwLang::UPC,
+            0x0013 => DwLang::D,
+            0x0014 => DwLang::Python,
+            0x0015 => DwLang::OpenCL,
+            0x0016 => DwLang::Go,
+            0x0017 => DwLang::Modula3,
+            0x0018 => DwLang::Haskell,
+            0x0019 => DwLang::CPlusPlus03,
+            0x001a => DwLang::CPlusPlus11,
+            0x001b => DwLang::OCaml,
+            0x001c => DwLang::Rust,
+            0x001d => DwLang::C11,
+            0x001e => DwLang::Swift,
+            0x001f => DwLang::Julia,
+            0x0020 => DwLang::Dylan,
+            0x0021 => DwLang::CPlusPlus14,
+            0x0022 => DwLang::Fortran03,
+            0x0023 => DwLang::Fortran08,
+            0x0024 => DwLang::RenderScript,
+            0x0025 => DwLang::BLISS,
+            0x8001 => DwLang::MipsAssembler,
+            n => DwLang::Unrecognized(n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DIEAttribute {
+    pub name: AttrName,
+    pub value: AttrValue,
+}
+
+impl DIEAttribute {
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttrValue {
+    Address(u64),
+    Constant(u64),
+    ExprLoc(Vec<u8>), // Holds an expression or location description.
+    Flag(bool),
+    MacPtr(u64),
+    OffsetReference(u64),
+    // An offset into __debug_info, which may be in another unit.
+    SectionReference(u64),
+    StrP(String),
+    TypeSignature(u64), // Identifies a type unit in .debug_types.
+    // DWARF 5 indexes, resolved through the unit's *_base attributes:
+    // into __debug_str_offs, __debug_addr, and the offsets array of
+    // __debug_loclists or __debug_rnglists.
+    StrIndex(u64),
+    AddrIndex(u64),
+    ListIndex(u64),
+    // A DW_FORM_implicit_const value, from the abbrev declaration.
+    ImplicitConst(i64),
+}
+
+impl AttrValue {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            AttrValue::Address(x)
+            | AttrValue::Constant(x)
+            | AttrValue::MacPtr(x)
+            | AttrValue::OffsetReference(x)
+            | AttrValue::SectionReference(x) => Some(x),
+            // Compilers use implicit_const for sizes and line numbers too.
+            AttrValue::ImplicitConst(n) => u64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            AttrValue::Flag(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttrValue::StrP(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            AttrValue::ExprLoc(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    // Decodes an exprloc value into its operations. Returns None for
+    // values of any other kind.
+    pub fn as_expression(&self, address_size: u8) -> Option<Result<Vec<expr::Op>, String>> {
+        match self {
+            AttrValue::ExprLoc(bytes) => Some(expr::decode(bytes, address_size)),
+            _ => None,
+        }
+    }
+
+    // Decodes the value at the start of `bytes`, returning it and its size.
     pub fn from(
         bytes: &[u8],
         form: AttrForm,
+        format: Format,
+        address_size: u8,
         strdata: &[u8],
     ) -> Result<(AttrValue, usize), String> {
-        match form {
-            AttrForm::Addr => {
-                // FIXME: Address size is set in the unit header.
-                let x = u64::from_ne_bytes(bytes[0..8].try_into().unwrap());
-                Ok((AttrValue::Address(x), 8))
-            },
-            AttrForm::Data1 => Ok((AttrValue::Constant(bytes[0] as u64), 1)),
-            AttrForm::Data2 => {
-                let x = u16::from_ne_bytes(bytes[0..2].try_into().unwrap());
-                Ok((AttrValue::Constant(x as u64), 2))
-            },
-            AttrForm::Data4 => {
-                let x = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
-                Ok((AttrValue::Constant(x as u64), 4))
-            },
+        let mut reader = Reader::new(bytes);
+        let value = Self::read(&mut reader, form, format, address_size, strdata)?;
+        Ok((value, reader.position()))
+    }
+
+    // Reads a value of the given form, leaving `reader` just past it.
+    // Section offsets, as read by DW_FORM_ref_addr, sec_offset and strp,
+    // are 4 or 8 bytes long depending on the unit's `format`; addresses
+    // are the unit header's `address_size`.
+    pub fn read(
+        reader: &mut Reader,
+        form: AttrForm,
+        format: Format,
+        address_size: u8,
+        strdata: &[u8],
+    ) -> Result<AttrValue, String> {
+        let section_offset = |reader: &mut Reader| match format {
+            Format::Dwarf32 => reader.u32().map(u64::from),
+            Format::Dwarf64 => reader.u64(),
+        };
+        Ok(match form {
+            AttrForm::Addr => AttrValue::Address(match address_size {
+                1 => reader.u8()? as u64,
+                2 => reader.u16()? as u64,
+                4 => reader.u32()? as u64,
+                8 => reader.u64()?,
+                n => return Err(format!("can't read {}-byte addresses", n)),
+            }),
+            AttrForm::Data1 => AttrValue::Constant(reader.u8()? as u64),
+            AttrForm::Data2 => AttrValue::Constant(reader.u16()? as u64),
+            AttrForm::Data4 => AttrValue::Constant(reader.u32()? as u64),
+            AttrForm::Udata => AttrValue::Constant(reader.uleb()?),
             AttrForm::ExprLoc => {
-                let (len, size) = uleb128_decode(bytes)?;
-                let (len, size) = (len as usize, size as usize);
-                Ok((AttrValue::ExprLoc(bytes[size..size+len].to_vec()), len + size))
-            },
-            AttrForm::Flag => Ok((AttrValue::Flag(bytes[0] != 0), 1)),
-            AttrForm::FlagPresent => Ok((AttrValue::Flag(true), 0)),
-            AttrForm::Ref1 => Ok((AttrValue::OffsetReference(bytes[0] as u64), 1)),
-            AttrForm::Ref2 => {
-                let x = u16::from_ne_bytes(bytes[0..2].try_into().unwrap());
-                Ok((AttrValue::OffsetReference(x as u64), 2))
-            },
-            AttrForm::Ref4 => {
-                let x = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
-                Ok((AttrValue::OffsetReference(x as u64), 4))
-            },
-            AttrForm::Ref8 => {
-                let x = u32::from_ne_bytes(bytes[0..8].try_into().unwrap());
-                Ok((AttrValue::OffsetReference(x as u64), 8))
-            },
-            AttrForm::SecOffset => {
-                let x = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
-                Ok((AttrValue::MacPtr(x as u64), 4))
+                let len = reader.uleb()?;
+                let len = usize::try_from(len)
+                    .map_err(|_| format!("exprloc of {:#x} bytes is too long", len))?;
+                AttrValue::ExprLoc(reader.bytes(len)?.to_vec())
             },
+            AttrForm::Flag => AttrValue::Flag(reader.u8()? != 0),
+            AttrForm::FlagPresent => AttrValue::Flag(true),
+            AttrForm::Ref1 => AttrValue::OffsetReference(reader.u8()? as u64),
+            AttrForm::Ref2 => AttrValue::OffsetReference(reader.u16()? as u64),
+            AttrForm::Ref4 => AttrValue::OffsetReference(reader.u32()? as u64),
+            AttrForm::Ref8 => AttrValue::OffsetReference(reader.u64()?),
+            AttrForm::RefUdata => AttrValue::OffsetReference(reader.uleb()?),
+            AttrForm::RefSig8 => AttrValue::TypeSignature(reader.u64()?),
+            AttrForm::RefAddr => AttrValue::SectionReference(section_offset(reader)?),
+            AttrForm::SecOffset => AttrValue::MacPtr(section_offset(reader)?),
             AttrForm::StrP => {
-                let offset = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
-                let string = CStr::from_bytes_until_nul(&strdata[offset as usize..])
+                let offset = section_offset(reader)? as usize;
+                let string = CStr::from_bytes_until_nul(strdata.get(offset..).unwrap_or(&[]))
                     .map_err(|e| e.to_string())?
                     .to_str()
                     .map_err(|e| e.to_string())?
                     .to_string();
-                Ok((AttrValue::StrP(string), 4))
+                AttrValue::StrP(string)
             },
-            _ => Ok((AttrValue::Unimplemented(form), 0)),
+            // The string sits inline in .debug_info, terminator and all.
+            AttrForm::Stringg => AttrValue::StrP(reader.cstr()?.to_string()),
+            AttrForm::Strx => AttrValue::StrIndex(reader.uleb()?),
+            AttrForm::Strx1 => AttrValue::StrIndex(reader.u8()? as u64),
+            AttrForm::Strx2 => AttrValue::StrIndex(reader.u16()? as u64),
+            AttrForm::Strx3 => AttrValue::StrIndex(reader.u24()? as u64),
+            AttrForm::Strx4 => AttrValue::StrIndex(reader.u32()? as u64),
+            AttrForm::Addrx => AttrValue::AddrIndex(reader.uleb()?),
+            AttrForm::Addrx1 => AttrValue::AddrIndex(reader.u8()? as u64),
+            AttrForm::Addrx2 => AttrValue::AddrIndex(reader.u16()? as u64),
+            AttrForm::Addrx3 => AttrValue::AddrIndex(reader.u24()? as u64),
+            AttrForm::Addrx4 => AttrValue::AddrIndex(reader.u32()? as u64),
+            AttrForm::Loclistx | AttrForm::Rnglistx => AttrValue::ListIndex(reader.uleb()?),
+            // Takes no space in the DIE; parsing takes the value from the
+            // attribute's spec instead.
+            AttrForm::ImplicitConst =>
+                return Err("DW_FORM_implicit_const values are in the abbrev, not the DIE".to_string()),
+            // Carrying on would mean guessing the value's size, and a wrong
+            // guess throws off every attribute after it.
+            _ => return Err(format!("can't read values of form {} yet", form)),
+        })
+    }
+}
+
+// The declarations from an abbrev table, indexed by code. DIEs name
+// their abbrev by code, so parsing looks one up for every DIE.
+pub struct AbbrevTable<'a> {
+    decls: &'a [AbbrevDecl],
+    by_code: HashMap<u64, usize>,
+}
+
+impl<'a> AbbrevTable<'a> {
+    pub fn new(decls: &'a [AbbrevDecl]) -> AbbrevTable<'a> {
+        let mut by_code = HashMap::with_capacity(decls.len());
+        for (i, decl) in decls.iter().enumerate() {
+            // Like a linear search, the first declaration of a code wins.
+            by_code.entry(decl.abbrev_code).or_insert(i);
         }
+        AbbrevTable { decls, by_code }
+    }
+
+    pub fn get(&self, code: u64) -> Option<&'a AbbrevDecl> {
+        self.by_code.get(&code).map(|&i| &self.decls[i])
+    }
+}
+
+// The tables in __debug_abbrev, by where each starts in the section.
+pub struct AbbrevTables<'a> {
+    tables: HashMap<u64, AbbrevTable<'a>>,
+}
+
+impl<'a> AbbrevTables<'a> {
+    // Splits `decls` into the tables listed in `tables`, as (offset, index
+    // of first declaration) pairs in section order.
+    pub fn new(decls: &'a [AbbrevDecl], tables: &[(u64, usize)]) -> AbbrevTables<'a> {
+        let tables = tables.iter().enumerate()
+            .map(|(i, &(offset, start))| {
+                let end = tables.get(i + 1).map_or(decls.len(), |&(_, next)| next);
+                (offset, AbbrevTable::new(&decls[start..end]))
+            })
+            .collect();
+        AbbrevTables { tables }
+    }
+
+    pub fn get(&self, offset: u64) -> Result<&AbbrevTable<'a>, String> {
+        self.tables.get(&offset)
+            .ok_or_else(|| format!("no abbrev table starts at {:#x} in __debug_abbrev", offset))
     }
 }
 
@@ -613,38 +2532,43 @@ pub struct AbbrevDecl {
 
 impl AbbrevDecl {
     pub fn from(bytes: &[u8]) -> Result<(AbbrevDecl, usize), String> {
-        let mut offset = 0;
-        let (abbrev_code, code_size) = uleb128_decode(bytes)?;
-        offset += code_size;
-        let (tag, code_size) = uleb128_decode(&bytes[offset..])?;
-        offset += code_size;
-        let has_children = match bytes[offset] {
+        let mut reader = Reader::new(bytes);
+        let abbrev_code = reader.uleb()?;
+        let decl = Self::read_after_code(abbrev_code, &mut reader)?;
+        Ok((decl, reader.position()))
+    }
+
+    // Reads the rest of a declaration, once its code has been read.
+    fn read_after_code(abbrev_code: u64, reader: &mut Reader) -> Result<AbbrevDecl, String> {
+        let tag = reader.uleb()?;
+        let has_children = match reader.u8()? {
             0 => Ok(false),
             1 => Ok(true),
             x => Err(format!("bad DW_CHILDREN value, {}", x)),
         }?;
-        offset += 1;
         let mut attr_specs = vec![];
         loop {
-            let (name, leb_size) = uleb128_decode(&bytes[offset..])?;
-            offset += leb_size;
-            let (form, leb_size) = uleb128_decode(&bytes[offset..])?;
-            offset += leb_size;
-            if name == 0 && form == 0 { break; }
+            let name = reader.uleb()?;
+            let form = AttrForm::from(reader.uleb()?);
+            if name == 0 && form == AttrForm::Unrecognized(0) { break; }
+            // DW_FORM_implicit_const keeps its value here, in the
+            // declaration, rather than in each DIE.
+            let implicit_const = match form {
+                AttrForm::ImplicitConst => Some(reader.ileb()?),
+                _ => None,
+            };
             attr_specs.push(AttrSpec {
                 name: AttrName::from(name),
-                form: AttrForm::from(form),
+                form,
+                implicit_const,
             });
         }
-        Ok((
-            AbbrevDecl {
-                abbrev_code,
-                tag: DIETag::from(tag)?,
-                has_children,
-                attr_specs,
-            },
-            offset,
-        ))
+        Ok(AbbrevDecl {
+            abbrev_code,
+            tag: DIETag::from(tag),
+            has_children,
+            attr_specs,
+        })
     }
 }
 
@@ -652,9 +2576,11 @@ impl AbbrevDecl {
 pub struct AttrSpec {
     pub name: AttrName,
     pub form: AttrForm,
+    // The value of a DW_FORM_implicit_const attribute.
+    pub implicit_const: Option<i64>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum AttrName {
     Sibling,
     Location,
@@ -748,6 +2674,35 @@ pub enum AttrName {
     ConstExpr,
     EnumClass,
     LinkageName,
+    StringLengthBitSize,
+    StringLengthByteSize,
+    Rank,
+    StrOffsetsBase,
+    AddrBase,
+    RnglistsBase,
+    DwoName,
+    Reference,
+    RvalueReference,
+    Macros,
+    CallAllCalls,
+    CallAllSourceCalls,
+    CallAllTailCalls,
+    CallReturnPc,
+    CallValue,
+    CallOrigin,
+    CallParameter,
+    CallPc,
+    CallTailCall,
+    CallTarget,
+    CallTargetClobbered,
+    CallDataLocation,
+    CallDataValue,
+    Noreturn,
+    Alignment,
+    ExportSymbols,
+    Deleted,
+    Defaulted,
+    LoclistsBase,
     LoUser,
     HiUser,
     Unrecognized(u64),
@@ -848,6 +2803,36 @@ impl AttrName {
             0x6c   => AttrName::ConstExpr,
             0x6d   => AttrName::EnumClass,
             0x6e   => AttrName::LinkageName,
+            // DWARF5 additions.
+            0x6f   => AttrName::StringLengthBitSize,
+            0x70   => AttrName::StringLengthByteSize,
+            0x71   => AttrName::Rank,
+            0x72   => AttrName::StrOffsetsBase,
+            0x73   => AttrName::AddrBase,
+            0x74   => AttrName::RnglistsBase,
+            0x76   => AttrName::DwoName,
+            0x77   => AttrName::Reference,
+            0x78   => AttrName::RvalueReference,
+            0x79   => AttrName::Macros,
+            0x7a   => AttrName::CallAllCalls,
+            0x7b   => AttrName::CallAllSourceCalls,
+            0x7c   => AttrName::CallAllTailCalls,
+            0x7d   => AttrName::CallReturnPc,
+            0x7e   => AttrName::CallValue,
+            0x7f   => AttrName::CallOrigin,
+            0x80   => AttrName::CallParameter,
+            0x81   => AttrName::CallPc,
+            0x82   => AttrName::CallTailCall,
+            0x83   => AttrName::CallTarget,
+            0x84   => AttrName::CallTargetClobbered,
+            0x85   => AttrName::CallDataLocation,
+            0x86   => AttrName::CallDataValue,
+            0x87   => AttrName::Noreturn,
+            0x88   => AttrName::Alignment,
+            0x89   => AttrName::ExportSymbols,
+            0x8a   => AttrName::Deleted,
+            0x8b   => AttrName::Defaulted,
+            0x8c   => AttrName::LoclistsBase,
             0x2000 => AttrName::LoUser,
             0x3fff => AttrName::HiUser,
             n => AttrName::Unrecognized(n),
@@ -855,7 +2840,51 @@ impl AttrName {
     }
 }
 
-#[derive(Clone, Debug)]
+// Spells a DIETag, AttrName or AttrForm the way the DWARF spec does, given
+// its variant name: StmtList becomes stmt_list.
+fn snake_case(variant: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in variant.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 { out.push('_'); }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+impl AttrName {
+    // Like DIETag::from_name, for names like "decl_file" or "DW_AT_name".
+    pub fn from_name(name: &str) -> Option<AttrName> {
+        let name = strip_prefix_ignore_case(name, "DW_AT_");
+        (0..=0x3fff)
+            .map(AttrName::from)
+            .filter(|attr| !matches!(attr, AttrName::Unrecognized(_)))
+            .find(|attr| attr.to_string()["DW_AT_".len()..].eq_ignore_ascii_case(name))
+    }
+}
+
+// `name` without `prefix`, if it starts with it in any case.
+fn strip_prefix_ignore_case<'a>(name: &'a str, prefix: &str) -> &'a str {
+    match name.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => &name[prefix.len()..],
+        _ => name,
+    }
+}
+
+impl Display for AttrName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            AttrName::Unrecognized(n) => write!(f, "DW_AT_unknown_{:#x}", n),
+            AttrName::UseUTF8 => write!(f, "DW_AT_use_UTF8"),
+            name => write!(f, "DW_AT_{}", snake_case(&format!("{:?}", name))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum AttrForm {
     Addr,
     Block2,
@@ -881,7 +2910,22 @@ pub enum AttrForm {
     SecOffset,
     ExprLoc,
     FlagPresent,
+    Strx,
+    Addrx,
+    Loclistx,
+    Rnglistx,
     RefSig8,
+    Data16,
+    LineStrp,
+    ImplicitConst,
+    Strx1,
+    Strx2,
+    Strx3,
+    Strx4,
+    Addrx1,
+    Addrx2,
+    Addrx3,
+    Addrx4,
     Unrecognized(u64),
 }
 
@@ -912,12 +2956,80 @@ impl AttrForm {
             0x17 => AttrForm::SecOffset,
             0x18 => AttrForm::ExprLoc,
             0x19 => AttrForm::FlagPresent,
+            0x1a => AttrForm::Strx,
+            0x1b => AttrForm::Addrx,
+            0x1e => AttrForm::Data16,
+            0x1f => AttrForm::LineStrp,
             0x20 => AttrForm::RefSig8,
+            0x21 => AttrForm::ImplicitConst,
+            0x22 => AttrForm::Loclistx,
+            0x23 => AttrForm::Rnglistx,
+            0x25 => AttrForm::Strx1,
+            0x26 => AttrForm::Strx2,
+            0x27 => AttrForm::Strx3,
+            0x28 => AttrForm::Strx4,
+            0x29 => AttrForm::Addrx1,
+            0x2a => AttrForm::Addrx2,
+            0x2b => AttrForm::Addrx3,
+            0x2c => AttrForm::Addrx4,
             n => AttrForm::Unrecognized(n),
         }
     }
 }
 
+impl Display for AttrForm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let name = match self {
+            AttrForm::Unrecognized(n) => return write!(f, "DW_FORM_unknown_{:#x}", n),
+            AttrForm::Stringg => "string".to_string(),
+            AttrForm::StrP => "strp".to_string(),
+            AttrForm::SData => "sdata".to_string(),
+            AttrForm::ExprLoc => "exprloc".to_string(),
+            form => snake_case(&format!("{:?}", form)),
+        };
+        write!(f, "DW_FORM_{}", name)
+    }
+}
+
+// One unit's address ranges from __debug_aranges. The header is laid out
+// like a unit header, except that where a unit gives its abbrevs' offset,
+// a set gives the offset of its unit in __debug_info.
+#[derive(Debug)]
+pub struct ArangeSet {
+    pub header: CUHeader,
+    pub segment_size: u8,
+    pub tuples: Vec<ArangeTuple>,
+}
+
+impl ArangeSet {
+    pub fn from(bytes: &[u8]) -> Result<ArangeSet, String> {
+        let header = CUHeader::from(bytes)?;
+        let bytes = bytes.get(..header.unit_size())
+            .ok_or_else(|| format!("set of {:#x} bytes runs past the end of the section", header.unit_size()))?;
+        let segment_size = *bytes.get(header.size()).ok_or("set ends before its segment_size")?;
+        // Both sizes come from the set, so add them as usize: 0xff plus two
+        // 8-byte addresses would overflow a u8.
+        let tuple_size = segment_size as usize + 2*header.address_size as usize;
+        // The tuples are aligned to the tuple size, so in 32-bit DWARF
+        // with 8-byte addresses they start at 16, not 12.
+        let mut offset = (header.size() + 1).next_multiple_of(tuple_size);
+        let mut tuples = vec![];
+        // The list ends with a tuple of zeros, or failing that, the set.
+        while let Some(tuple) = bytes.get(offset..offset+tuple_size) {
+            let tuple = ArangeTuple::from(tuple, segment_size as u64, header.address_size as u64);
+            if tuple.is_zero() { break; }
+            tuples.push(tuple);
+            offset += tuple_size;
+        }
+        Ok(ArangeSet { header, segment_size, tuples })
+    }
+
+    // Where the set's unit starts in __debug_info.
+    pub fn debug_info_offset(&self) -> u64 {
+        self.header.debug_abbrev_offset
+    }
+}
+
 #[derive(Debug)]
 pub struct ArangeTuple {
     pub segment: Vec<u8>,
@@ -929,7 +3041,7 @@ impl ArangeTuple {
     pub fn from(bytes: &[u8], seg_size: u64, addr_size: u64) -> ArangeTuple {
         let seg_size = seg_size as usize;
         let segment = bytes[0..seg_size].to_vec();
-        let mut off = seg_size as usize;
+        let mut off = seg_size;
         let addr = match addr_size {
             8 => u64::from_ne_bytes(bytes[off..off+8].try_into().unwrap()),
             4 => u32::from_ne_bytes(bytes[off..off+4].try_into().unwrap()) as u64,
@@ -974,6 +3086,11 @@ pub struct RawDebugLine {
     // information and is independent of the DWARF version number.
     pub version: u16,
 
+    // The size in bytes of a target address and segment selector. These
+    // are only in version 5 headers, and are 0 for earlier versions.
+    pub address_size: u8,
+    pub segment_selector_size: u8,
+
     // The number of bytes following the header_length field to the
     // beginning of the first byte of the line number program itself.
     // In the 32-bit DWARF format, this is a 4-byte unsigned length;
@@ -1103,44 +3220,112 @@ pub struct RawDebugLine {
     // A compiler may generate a single null byte for the file names field
     // and define file names using the extended opcode DW_LNE_define_file.
     pub file_names: Vec<DebugLineFileEntry>,
+
+    // The line number program itself, which follows the header.
+    pub program: Vec<u8>,
 }
 
 impl RawDebugLine {
-    pub fn from(bytes: &[u8]) -> Result<RawDebugLine, String> {
+    // `strs` and `line_strs` are the contents of __debug_str and
+    // __debug_line_str, which version 5 headers may refer into for paths.
+    pub fn from(bytes: &[u8], strs: &[u8], line_strs: &[u8]) -> Result<RawDebugLine, String> {
+        let too_short = |needed: usize| format!(
+            "line program header needs {:#x} bytes, but __debug_line has {:#x}", needed, bytes.len());
+        if bytes.len() < 6 {
+            return Err(too_short(6));
+        }
         let unit_length = u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as u64;
         let version     = u16::from_ne_bytes(bytes[4..6].try_into().unwrap());
+        if !CUHeader::SUPPORTED_VERSIONS.contains(&version) {
+            return Err(format!("line program has version {}, which isn't supported", version));
+        }
+        // Version 5 puts address_size and segment_selector_size before
+        // header_length, shifting everything after them along.
+        // The fixed-size fields run through opcode_base, which is 4 bytes
+        // past `rest` below.
+        let fixed_end = match version {
+            5.. => 18,
+            4 => 16,
+            _ => 15,
+        };
+        if bytes.len() < fixed_end {
+            return Err(too_short(fixed_end));
+        }
+        let (address_size, segment_selector_size, fixed) = if version >= 5 {
+            (bytes[6], bytes[7], 12)
+        } else {
+            (0, 0, 10)
+        };
         let header_length =
-            u32::from_ne_bytes(bytes[6..10].try_into().unwrap()) as u64;
-        let minimum_instruction_length         = bytes[10];
-        let maximum_operations_per_instruction = bytes[11];
-        let default_is_stmt                    = bytes[12];
-        let line_base                          = bytes[13] as i8;
-        let line_range                         = bytes[14];
-        let opcode_base                        = bytes[15];
-        let standard_opcode_lengths = bytes[16..16+opcode_base as usize -1].to_vec();
-        let mut offset = 16+(opcode_base as usize)-1;
-        println!("{:x?}\n", &bytes[offset..]);
-        let mut include_directories = vec![];
-        while bytes[offset] != 0 {
-            let string = CStr::from_bytes_until_nul(&bytes[offset..])
-                .map_err(|e| e.to_string())?
-                .to_str()
-                .map_err(|e| e.to_string())?
-                .to_string();
-            offset += string.len() + 1;
-            include_directories.push(string);
-        }
-        offset += 1;
-
-        let mut file_names = vec![];
-        while bytes[offset] != 0 {
-            let (file_entry, size) = DebugLineFileEntry::from(&bytes[offset..])?;
-            file_names.push(file_entry);
-            offset += size;
+            u32::from_ne_bytes(bytes[fixed-4..fixed].try_into().unwrap()) as u64;
+        let minimum_instruction_length = bytes[fixed];
+        // maximum_operations_per_instruction arrived in version 4. Before
+        // that there's no VLIW support, which is the same as it being 1.
+        let (maximum_operations_per_instruction, rest) = if version >= 4 {
+            (bytes[fixed+1], fixed+2)
+        } else {
+            (1, fixed+1)
+        };
+        let default_is_stmt = bytes[rest];
+        let line_base       = bytes[rest+1] as i8;
+        let line_range      = bytes[rest+2];
+        let opcode_base     = bytes[rest+3];
+        if opcode_base == 0 {
+            return Err("opcode_base in __debug_line header is 0".to_string());
         }
+        // One entry for each standard opcode, 1 through opcode_base - 1.
+        let mut offset = rest+4+(opcode_base as usize)-1;
+        let standard_opcode_lengths = bytes.get(rest+4..offset)
+            .ok_or_else(|| format!("standard_opcode_lengths for opcode_base {} runs past end of __debug_line",
+                opcode_base))?
+            .to_vec();
+
+        let (include_directories, file_names) = if version >= 5 {
+            let mut reader = Reader::new(&bytes[offset..]);
+            let directories = read_line_entries(&mut reader, strs, line_strs)?;
+            let files = read_line_entries(&mut reader, strs, line_strs)?;
+            let include_directories = directories.into_iter()
+                .map(|dir| dir.path)
+                .collect();
+            (include_directories, files)
+        } else {
+            let mut include_directories = vec![];
+            let end_of_header = || format!("line program header runs past end of __debug_line ({:#x} bytes)", bytes.len());
+            while *bytes.get(offset).ok_or_else(end_of_header)? != 0 {
+                let string = CStr::from_bytes_until_nul(&bytes[offset..])
+                    .map_err(|e| e.to_string())?
+                    .to_str()
+                    .map_err(|e| e.to_string())?
+                    .to_string();
+                offset += string.len() + 1;
+                include_directories.push(string);
+            }
+            offset += 1;
+
+            let mut file_names = vec![];
+            while *bytes.get(offset).ok_or_else(end_of_header)? != 0 {
+                let (file_entry, size) = DebugLineFileEntry::from(&bytes[offset..])?;
+                file_names.push(file_entry);
+                offset += size;
+            }
+            (include_directories, file_names)
+        };
+
+        let program_start = (header_length as usize).checked_add(fixed)
+            .ok_or_else(|| format!("header_length {:#x} is too large", header_length))?;
+        let program_end = (unit_length as usize).checked_add(4)
+            .ok_or_else(|| format!("unit_length {:#x} is too large", unit_length))?;
+        if program_start > program_end || program_end > bytes.len() {
+            return Err(format!(
+                "line program at {:#x}..{:#x} runs past end of __debug_line ({:#x} bytes)",
+                program_start, program_end, bytes.len()));
+        }
+        let program = bytes[program_start..program_end].to_vec();
         Ok(RawDebugLine {
             unit_length,
             version,
+            address_size,
+            segment_selector_size,
             header_length,
             minimum_instruction_length,
             maximum_operations_per_instruction,
@@ -1151,11 +3336,80 @@ impl RawDebugLine {
             standard_opcode_lengths,
             include_directories,
             file_names,
+            program,
         })
     }
 }
 
-#[derive(Debug)]
+// Reads a version 5 directory or file name table: a list of (content type,
+// form) pairs describing each entry, then the entries themselves. Content
+// types other than the path, directory index, timestamp and size (such as
+// DW_LNCT_MD5) are read past and dropped.
+fn read_line_entries(
+    reader: &mut Reader, strs: &[u8], line_strs: &[u8]
+) -> Result<Vec<DebugLineFileEntry>, String> {
+    let format_count = reader.u8()?;
+    let mut format = vec![];
+    for _ in 0..format_count {
+        format.push((reader.uleb()?, AttrForm::from(reader.uleb()?)));
+    }
+    let count = reader.uleb()?;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let mut entry = DebugLineFileEntry {
+            path: String::new(),
+            dir_index: 0,
+            last_mod: 0,
+            file_len: 0,
+        };
+        for (content_type, form) in format.iter() {
+            let value = match form {
+                AttrForm::Stringg => LineEntryValue::Str(reader.cstr()?.to_string()),
+                AttrForm::StrP =>
+                    LineEntryValue::Str(str_at(strs, reader.u32()?, "__debug_str")?),
+                AttrForm::LineStrp =>
+                    LineEntryValue::Str(str_at(line_strs, reader.u32()?, "__debug_line_str")?),
+                AttrForm::Udata => LineEntryValue::Num(reader.uleb()?),
+                AttrForm::Data1 => LineEntryValue::Num(reader.u8()? as u64),
+                AttrForm::Data2 => LineEntryValue::Num(reader.u16()? as u64),
+                AttrForm::Data4 => LineEntryValue::Num(reader.u32()? as u64),
+                AttrForm::Data8 => LineEntryValue::Num(reader.u64()?),
+                AttrForm::Data16 => { reader.skip(16)?; LineEntryValue::Skipped },
+                AttrForm::Block => {
+                    let len = reader.uleb()? as usize;
+                    reader.skip(len)?;
+                    LineEntryValue::Skipped
+                },
+                form => return Err(format!("unsupported form {:?} in line table entry", form)),
+            };
+            match (content_type, value) {
+                (0x1, LineEntryValue::Str(path)) => entry.path = path,
+                (0x2, LineEntryValue::Num(n)) => entry.dir_index = n,
+                (0x3, LineEntryValue::Num(n)) => entry.last_mod = n,
+                (0x4, LineEntryValue::Num(n)) => entry.file_len = n,
+                _ => {},
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+enum LineEntryValue {
+    Str(String),
+    Num(u64),
+    Skipped,
+}
+
+// Reads the null-terminated string at `offset` in a string section.
+fn str_at(data: &[u8], offset: u32, section: &str) -> Result<String, String> {
+    if offset as usize >= data.len() {
+        return Err(format!("offset {:#x} is past the end of {}", offset, section));
+    }
+    Reader::new(&data[offset as usize..]).cstr().map(|s| s.to_string())
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct DebugLineFileEntry {
     pub path: String,
     pub dir_index: u64,
@@ -1165,7 +3419,7 @@ pub struct DebugLineFileEntry {
 
 impl DebugLineFileEntry {
     pub fn from(bytes: &[u8]) -> Result<(DebugLineFileEntry, usize), String> {
-        let path = CStr::from_bytes_until_nul(&bytes)
+        let path = CStr::from_bytes_until_nul(bytes)
             .map_err(|e| e.to_string())?
             .to_str()
             .map_err(|e| e.to_string())?
@@ -1181,11 +3435,2237 @@ impl DebugLineFileEntry {
     }
 }
 
+// One row of the line number matrix. See section 6.2.2 of the DWARF 4 spec
+// for the meaning of each register.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineRow {
+    pub address: u64,
+    // Which operation within the VLIW instruction at `address`. Always 0
+    // when maximum_operations_per_instruction is 1.
+    pub op_index: u64,
+    pub file: u64,
+    pub line: u64,
+    pub column: u64,
+    pub is_stmt: bool,
+    pub basic_block: bool,
+    pub end_sequence: bool,
+    pub prologue_end: bool,
+    pub epilogue_begin: bool,
+    pub isa: u64,
+    pub discriminator: u64,
+}
+
+impl LineRow {
+    fn new(default_is_stmt: bool) -> LineRow {
+        LineRow {
+            address: 0,
+            op_index: 0,
+            file: 1,
+            line: 1,
+            column: 0,
+            is_stmt: default_is_stmt,
+            basic_block: false,
+            end_sequence: false,
+            prologue_end: false,
+            epilogue_begin: false,
+            isa: 0,
+            discriminator: 0,
+        }
+    }
+
+    // Moves on by `operation_advance` operations, as special opcodes,
+    // DW_LNS_advance_pc and DW_LNS_const_add_pc do. The address only moves
+    // when op_index wraps past the operations in one instruction. Like the
+    // target's own address arithmetic, it wraps rather than overflows.
+    fn advance(&mut self, operation_advance: u64, min_inst_len: u64, max_ops: u64) {
+        let ops = self.op_index.wrapping_add(operation_advance);
+        self.address = self.address.wrapping_add(min_inst_len.wrapping_mul(ops / max_ops));
+        self.op_index = ops % max_ops;
+    }
+}
+
+// The result of running a line number program.
 #[derive(Debug)]
-pub struct CompiledDebugLine {}
+pub struct CompiledDebugLine {
+    pub rows: Vec<LineRow>,
+
+    // The header's file_names, followed by any the program added with
+    // DW_LNE_define_file.
+    pub file_names: Vec<DebugLineFileEntry>,
+
+    // The header's include_directories, which file_names index into.
+    pub include_directories: Vec<String>,
+
+    // The line program's version. Rows count files from 1 before version
+    // 5, and from 0 after.
+    pub version: u16,
+}
 
 impl CompiledDebugLine {
-    pub fn from(raw: &RawDebugLine) -> CompiledDebugLine {
-        CompiledDebugLine {} // TODO
+    pub fn from(raw: &RawDebugLine) -> Result<CompiledDebugLine, String> {
+        if raw.line_range == 0 {
+            return Err("line_range in __debug_line header is 0".to_string());
+        }
+        if raw.standard_opcode_lengths.len() + 1 != raw.opcode_base as usize {
+            return Err(format!("__debug_line header has {} standard_opcode_lengths for opcode_base {}",
+                raw.standard_opcode_lengths.len(), raw.opcode_base));
+        }
+        let default_is_stmt = raw.default_is_stmt != 0;
+        let min_inst_len = raw.minimum_instruction_length as u64;
+        // Some producers write 0 where they mean "not VLIW".
+        let max_ops = (raw.maximum_operations_per_instruction as u64).max(1);
+        let mut file_names = raw.file_names.clone();
+        let mut rows = vec![];
+        let mut state = LineRow::new(default_is_stmt);
+        let mut reader = Reader::new(&raw.program);
+
+        // Appends a row and clears the registers that only apply to one row.
+        let emit = |state: &mut LineRow, rows: &mut Vec<LineRow>| {
+            rows.push(state.clone());
+            state.basic_block = false;
+            state.prologue_end = false;
+            state.epilogue_begin = false;
+            state.discriminator = 0;
+        };
+
+        while !reader.is_empty() {
+            let opcode = reader.u8()?;
+            if opcode >= raw.opcode_base {
+                // A special opcode advances the address and line together.
+                let adjusted = opcode - raw.opcode_base;
+                state.advance((adjusted / raw.line_range) as u64, min_inst_len, max_ops);
+                state.line = state.line.wrapping_add_signed(
+                    raw.line_base as i64 + (adjusted % raw.line_range) as i64);
+                emit(&mut state, &mut rows);
+                continue;
+            }
+            match opcode {
+                0x00 => {
+                    // Extended opcodes give their length up front, so ones we
+                    // don't know (like vendor ones from DW_LNE_lo_user up)
+                    // can be skipped.
+                    let len = reader.uleb()? as usize;
+                    let body = reader.bytes(len)?;
+                    let Some((&sub_opcode, operands)) = body.split_first() else {
+                        continue;
+                    };
+                    match sub_opcode {
+                        0x01 => { // DW_LNE_end_sequence
+                            state.end_sequence = true;
+                            emit(&mut state, &mut rows);
+                            state = LineRow::new(default_is_stmt);
+                        },
+                        0x02 => { // DW_LNE_set_address
+                            let mut operand = Reader::new(operands);
+                            state.address = match operands.len() {
+                                4 => operand.u32()? as u64,
+                                8 => operand.u64()?,
+                                n => return Err(format!(
+                                    "DW_LNE_set_address with {}-byte operand", n)),
+                            };
+                            state.op_index = 0;
+                        },
+                        0x03 => { // DW_LNE_define_file
+                            let (entry, _) = DebugLineFileEntry::from(operands)?;
+                            file_names.push(entry);
+                        },
+                        0x04 => { // DW_LNE_set_discriminator
+                            state.discriminator = Reader::new(operands).uleb()?;
+                        },
+                        _ => {},
+                    }
+                },
+                0x01 => emit(&mut state, &mut rows), // DW_LNS_copy
+                0x02 => state.advance(reader.uleb()?, min_inst_len, max_ops), // DW_LNS_advance_pc
+                0x03 => state.line = state.line.wrapping_add_signed(reader.ileb()?),
+                0x04 => state.file = reader.uleb()?,
+                0x05 => state.column = reader.uleb()?,
+                0x06 => state.is_stmt = !state.is_stmt,
+                0x07 => state.basic_block = true,
+                0x08 => { // DW_LNS_const_add_pc
+                    let adjusted = 255 - raw.opcode_base;
+                    state.advance((adjusted / raw.line_range) as u64, min_inst_len, max_ops);
+                },
+                0x09 => { // DW_LNS_fixed_advance_pc
+                    state.address = state.address.wrapping_add(reader.u16()? as u64);
+                    state.op_index = 0;
+                },
+                0x0a => state.prologue_end = true,
+                0x0b => state.epilogue_begin = true,
+                0x0c => state.isa = reader.uleb()?,
+                _ => {
+                    // A standard opcode from a later version, or a vendor's.
+                    // The header says how many LEB128 operands it takes.
+                    for _ in 0..raw.standard_opcode_lengths[opcode as usize - 1] {
+                        reader.uleb()?;
+                    }
+                },
+            }
+        }
+        Ok(CompiledDebugLine {
+            rows,
+            file_names,
+            include_directories: raw.include_directories.clone(),
+            version: raw.version,
+        })
+    }
+
+    // The entry for a row's file register.
+    pub fn file(&self, index: u64) -> Option<&DebugLineFileEntry> {
+        let index = if self.version >= 5 { index } else { index.checked_sub(1)? };
+        self.file_names.get(usize::try_from(index).ok()?)
+    }
+
+    // Where the file for a row's file register lives, given `comp_dir`,
+    // its unit's DW_AT_comp_dir. Before version 5, directory 0 means the
+    // compilation directory and the header's list counts from 1.
+    pub fn file_path(&self, index: u64, comp_dir: Option<&str>) -> Option<PathBuf> {
+        let file = self.file(index)?;
+        let dir_index = if self.version >= 5 { Some(file.dir_index) } else { file.dir_index.checked_sub(1) };
+        let dir = dir_index.and_then(|i| self.include_directories.get(usize::try_from(i).ok()?));
+        Some(resolve_path(comp_dir, dir.map(String::as_str), &file.path))
+    }
+
+    // The row covering `address`: the last one at or before it whose
+    // sequence carries on past it.
+    pub fn row_for(&self, address: u64) -> Option<&LineRow> {
+        self.rows.windows(2)
+            .find(|pair| !pair[0].end_sequence
+                && pair[0].address <= address && address < pair[1].address)
+            .map(|pair| &pair[0])
+    }
+
+    // The rows as CSV, with a header line. Files are given by path, resolved
+    // against `comp_dir`, or left empty when the index is out of range.
+    pub fn to_csv(&self, comp_dir: Option<&str>) -> String {
+        let mut csv = "address,file,line,column,is_stmt,end_sequence\n".to_string();
+        for row in self.rows.iter() {
+            let path = self.file_path(row.file, comp_dir).unwrap_or_default();
+            writeln!(csv, "{:#x},{},{},{},{},{}", row.address, csv_field(&path.to_string_lossy()), row.line,
+                row.column, row.is_stmt, row.end_sequence).unwrap();
+        }
+        csv
+    }
+}
+
+// Quotes a CSV field if it holds a comma, quote or newline, doubling any
+// quotes inside it.
+fn csv_field(s: &str) -> Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", s.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+// Where a file named by a line table or DW_AT_decl_file lives. A relative
+// file is under its include directory `dir`, and a relative directory (or
+// a file with none) is under the unit's DW_AT_comp_dir.
+pub fn resolve_path(comp_dir: Option<&str>, dir: Option<&str>, file: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    for part in [comp_dir, dir].into_iter().flatten() {
+        // Pushing an absolute path replaces what's there already.
+        path.push(part);
+    }
+    path.push(file);
+    path
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Lays out a Mach-O file with one __DWARF segment holding `sections`.
+    pub fn macho_with_dwarf(sections: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        use crate::macho::tests::*;
+
+        let mut offset = 32 + 8 + 64 + 80 * sections.len();
+        let mut specs = vec![];
+        for (name, contents) in sections {
+            specs.push((*name, offset as u32, contents.len() as u64, 0, 0));
+            offset += contents.len();
+        }
+        let mut bytes = header_bytes(0x2, &[segment64_bytes("__DWARF", &specs)]);
+        for (_, contents) in sections {
+            bytes.extend_from_slice(contents);
+        }
+        bytes
+    }
+
+    // A compile unit for main.c with a single subprogram, main.
+    pub fn sample_sections() -> Vec<(&'static str, Vec<u8>)> {
+        let abbrev = vec![
+            0x01, 0x11, 0x01, // [1] DW_TAG_compile_unit, has children
+            0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+            0x25, 0x0e,       // DW_AT_producer, DW_FORM_strp
+            0x1b, 0x0e,       // DW_AT_comp_dir, DW_FORM_strp
+            0x00, 0x00,
+            0x02, 0x2e, 0x00, // [2] DW_TAG_subprogram, no children
+            0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+            0x11, 0x01,       // DW_AT_low_pc, DW_FORM_addr
+            0x12, 0x06,       // DW_AT_high_pc, DW_FORM_data4
+            0x00, 0x00,
+            0x00,
+        ];
+        let strs = b"\0main.c\0clang version 17\0/tmp/src\0main\0".to_vec();
+        let mut dies = vec![0x01];
+        for strp in [1u32, 8, 25] {
+            dies.extend_from_slice(&strp.to_ne_bytes());
+        }
+        dies.push(0x02);
+        dies.extend_from_slice(&34u32.to_ne_bytes());
+        dies.extend_from_slice(&0x100003f20u64.to_ne_bytes());
+        dies.extend_from_slice(&0x20u32.to_ne_bytes());
+        dies.push(0x00);
+        let mut info = vec![];
+        info.extend_from_slice(&(7 + dies.len() as u32).to_ne_bytes());
+        info.extend_from_slice(&4u16.to_ne_bytes());
+        info.extend_from_slice(&0u32.to_ne_bytes());
+        info.push(8);
+        info.extend(dies);
+        vec![
+            ("__debug_abbrev", abbrev),
+            ("__debug_str", strs),
+            ("__debug_info", info),
+        ]
+    }
+
+    #[test]
+    fn parses_sample_file() {
+        let bytes = macho_with_dwarf(&sample_sections());
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        let tags: Vec<DIETag> = file.dies().map(|die| die.tag).collect();
+        assert_eq!(tags, vec![DIETag::CompileUnit, DIETag::Subprogram]);
+        let main = file.dies().nth(1).unwrap();
+        assert_eq!(main.attrs[0].value.as_str(), Some("main"));
+        assert_eq!(main.attrs[1].value.as_u64(), Some(0x100003f20));
+    }
+
+    #[test]
+    fn attr_name_from_dwarf5_codes() {
+        assert!(matches!(AttrName::from(0x6f), AttrName::StringLengthBitSize));
+        assert!(matches!(AttrName::from(0x72), AttrName::StrOffsetsBase));
+        assert!(matches!(AttrName::from(0x73), AttrName::AddrBase));
+        assert!(matches!(AttrName::from(0x74), AttrName::RnglistsBase));
+        assert!(matches!(AttrName::from(0x76), AttrName::DwoName));
+        assert!(matches!(AttrName::from(0x87), AttrName::Noreturn));
+        assert!(matches!(AttrName::from(0x88), AttrName::Alignment));
+        assert!(matches!(AttrName::from(0x8c), AttrName::LoclistsBase));
+    }
+
+    #[test]
+    fn attr_name_from_unknown_code() {
+        // 0x75 is reserved in DWARF5.
+        assert!(matches!(AttrName::from(0x75), AttrName::Unrecognized(0x75)));
+        assert!(matches!(AttrName::from(0x8d), AttrName::Unrecognized(0x8d)));
+    }
+
+    #[test]
+    fn attr_value_getters() {
+        assert_eq!(AttrValue::Address(0x1000).as_u64(), Some(0x1000));
+        assert_eq!(AttrValue::Constant(7).as_u64(), Some(7));
+        assert_eq!(AttrValue::MacPtr(0x20).as_u64(), Some(0x20));
+        assert_eq!(AttrValue::OffsetReference(0x2b).as_u64(), Some(0x2b));
+        assert_eq!(AttrValue::ImplicitConst(4).as_u64(), Some(4));
+        assert_eq!(AttrValue::ImplicitConst(-1).as_u64(), None);
+        assert_eq!(AttrValue::Flag(true).as_u64(), None);
+
+        assert_eq!(AttrValue::Flag(true).as_bool(), Some(true));
+        assert_eq!(AttrValue::Flag(false).as_bool(), Some(false));
+        assert_eq!(AttrValue::Constant(1).as_bool(), None);
+
+        assert_eq!(AttrValue::StrP("main".to_string()).as_str(), Some("main"));
+        assert_eq!(AttrValue::Constant(0).as_str(), None);
+
+        assert_eq!(AttrValue::ExprLoc(vec![0x9c]).as_bytes(), Some(&[0x9c][..]));
+        assert_eq!(AttrValue::StrP("main".to_string()).as_bytes(), None);
+    }
+
+    #[test]
+    fn attr_value_as_expression() {
+        let value = AttrValue::ExprLoc(vec![0x91, 0x6c]);
+        assert_eq!(value.as_expression(8), Some(Ok(vec![expr::Op::Fbreg(-20)])));
+        assert_eq!(AttrValue::Constant(3).as_expression(8), None);
+    }
+
+    #[test]
+    fn attr_value_ref_sig8() {
+        let mut bytes = 0xe35a_4b2c_1d0f_9a87u64.to_ne_bytes().to_vec();
+        bytes.push(0xff); // Belongs to the next attribute.
+        let (value, size) = AttrValue::from(&bytes, AttrForm::RefSig8, Format::Dwarf32, 8, &[]).unwrap();
+        assert!(matches!(value, AttrValue::TypeSignature(0xe35a_4b2c_1d0f_9a87)));
+        assert_eq!(size, 8);
+    }
+
+    // A version 4 type unit for `signature`, whose type DIE is `die`.
+    fn type_unit_bytes(signature: u64, die: &[u8]) -> Vec<u8> {
+        let mut bytes = cu_header_bytes(8);
+        bytes[..4].copy_from_slice(&(19 + die.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&signature.to_ne_bytes());
+        bytes.extend_from_slice(&23u32.to_ne_bytes());
+        bytes.extend_from_slice(die);
+        bytes
+    }
+
+    fn debug_types_others() -> Vec<Section> {
+        vec![
+            Section::DebugAbbrev { abbrevs: vec![AbbrevDecl {
+                abbrev_code: 1,
+                tag: DIETag::StructureType,
+                has_children: false,
+                attr_specs: vec![AttrSpec { name: AttrName::ByteSize, form: AttrForm::Data1, implicit_const: None }],
+            }], tables: vec![(0, 0)] },
+            Section::DebugStr(DebugStr { bytes: vec![] }),
+        ]
+    }
+
+    #[test]
+    fn debug_types_section() {
+        let mut bytes = type_unit_bytes(0x1122_3344_5566_7788, &[0x01, 0x10]); // structure, byte_size 16
+        let second = bytes.len();
+        bytes.extend(type_unit_bytes(0x99aa_bbcc_ddee_ff00, &[0x01, 0x08]));   // structure, byte_size 8
+
+        match Section::from("__debug_types", &bytes, &debug_types_others()).unwrap() {
+            Section::DebugTypes { units } => {
+                assert_eq!(units.len(), 2);
+                let header = &units[0].header;
+                assert_eq!(header.header.version, 4);
+                assert_eq!(header.header.address_size, 8);
+                assert_eq!(header.type_signature, 0x1122_3344_5566_7788);
+                assert_eq!(header.type_offset, 23);
+                assert_eq!(units[0].dies.len(), 1);
+                assert!(matches!(units[0].dies[0].tag, DIETag::StructureType));
+                assert_eq!(units[0].dies[0].offset, 23);
+                assert!(matches!(units[0].dies[0].attrs[0].value, AttrValue::Constant(16)));
+
+                assert_eq!(units[1].offset, second);
+                assert_eq!(units[1].header.type_signature, 0x99aa_bbcc_ddee_ff00);
+                assert!(matches!(units[1].dies[0].attrs[0].value, AttrValue::Constant(8)));
+            },
+            sec => panic!("expected .debug_types, got {:?}", sec),
+        }
+    }
+
+    #[test]
+    fn debug_types_too_short() {
+        let others = debug_types_others();
+        let err = Section::from("__debug_types", &cu_header_bytes(8), &others).unwrap_err();
+        assert_eq!(err, "__debug_types is too short for a type unit header");
+        let mut bytes = type_unit_bytes(1, &[0x01, 0x10]);
+        bytes[..4].copy_from_slice(&0x40u32.to_ne_bytes());
+        let err = Section::from("__debug_types", &bytes, &others).unwrap_err();
+        assert_eq!(err, "type unit at 0x0 runs past end of __debug_types (0x44 bytes from 0x19)");
+    }
+
+    fn leaf(tag: DIETag) -> DIE {
+        DIE { offset: 0, tag, attrs: vec![], children: vec![] }
+    }
+
+    fn small_tree_file() -> File {
+        let cu = DIE {
+            offset: 11,
+            tag: DIETag::CompileUnit,
+            attrs: vec![],
+            children: vec![
+                DIE {
+                    offset: 0,
+                    tag: DIETag::Subprogram,
+                    attrs: vec![],
+                    children: vec![leaf(DIETag::Variable), leaf(DIETag::Variable)],
+                },
+                leaf(DIETag::Subprogram),
+                leaf(DIETag::BaseType),
+            ],
+        };
+        File {
+            sections: vec![Section::DebugInfo {
+                units: vec![CompilationUnit {
+                    offset: 0,
+                    header: CUHeader::from(&cu_header_bytes(8)).unwrap(),
+                    dies: vec![cu],
+                    dies_end: 0x44,
+                }],
+            }],
+            section_sizes: vec![0],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn file_dies_depth_first() {
+        let file = small_tree_file();
+        let tags: Vec<DIETag> = file.dies().map(|die| die.tag).collect();
+        assert_eq!(tags, vec![
+            DIETag::CompileUnit,
+            DIETag::Subprogram,
+            DIETag::Variable,
+            DIETag::Variable,
+            DIETag::Subprogram,
+            DIETag::BaseType,
+        ]);
+    }
+
+    #[test]
+    fn file_tag_histogram() {
+        let histogram = small_tree_file().tag_histogram();
+        assert_eq!(histogram.into_iter().collect::<Vec<_>>(), vec![
+            (DIETag::CompileUnit, 1),
+            (DIETag::BaseType, 1),
+            (DIETag::Subprogram, 2),
+            (DIETag::Variable, 2),
+        ]);
+    }
+
+    fn named(tag: DIETag, name: &str, attrs: Vec<DIEAttribute>, children: Vec<DIE>) -> DIE {
+        let mut attrs = attrs;
+        attrs.insert(0, DIEAttribute {
+            name: AttrName::Name,
+            value: AttrValue::StrP(name.to_string()),
+        });
+        DIE { offset: 0, tag, attrs, children }
+    }
+
+    fn file_of_units(units: Vec<DIE>) -> File {
+        File {
+            sections: vec![Section::DebugInfo {
+                units: units.into_iter()
+                    .map(|die| CompilationUnit {
+                        offset: 0,
+                        header: CUHeader::from(&cu_header_bytes(8)).unwrap(),
+                        dies: vec![die],
+                        dies_end: 0x44,
+                    })
+                    .collect(),
+            }],
+            section_sizes: vec![0],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn file_diff() {
+        let high_pc = |n| vec![DIEAttribute { name: AttrName::HighPc, value: AttrValue::Constant(n) }];
+        let before = file_of_units(vec![
+            named(DIETag::CompileUnit, "main.c", vec![], vec![
+                named(DIETag::Subprogram, "main", high_pc(0x20), vec![
+                    named(DIETag::Variable, "argc", vec![], vec![]),
+                ]),
+                named(DIETag::Subprogram, "helper", vec![], vec![]),
+                leaf(DIETag::BaseType),
+            ]),
+            named(DIETag::CompileUnit, "old.c", vec![], vec![]),
+        ]);
+        let after = file_of_units(vec![
+            named(DIETag::CompileUnit, "main.c", vec![], vec![
+                named(DIETag::Subprogram, "main", high_pc(0x30), vec![]),
+                named(DIETag::Variable, "argc", vec![], vec![]),
+                named(DIETag::Variable, "argv", vec![], vec![]),
+            ]),
+            named(DIETag::CompileUnit, "new.c", vec![], vec![]),
+        ]);
+
+        let diffs = before.diff(&after);
+        assert_eq!(diffs, vec![
+            Difference::RemovedDIE {
+                unit: "main.c".to_string(),
+                tag: DIETag::Subprogram,
+                name: "helper".to_string(),
+            },
+            Difference::ChangedAttr {
+                unit: "main.c".to_string(),
+                tag: DIETag::Subprogram,
+                name: "main".to_string(),
+                attr: AttrName::HighPc,
+                old: Some(AttrValue::Constant(0x20)),
+                new: Some(AttrValue::Constant(0x30)),
+            },
+            Difference::AddedDIE {
+                unit: "main.c".to_string(),
+                tag: DIETag::Variable,
+                name: "argv".to_string(),
+            },
+            Difference::RemovedUnit("old.c".to_string()),
+            Difference::AddedUnit("new.c".to_string()),
+        ]);
+        assert_eq!(diffs[1].to_string(),
+            "~ main.c: DW_TAG_Subprogram main: DW_AT_high_pc Some(Constant(20)) -> Some(Constant(30))");
+        assert!(after.diff(&after).is_empty());
+    }
+
+    #[test]
+    fn die_address_class() {
+        let abbrevs = vec![AbbrevDecl {
+            abbrev_code: 1,
+            tag: DIETag::PointerType,
+            has_children: false,
+            attr_specs: vec![
+                AttrSpec { name: AttrName::AddressClass, form: AttrForm::Data1, implicit_const: None },
+                AttrSpec { name: AttrName::Segment, form: AttrForm::Data2, implicit_const: None },
+            ],
+        }];
+        let (die, size) = DIE::from(&[0x01, 0x02, 0x34, 0x12], Format::Dwarf32, 8, &AbbrevTable::new(&abbrevs), &[]).unwrap();
+        assert_eq!(size, 4);
+        assert_eq!(die.address_class(), Some(2));
+        assert_eq!(die.segment(), Some(0x1234));
+        assert_eq!(leaf(DIETag::PointerType).address_class(), None);
+    }
+
+    #[test]
+    fn die_member_offset() {
+        let member = |value| DIE {
+            attrs: vec![DIEAttribute { name: AttrName::DataMemberLocation, value }],
+            ..leaf(DIETag::Member)
+        };
+        assert_eq!(member(AttrValue::Constant(8)).member_offset(), Some(8));
+        // DW_OP_plus_uconst 0x90
+        assert_eq!(member(AttrValue::ExprLoc(vec![0x23, 0x90, 0x01])).member_offset(), Some(0x90));
+        // DW_OP_dup, DW_OP_deref, DW_OP_constu 24, DW_OP_minus, DW_OP_deref, DW_OP_plus
+        let virtual_base = vec![0x12, 0x06, 0x10, 24, 0x1c, 0x06, 0x22];
+        assert_eq!(member(AttrValue::ExprLoc(virtual_base)).member_offset(), None);
+        assert_eq!(member(AttrValue::ExprLoc(vec![0x23])).member_offset(), None);
+        assert_eq!(leaf(DIETag::Member).member_offset(), None);
+
+        let abbrevs = vec![AbbrevDecl {
+            abbrev_code: 1,
+            tag: DIETag::Member,
+            has_children: false,
+            attr_specs: vec![AttrSpec { name: AttrName::DataMemberLocation, form: AttrForm::Udata, implicit_const: None }],
+        }];
+        let (die, _) = DIE::from(&[0x01, 0x80, 0x02], Format::Dwarf32, 8, &AbbrevTable::new(&abbrevs), &[]).unwrap();
+        assert_eq!(die.member_offset(), Some(0x100));
+    }
+
+    // A version 4 line program header for main.c, followed by `program`.
+    pub fn debug_line_bytes(program: &[u8]) -> Vec<u8> {
+        let mut header = vec![
+            1,                                  // minimum_instruction_length
+            1,                                  // maximum_operations_per_instruction
+            1,                                  // default_is_stmt
+            -5i8 as u8,                         // line_base
+            14,                                 // line_range
+            13,                                 // opcode_base
+            0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1, // standard_opcode_lengths
+            0,                                  // include_directories
+        ];
+        header.extend_from_slice(b"main.c\0\0\0\0");
+        header.push(0);
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&((2 + 4 + header.len() + program.len()) as u32).to_ne_bytes());
+        bytes.extend_from_slice(&4u16.to_ne_bytes());
+        bytes.extend_from_slice(&(header.len() as u32).to_ne_bytes());
+        bytes.extend(header);
+        bytes.extend_from_slice(program);
+        bytes
+    }
+
+    #[test]
+    fn line_program_extended_opcodes() {
+        let mut program = vec![0x00, 9, 0x02]; // DW_LNE_set_address 0x1000
+        program.extend_from_slice(&0x1000u64.to_ne_bytes());
+        program.extend_from_slice(&[
+            19,                                 // special: line += 1
+            0x00, 4, 0x80, 0xaa, 0xbb, 0xcc,    // vendor extended opcode
+            0x00, 8, 0x03, b'b', b'.', b'c', 0, 0, 0, 0, // DW_LNE_define_file
+            0x04, 2,                            // DW_LNS_set_file 2
+            0x00, 2, 0x04, 3,                   // DW_LNE_set_discriminator 3
+            75,                                 // special: address += 4, line += 1
+            0x02, 2,                            // DW_LNS_advance_pc 2
+            0x00, 1, 0x01,                      // DW_LNE_end_sequence
+        ]);
+        let section = Section::from("__debug_line", &debug_line_bytes(&program), &[]).unwrap();
+        let Section::DebugLine { compiled, .. } = section else { panic!("not a line section") };
+
+        let rows: Vec<(u64, u64, u64, u64, bool)> = compiled.rows.iter()
+            .map(|row| (row.address, row.file, row.line, row.discriminator, row.end_sequence))
+            .collect();
+        assert_eq!(rows, vec![
+            (0x1000, 1, 2, 0, false),
+            (0x1004, 2, 3, 3, false),
+            (0x1006, 2, 3, 0, true),
+        ]);
+        let paths: Vec<&str> = compiled.file_names.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["main.c", "b.c"]);
+    }
+
+    #[test]
+    fn line_program_vliw_op_index() {
+        // Bundles of three operations, each bundle 8 bytes long.
+        let mut bytes = debug_line_bytes(&[
+            0x00, 9, 0x02, 0, 0x10, 0, 0, 0, 0, 0, 0, // DW_LNE_set_address 0x1000
+            0x02, 2,                            // DW_LNS_advance_pc 2 ops
+            0x01,                               // DW_LNS_copy
+            47,                                 // special: 2 ops on, line += 1
+            0x02, 4,                            // DW_LNS_advance_pc 4 ops
+            0x01,                               // DW_LNS_copy
+            0x09, 0x10, 0x00,                   // DW_LNS_fixed_advance_pc 0x10
+            0x01,                               // DW_LNS_copy
+            0x00, 1, 0x01,                      // DW_LNE_end_sequence
+        ]);
+        bytes[10] = 8; // minimum_instruction_length
+        bytes[11] = 3; // maximum_operations_per_instruction
+        if cfg!(target_endian = "big") {
+            bytes[17..21].reverse();
+        }
+        let section = Section::from("__debug_line", &bytes, &[]).unwrap();
+        let Section::DebugLine { compiled, .. } = section else { panic!("not a line section") };
+        let rows: Vec<(u64, u64, u64)> = compiled.rows.iter()
+            .map(|row| (row.address, row.op_index, row.line))
+            .collect();
+        assert_eq!(rows, vec![
+            (0x1000, 2, 1),
+            (0x1008, 1, 2),
+            (0x1010, 2, 2),
+            (0x1020, 0, 2),
+            (0x1020, 0, 2),
+        ]);
+    }
+
+    #[test]
+    fn line_program_addresses_wrap() {
+        let mut program = vec![0x00, 9, 0x02]; // DW_LNE_set_address
+        program.extend_from_slice(&(u64::MAX - 0xf).to_ne_bytes());
+        program.push(0x09);                    // DW_LNS_fixed_advance_pc 0x20
+        program.extend_from_slice(&0x20u16.to_ne_bytes());
+        program.push(0x01);                    // DW_LNS_copy
+        program.push(0x02);                    // DW_LNS_advance_pc 2^64 - 0x10
+        program.extend_from_slice(&[0xf0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        program.extend_from_slice(&[
+            0x01,                              // DW_LNS_copy
+            0x00, 1, 0x01,                     // DW_LNE_end_sequence
+        ]);
+        let section = Section::from("__debug_line", &debug_line_bytes(&program), &[]).unwrap();
+        let Section::DebugLine { compiled, .. } = section else { panic!("not a line section") };
+        let addresses: Vec<u64> = compiled.rows.iter().map(|row| row.address).collect();
+        assert_eq!(addresses, vec![0x10, 0, 0]);
+    }
+
+    #[test]
+    fn line_program_skips_unknown_standard_opcodes() {
+        let mut program = vec![0x00, 9, 0x02]; // DW_LNE_set_address 0x1000
+        program.extend_from_slice(&0x1000u64.to_ne_bytes());
+        program.extend_from_slice(&[
+            0x0d, 0x80, 0x01, 0x05,             // opcode 13, with two operands
+            0x01,                               // DW_LNS_copy
+            76,                                 // special: address += 4, line += 1
+            0x00, 1, 0x01,                      // DW_LNE_end_sequence
+        ]);
+        let mut bytes = debug_line_bytes(&program);
+        bytes[15] = 14;    // opcode_base
+        bytes.insert(28, 2); // standard_opcode_lengths[13 - 1]
+        for field in [0..4, 6..10] {
+            let length = u32::from_ne_bytes(bytes[field.clone()].try_into().unwrap());
+            bytes[field].copy_from_slice(&(length + 1).to_ne_bytes());
+        }
+        let section = Section::from("__debug_line", &bytes, &[]).unwrap();
+        let Section::DebugLine { mut raw, compiled } = section else { panic!("not a line section") };
+        assert_eq!(raw.standard_opcode_lengths.len(), 13);
+        let rows: Vec<(u64, u64)> = compiled.rows.iter().map(|row| (row.address, row.line)).collect();
+        assert_eq!(rows, vec![(0x1000, 1), (0x1004, 2), (0x1004, 2)]);
+
+        raw.standard_opcode_lengths.pop();
+        assert_eq!(CompiledDebugLine::from(&raw).unwrap_err(),
+            "__debug_line header has 12 standard_opcode_lengths for opcode_base 14");
+        bytes[15] = 0;
+        assert_eq!(RawDebugLine::from(&bytes, &[], &[]).unwrap_err(), "opcode_base in __debug_line header is 0");
+    }
+
+    #[test]
+    fn resolve_paths() {
+        use std::path::Path;
+        let resolve = resolve_path;
+        assert_eq!(resolve(Some("/src"), Some("include"), "a.h"), Path::new("/src/include/a.h"));
+        assert_eq!(resolve(Some("/src"), Some("/usr/include"), "a.h"), Path::new("/usr/include/a.h"));
+        assert_eq!(resolve(Some("/src"), Some("include"), "/tmp/a.h"), Path::new("/tmp/a.h"));
+        assert_eq!(resolve(Some("/src"), Some("/usr/include"), "/tmp/a.h"), Path::new("/tmp/a.h"));
+        assert_eq!(resolve(None, None, "/tmp/a.h"), Path::new("/tmp/a.h"));
+        assert_eq!(resolve(Some("/src"), None, "main.c"), Path::new("/src/main.c"));
+        assert_eq!(resolve(None, Some("include"), "a.h"), Path::new("include/a.h"));
+        assert_eq!(resolve(None, Some("/usr/include"), "a.h"), Path::new("/usr/include/a.h"));
+        assert_eq!(resolve(None, None, "main.c"), Path::new("main.c"));
+    }
+
+    #[test]
+    fn line_table_csv() {
+        let mut program = vec![0x00, 9, 0x02]; // DW_LNE_set_address 0x1000
+        program.extend_from_slice(&0x1000u64.to_ne_bytes());
+        program.extend_from_slice(&[
+            0x05, 7,                            // DW_LNS_set_column 7
+            19,                                 // special: line += 1
+            0x06,                               // DW_LNS_negate_stmt
+            75,                                 // special: address += 4, line += 1
+            0x00, 1, 0x01,                      // DW_LNE_end_sequence
+        ]);
+        let section = Section::from("__debug_line", &debug_line_bytes(&program), &[]).unwrap();
+        let Section::DebugLine { mut compiled, .. } = section else { panic!("not a line section") };
+        assert_eq!(compiled.to_csv(None), concat!(
+            "address,file,line,column,is_stmt,end_sequence\n",
+            "0x1000,main.c,2,7,true,false\n",
+            "0x1004,main.c,3,7,false,false\n",
+            "0x1004,main.c,3,7,false,true\n",
+        ));
+        assert!(compiled.to_csv(Some("/tmp/src")).contains("\n0x1000,/tmp/src/main.c,2,"));
+
+        // Paths are under their include directory, counted from 1 before
+        // version 5, and that's under the compilation directory.
+        compiled.include_directories = vec!["include".to_string()];
+        compiled.file_names[0].dir_index = 1;
+        assert_eq!(compiled.file_path(1, Some("/tmp/src")), Some(PathBuf::from("/tmp/src/include/main.c")));
+        compiled.version = 5;
+        compiled.file_names[0].dir_index = 0;
+        assert_eq!(compiled.file_path(0, Some("/tmp/src")), Some(PathBuf::from("/tmp/src/include/main.c")));
+
+        assert_eq!(csv_field("a,b.c"), "\"a,b.c\"");
+        assert_eq!(csv_field("say \"hi\".c"), "\"say \"\"hi\"\".c\"");
+        assert_eq!(csv_field("plain.c"), "plain.c");
+    }
+
+    #[test]
+    fn line_header_versions() {
+        // Version 3 headers have no maximum_operations_per_instruction.
+        let mut bytes = debug_line_bytes(&[0x00, 1, 0x01]);
+        bytes.remove(11);
+        for field in [0..4, 6..10] {
+            let length = u32::from_ne_bytes(bytes[field.clone()].try_into().unwrap());
+            bytes[field].copy_from_slice(&(length - 1).to_ne_bytes());
+        }
+        bytes[4..6].copy_from_slice(&3u16.to_ne_bytes());
+        let raw = RawDebugLine::from(&bytes, &[], &[]).unwrap();
+        assert_eq!(raw.maximum_operations_per_instruction, 1);
+        assert_eq!(raw.line_base, -5);
+        assert_eq!(raw.opcode_base, 13);
+        assert_eq!(raw.file_names.len(), 1);
+
+        bytes[4..6].copy_from_slice(&6u16.to_ne_bytes());
+        assert_eq!(RawDebugLine::from(&bytes, &[], &[]).unwrap_err(),
+            "line program has version 6, which isn't supported");
+
+        // Truncated headers are errors, not panics.
+        bytes[4..6].copy_from_slice(&3u16.to_ne_bytes());
+        assert_eq!(RawDebugLine::from(&bytes[..4], &[], &[]).unwrap_err(),
+            "line program header needs 0x6 bytes, but __debug_line has 0x4");
+        assert_eq!(RawDebugLine::from(&bytes[..12], &[], &[]).unwrap_err(),
+            "line program header needs 0xf bytes, but __debug_line has 0xc");
+        assert!(RawDebugLine::from(&bytes[..30], &[], &[]).is_err());
+    }
+
+    #[test]
+    fn line_header_v5_line_strp() {
+        let line_strs = b"/tmp/src\0main.c\0".to_vec();
+        let mut header = vec![
+            1, 1, 1, -5i8 as u8, 14, 13,         // as in version 4
+            0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1,  // standard_opcode_lengths
+            1, 0x01, 0x1f,                       // directory format: path as line_strp
+            1, 0, 0, 0, 0,                       // one directory, at offset 0
+            2, 0x01, 0x1f, 0x02, 0x0b,           // file format: path, data1 dir index
+            1, 9, 0, 0, 0, 0,                    // one file, at offset 9, dir 0
+        ];
+        let program = [0x00, 1, 0x01];           // DW_LNE_end_sequence
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&((2 + 2 + 4 + header.len() + program.len()) as u32).to_ne_bytes());
+        bytes.extend_from_slice(&5u16.to_ne_bytes());
+        bytes.extend_from_slice(&[8, 0]);        // address_size, segment_selector_size
+        bytes.extend_from_slice(&(header.len() as u32).to_ne_bytes());
+        bytes.append(&mut header);
+        bytes.extend_from_slice(&program);
+
+        let others = vec![Section::DebugLineStr(DebugStr { bytes: line_strs })];
+        let Section::DebugLine { raw, compiled } =
+            Section::from("__debug_line", &bytes, &others).unwrap()
+        else { panic!("not a line section") };
+        assert_eq!(raw.address_size, 8);
+        assert_eq!(raw.include_directories, vec!["/tmp/src"]);
+        assert_eq!(raw.file_names, vec![DebugLineFileEntry {
+            path: "main.c".to_string(),
+            dir_index: 0,
+            last_mod: 0,
+            file_len: 0,
+        }]);
+        assert_eq!(compiled.rows.len(), 1);
+
+        let err = Section::from("__debug_line", &bytes, &[]).unwrap_err();
+        assert_eq!(err, "offset 0x0 is past the end of __debug_line_str");
+    }
+
+    #[test]
+    fn object_segment_skips_code_and_data() {
+        use crate::macho::tests::*;
+
+        // An object's one unnamed segment, with __bss taking no space in
+        // the file and __text pointing at the Mach-O header.
+        let sections = sample_sections();
+        let mut offset = 32 + 8 + 64 + 80 * (sections.len() + 2);
+        let mut specs = vec![("__text", 0, 0x20, 0, 0), ("__bss", 0, 0x10_0000, 0, 0)];
+        for (name, contents) in sections.iter() {
+            specs.push((*name, offset as u32, contents.len() as u64, 0, 0));
+            offset += contents.len();
+        }
+        let mut bytes = header_bytes(0x1, &[segment64_bytes("", &specs)]);
+        for (_, contents) in sections.iter() {
+            bytes.extend_from_slice(contents);
+        }
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.dwarf_segment().unwrap(), &bytes).unwrap();
+        let names: Vec<&str> = file.sections.iter().map(|sec| sec.name()).collect();
+        assert_eq!(names, sections.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        assert_eq!(file.units().count(), 1);
+    }
+
+    #[test]
+    fn abbrevs_only_ignores_corrupt_info() {
+        let mut sections = sample_sections();
+        sections[2].1.truncate(5);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let segment = macho.segment("__DWARF").unwrap();
+        assert!(File::from(&macho, segment, &bytes).is_err());
+
+        let file = File::abbrevs_only(segment, &bytes).unwrap();
+        let Section::DebugAbbrev { abbrevs, .. } = &file.sections[0] else { panic!("not abbrevs") };
+        let tags: Vec<DIETag> = abbrevs.iter().map(|abbrev| abbrev.tag).collect();
+        assert_eq!(tags, vec![DIETag::CompileUnit, DIETag::Subprogram]);
+        assert!(file.to_string().starts_with(".debug_abbrev contents:"));
+    }
+
+    // Wraps `bytes` in a zlib stream of one stored, uncompressed block.
+    #[cfg(feature = "zlib")]
+    fn zlib_stored(bytes: &[u8]) -> Vec<u8> {
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(bytes.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(bytes);
+        let (a, b) = bytes.iter()
+            .fold((1u32, 0u32), |(a, b), &byte| ((a + byte as u32) % 65521, (b + a + byte as u32) % 65521));
+        stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        stream
+    }
+
+    #[test]
+    #[cfg(feature = "zlib")]
+    fn compressed_sections() {
+        let parse = |name, contents: Vec<u8>| {
+            let mut sections = sample_sections();
+            sections[0] = (name, contents);
+            let bytes = macho_with_dwarf(&sections);
+            let macho = macho::File::from(&bytes).unwrap();
+            File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).map(|file| file.to_string())
+        };
+        let abbrev = sample_sections().remove(0).1;
+        let expected = parse("__debug_abbrev", abbrev.clone()).unwrap();
+
+        let mut gnu = b"ZLIB".to_vec();
+        gnu.extend_from_slice(&(abbrev.len() as u64).to_be_bytes());
+        gnu.extend(zlib_stored(&abbrev));
+        assert_eq!(parse("__zdebug_abbrev", gnu.clone()).unwrap(), expected);
+
+        // Listed and verified at their decompressed sizes.
+        let mut sections = sample_sections();
+        let info = sections[2].1.clone();
+        let mut compressed_info = b"ZLIB".to_vec();
+        compressed_info.extend_from_slice(&(info.len() as u64).to_be_bytes());
+        compressed_info.extend(zlib_stored(&info));
+        sections[0] = ("__zdebug_abbrev", gnu.clone());
+        sections[2] = ("__zdebug_info", compressed_info);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        assert_eq!(file.listing()[0], ("__debug_abbrev", abbrev.len()));
+        assert_eq!(file.listing()[2], ("__debug_info", info.len()));
+        assert_eq!(file.verify(), Vec::<String>::new());
+
+        // Only __zdebug_* sections are compressed, whatever the others
+        // start with.
+        let mut sections = sample_sections();
+        sections.push(("__debug_foo", b"ZLIB, but not compressed".to_vec()));
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        assert!(matches!(&file.sections[3], Section::Unrecognized { contents, .. }
+            if contents == b"ZLIB, but not compressed"));
+
+        gnu[11] += 1;
+        assert_eq!(parse("__zdebug_abbrev", gnu.clone()).unwrap_err(),
+            "__zdebug_abbrev decompressed to 0x17 bytes, not the 0x18 its header gives");
+        gnu[11] -= 2;
+        assert_eq!(parse("__zdebug_abbrev", gnu).unwrap_err(),
+            "decompressing __zdebug_abbrev: deflate stream inflates past the 0x16 bytes expected");
+        assert_eq!(parse("__zdebug_abbrev", abbrev).unwrap_err(),
+            "__zdebug_abbrev doesn't start with a ZLIB compression header");
+        assert_eq!(parse("__zdebug_abbrev", b"ZLIB".to_vec()).unwrap_err(),
+            "__zdebug_abbrev is too short for its compression header");
+    }
+
+    #[test]
+    #[cfg(not(feature = "zlib"))]
+    fn compressed_sections_need_zlib() {
+        let mut sections = sample_sections();
+        let mut gnu = b"ZLIB".to_vec();
+        gnu.extend_from_slice(&(sections[0].1.len() as u64).to_be_bytes());
+        sections[0] = ("__zdebug_abbrev", gnu);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        assert_eq!(File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap_err(),
+            "decompressing __zdebug_abbrev: this build leaves out zlib support");
+    }
+
+    #[test]
+    fn file_location_list() {
+        let mut loc = vec![0xee; 4]; // another list's bytes, before ours
+        for (begin, end, expr) in [
+            (0x10u64, 0x20u64, &[0x50][..]), // DW_OP_reg0
+            (u64::MAX, 0x2000, &[][..]),     // new base address
+            (0x04, 0x08, &[0x91, 0x7c][..]), // DW_OP_fbreg -4
+            (0, 0, &[][..]),
+        ] {
+            loc.extend_from_slice(&begin.to_ne_bytes());
+            loc.extend_from_slice(&end.to_ne_bytes());
+            if begin != 0 && begin != u64::MAX {
+                loc.extend_from_slice(&(expr.len() as u16).to_ne_bytes());
+                loc.extend_from_slice(expr);
+            }
+        }
+        let mut file = small_tree_file();
+        file.sections.push(Section::from("__debug_loc", &loc, &[]).unwrap());
+
+        let unit = file.units().next().unwrap();
+        assert_eq!(file.location_list(unit, 4, 0x1000).unwrap(), vec![
+            (0x1010, 0x1020, vec![0x50]),
+            (0x2004, 0x2008, vec![0x91, 0x7c]),
+        ]);
+        assert_eq!(file.location_list(unit, 0x1000, 0).unwrap_err(),
+            "location list offset 0x1000 is past end of __debug_loc");
+        // Offsets from a high base wrap rather than overflow.
+        assert_eq!(file.location_list(unit, 4, u64::MAX - 0x10).unwrap()[0],
+            (u64::MAX - 0x10 + 0x10, 0xf, vec![0x50]));
+
+        // A unit with 4-byte addresses reads its lists with them too.
+        let mut loc = vec![];
+        for word in [0x10u32, 0x20] {
+            loc.extend_from_slice(&word.to_ne_bytes());
+        }
+        loc.extend_from_slice(&[0x01, 0x00, 0x50]);
+        loc.extend_from_slice(&[0; 8]);
+        let file = File {
+            sections: vec![Section::from("__debug_loc", &loc, &[]).unwrap()],
+            section_sizes: vec![loc.len()],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        let unit = CompilationUnit {
+            offset: 0,
+            header: CUHeader::from(&cu_header_bytes(4)).unwrap(),
+            dies: vec![],
+            dies_end: 0,
+        };
+        assert_eq!(file.location_list(&unit, 0, 0x1000).unwrap(), vec![(0x1010, 0x1020, vec![0x50])]);
+    }
+
+    // A DWARF 5 lists section with 8-byte addresses, whose offsets array
+    // points at each of `lists` in turn.
+    fn lists_section_bytes(lists: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = vec![];
+        let mut offset = 4 * lists.len();
+        for list in lists {
+            body.extend_from_slice(&(offset as u32).to_ne_bytes());
+            offset += list.len();
+        }
+        for list in lists {
+            body.extend_from_slice(list);
+        }
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&5u16.to_ne_bytes());
+        bytes.extend_from_slice(&[8, 0]);
+        bytes.extend_from_slice(&(lists.len() as u32).to_ne_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn file_rnglist() {
+        let mut second = vec![
+            0x04, 0x10, 0x20,           // DW_RLE_offset_pair
+            0x07,                       // DW_RLE_start_length
+        ];
+        second.extend_from_slice(&0x5000u64.to_ne_bytes());
+        second.extend_from_slice(&[0x80, 0x01, 0x00]);
+        let mut bytes = lists_section_bytes(&[vec![0x00], second]);
+        let first_len = bytes.len();
+        bytes.extend(lists_section_bytes(&[vec![0x04, 0x30, 0x40, 0x00]]));
+        let mut file = small_tree_file();
+        file.sections.push(Section::from("__debug_rnglists", &bytes, &[]).unwrap());
+
+        let unit = file.units().next().unwrap();
+        assert_eq!(file.rnglist(unit, 0, 0x1000).unwrap(), vec![]);
+        assert_eq!(file.rnglist(unit, 1, 0x1000).unwrap(), vec![(0x1010, 0x1020), (0x5000, 0x5080)]);
+        assert_eq!(file.rnglist(unit, 2, 0x1000).unwrap_err(),
+            "list index 2 is out of range; there are 2 offsets");
+
+        // A unit whose DW_AT_rnglists_base points past the second header.
+        let based = |base: usize| CompilationUnit {
+            offset: 0,
+            header: CUHeader::from(&cu_header_bytes(8)).unwrap(),
+            dies: vec![DIE {
+                offset: 11,
+                tag: DIETag::CompileUnit,
+                attrs: vec![DIEAttribute { name: AttrName::RnglistsBase, value: AttrValue::MacPtr(base as u64) }],
+                children: vec![],
+            }],
+            dies_end: 0,
+        };
+        assert_eq!(file.rnglist(&based(first_len + 12), 0, 0x1000).unwrap(), vec![(0x1030, 0x1040)]);
+        assert_eq!(file.rnglist(&based(first_len + 12), 1, 0x1000).unwrap_err(),
+            "list index 1 is out of range; there are 1 offsets");
+        assert_eq!(file.rnglist(&based(4), 0, 0x1000).unwrap_err(),
+            "list base 0x4 leaves no room for a header");
+        assert!(file.rnglist(&based(bytes.len() + 12), 0, 0x1000).is_err());
+        // Offsets and lengths past the top of the address space wrap.
+        assert_eq!(file.rnglist(unit, 1, u64::MAX - 0xf).unwrap()[0], (0, 0x10));
+    }
+
+    #[test]
+    fn file_loclist() {
+        let mut list = vec![
+            0x04, 0x10, 0x20, 0x01, 0x50, // DW_LLE_offset_pair, DW_OP_reg0
+            0x06,                         // DW_LLE_base_address
+        ];
+        list.extend_from_slice(&0x2000u64.to_ne_bytes());
+        list.extend_from_slice(&[0x04, 0x00, 0x04, 0x01, 0x51]); // offset_pair, DW_OP_reg1
+        list.push(0x08);                                         // DW_LLE_start_length
+        list.extend_from_slice(&0x3000u64.to_ne_bytes());
+        list.extend_from_slice(&[0x08, 0x02, 0x91, 0x7c, 0x00]); // DW_OP_fbreg -4
+        let bytes = lists_section_bytes(&[list]);
+        let mut file = small_tree_file();
+        file.sections.push(Section::from("__debug_loclists", &bytes, &[]).unwrap());
+
+        let unit = file.units().next().unwrap();
+        assert_eq!(file.loclist(unit, 0, 0x1000).unwrap(), vec![
+            (0x1010, 0x1020, vec![0x50]),
+            (0x2000, 0x2004, vec![0x51]),
+            (0x3000, 0x3008, vec![0x91, 0x7c]),
+        ]);
+    }
+
+    #[test]
+    fn file_indexed_str() {
+        let mut offsets = vec![];
+        offsets.extend_from_slice(&12u32.to_ne_bytes());
+        offsets.extend_from_slice(&5u16.to_ne_bytes());
+        offsets.extend_from_slice(&[0, 0]);
+        for offset in [4u32, 0] {
+            offsets.extend_from_slice(&offset.to_ne_bytes());
+        }
+        let mut file = small_tree_file();
+        file.sections.push(Section::from("__debug_str_offs", &offsets, &[]).unwrap());
+        file.sections.push(Section::from("__debug_str", b"int\0main\0", &[]).unwrap());
+
+        let unit = file.units().next().unwrap();
+        assert_eq!(file.indexed_str(unit, 0), Ok("main"));
+        assert_eq!(file.indexed_str(unit, 1), Ok("int"));
+        assert_eq!(file.indexed_str(unit, 2).unwrap_err(),
+            "string index 2 is past end of __debug_str_offs");
+        assert!(file.indexed_str(unit, u64::MAX).is_err());
+    }
+
+    #[test]
+    fn index_forms() {
+        let read = |bytes: &[u8], form| AttrValue::from(bytes, form, Format::Dwarf32, 8, &[]);
+        assert_eq!(read(&[0x85, 0x01], AttrForm::Strx), Ok((AttrValue::StrIndex(0x85), 2)));
+        assert_eq!(read(&[0x07], AttrForm::Strx1), Ok((AttrValue::StrIndex(7), 1)));
+        assert_eq!(read(&0x0102u16.to_ne_bytes(), AttrForm::Addrx2), Ok((AttrValue::AddrIndex(0x0102), 2)));
+        let three = &0x030201u32.to_ne_bytes()[..];
+        let three = if cfg!(target_endian = "little") { &three[..3] } else { &three[1..] };
+        assert_eq!(read(three, AttrForm::Strx3), Ok((AttrValue::StrIndex(0x030201), 3)));
+        assert_eq!(read(three, AttrForm::Addrx3), Ok((AttrValue::AddrIndex(0x030201), 3)));
+        assert_eq!(read(&[0x02], AttrForm::Rnglistx), Ok((AttrValue::ListIndex(2), 1)));
+        assert_eq!(read(&[0x02], AttrForm::Loclistx), Ok((AttrValue::ListIndex(2), 1)));
+        assert!(matches!(AttrForm::from(0x2c), AttrForm::Addrx4));
+        assert_eq!(AttrForm::from(0x23).to_string(), "DW_FORM_rnglistx");
+    }
+
+    #[test]
+    fn die_language() {
+        let language = |tag, code| DIE {
+            offset: 0,
+            tag,
+            attrs: vec![DIEAttribute { name: AttrName::Language, value: AttrValue::Constant(code) }],
+            children: vec![],
+        }.language();
+        assert_eq!(language(DIETag::CompileUnit, 0x001c), Some(DwLang::Rust));
+        assert_eq!(language(DIETag::CompileUnit, 0x000c), Some(DwLang::C99));
+        assert_eq!(language(DIETag::CompileUnit, 0x0021), Some(DwLang::CPlusPlus14));
+        assert_eq!(language(DIETag::CompileUnit, 0x9999), Some(DwLang::Unrecognized(0x9999)));
+        assert_eq!(language(DIETag::Subprogram, 0x001c), None);
+        assert_eq!(leaf(DIETag::CompileUnit).language(), None);
+    }
+
+    #[test]
+    fn die_encoding() {
+        let base_type = |encoding, size| DIE {
+            offset: 0,
+            tag: DIETag::BaseType,
+            attrs: vec![
+                DIEAttribute { name: AttrName::Encoding, value: AttrValue::Constant(encoding) },
+                DIEAttribute { name: AttrName::ByteSize, value: AttrValue::Constant(size) },
+            ],
+            children: vec![],
+        };
+        for (code, encoding) in [
+            (0x02, DwAte::Boolean),
+            (0x04, DwAte::Float),
+            (0x05, DwAte::Signed),
+            (0x06, DwAte::SignedChar),
+            (0x07, DwAte::Unsigned),
+            (0x08, DwAte::UnsignedChar),
+            (0x10, DwAte::UTF),
+            (0x80, DwAte::Unrecognized(0x80)),
+        ] {
+            assert_eq!(base_type(code, 4).encoding(), Some(encoding));
+        }
+        assert_eq!(base_type(0x05, 4).byte_size(), Some(4));
+        assert_eq!(leaf(DIETag::BaseType).encoding(), None);
+        let mut not_base = base_type(0x05, 4);
+        not_base.tag = DIETag::Variable;
+        assert_eq!(not_base.encoding(), None);
+    }
+
+    // A DIE at `offset` with the given tag, name and DW_AT_type reference.
+    fn typed(offset: usize, tag: DIETag, name: Option<&str>, ty: Option<u64>) -> DIE {
+        let mut attrs = vec![];
+        if let Some(name) = name {
+            attrs.push(DIEAttribute { name: AttrName::Name, value: AttrValue::StrP(name.to_string()) });
+        }
+        if let Some(ty) = ty {
+            attrs.push(DIEAttribute { name: AttrName::Type, value: AttrValue::OffsetReference(ty) });
+        }
+        DIE { offset, tag, attrs, children: vec![] }
+    }
+
+    // int; const int; const int *; char *const; a cycle; and a variable.
+    fn types_unit() -> CompilationUnit {
+        let mut cu = typed(0x0b, DIETag::CompileUnit, Some("types.c"), None);
+        cu.children = vec![
+            typed(0x10, DIETag::BaseType, Some("int"), None),
+            typed(0x14, DIETag::ConstType, None, Some(0x10)),
+            typed(0x18, DIETag::PointerType, None, Some(0x14)),
+            typed(0x1c, DIETag::BaseType, Some("char"), None),
+            typed(0x20, DIETag::PointerType, None, Some(0x1c)),
+            typed(0x24, DIETag::ConstType, None, Some(0x20)),
+            typed(0x28, DIETag::PointerType, None, Some(0x2c)),
+            typed(0x2c, DIETag::PointerType, None, Some(0x28)),
+            typed(0x30, DIETag::Variable, Some("p"), Some(0x18)),
+            typed(0x34, DIETag::PointerType, None, None),
+        ];
+        CompilationUnit {
+            offset: 0,
+            header: CUHeader::from(&cu_header_bytes(8)).unwrap(),
+            dies: vec![cu],
+            dies_end: 0x44,
+        }
+    }
+
+    #[test]
+    fn compilation_unit_type_name() {
+        let unit = types_unit();
+        let die = |offset| unit.die_at(offset).unwrap();
+        assert_eq!(unit.type_name(die(0x30)).as_deref(), Some("const int *"));
+        assert_eq!(unit.type_name(die(0x18)).as_deref(), Some("const int *"));
+        assert_eq!(unit.type_name(die(0x24)).as_deref(), Some("char * const"));
+        assert_eq!(unit.type_name(die(0x34)).as_deref(), Some("void *"));
+        assert_eq!(unit.type_name(die(0x28)), None);
+        assert_eq!(unit.type_name(die(0x0b)), None);
+    }
+
+    #[test]
+    fn compilation_unit_name_and_comp_dir() {
+        let root = DIE {
+            offset: 0,
+            tag: DIETag::CompileUnit,
+            attrs: vec![
+                DIEAttribute { name: AttrName::Name, value: AttrValue::StrP("src/main.c".to_string()) },
+                DIEAttribute { name: AttrName::CompDir, value: AttrValue::StrP("/home/me/proj".to_string()) },
+            ],
+            children: vec![],
+        };
+        let built = build::unit(&root);
+        let abbrevs = match Section::from("__debug_abbrev", &built.abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+        let cu = CompilationUnit::from(&built.info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &built.strs).unwrap();
+        assert_eq!(cu.name(), Some("src/main.c"));
+        assert_eq!(cu.comp_dir(), Some("/home/me/proj"));
+
+        // A DW_FORM_string name, stored inline after the abbrev code.
+        let abbrev = [0x01, 0x11, 0x00, 0x03, 0x08, 0x00, 0x00, 0x00];
+        let abbrevs = match Section::from("__debug_abbrev", &abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+        let body = b"\x01inline.c\0";
+        let mut info = ((7 + body.len()) as u32).to_ne_bytes().to_vec();
+        info.extend_from_slice(&4u16.to_ne_bytes());
+        info.extend_from_slice(&0u32.to_ne_bytes());
+        info.push(8);
+        info.extend_from_slice(body);
+        let cu = CompilationUnit::from(&info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &[]).unwrap();
+        assert_eq!(cu.name(), Some("inline.c"));
+        assert_eq!(AttrValue::from(b"ab\0cd", AttrForm::Stringg, Format::Dwarf32, 8, &[]),
+            Ok((AttrValue::StrP("ab".to_string()), 3)));
+        assert!(AttrValue::from(b"ab", AttrForm::Stringg, Format::Dwarf32, 8, &[]).is_err());
+
+        let unit = types_unit();
+        assert_eq!(unit.name(), Some("types.c"));
+        assert_eq!(unit.comp_dir(), None);
+    }
+
+    #[test]
+    fn compilation_unit_resolve() {
+        let mut unit = types_unit();
+        let mut point = typed(0x40, DIETag::StructureType, Some("point"), None);
+        point.children = vec![typed(0x48, DIETag::Member, Some("x"), Some(0x10))];
+        unit.dies[0].children.push(point);
+
+        let member = unit.die_at(0x48).unwrap();
+        let ty = unit.resolve(member.attr(&AttrName::Type).unwrap()).unwrap();
+        assert_eq!((ty.offset, ty.tag), (0x10, DIETag::BaseType));
+        assert!(unit.resolve(&AttrValue::OffsetReference(0x44)).is_none());
+        assert!(unit.resolve(&AttrValue::Constant(0x10)).is_none());
+    }
+
+    #[test]
+    fn ref_forms_read_unit_offsets() {
+        let bytes = 0x1234u64.to_ne_bytes();
+        assert_eq!(AttrValue::from(&bytes, AttrForm::Ref8, Format::Dwarf32, 8, &[]),
+            Ok((AttrValue::OffsetReference(0x1234), 8)));
+        assert_eq!(AttrValue::from(&[0xb4, 0x24], AttrForm::RefUdata, Format::Dwarf32, 8, &[]),
+            Ok((AttrValue::OffsetReference(0x1234), 2)));
+    }
+
+    #[test]
+    fn section_offset_forms_follow_format() {
+        let strs = b"\0abc\0int\0";
+        // The same offset, 5, written for each format and followed by the
+        // next attribute's bytes.
+        let dwarf32 = [&5u32.to_ne_bytes()[..], &[0xff; 8]].concat();
+        let dwarf64 = [&5u64.to_ne_bytes()[..], &[0xff; 8]].concat();
+        for (bytes, format, size) in [(dwarf32, Format::Dwarf32, 4), (dwarf64, Format::Dwarf64, 8)] {
+            let read = |form| AttrValue::from(&bytes, form, format, 8, strs);
+            assert_eq!(read(AttrForm::RefAddr), Ok((AttrValue::SectionReference(5), size)));
+            assert_eq!(read(AttrForm::SecOffset), Ok((AttrValue::MacPtr(5), size)));
+            assert_eq!(read(AttrForm::StrP), Ok((AttrValue::StrP("int".to_string()), size)));
+        }
+        assert!(AttrValue::from(&[0; 4], AttrForm::RefAddr, Format::Dwarf64, 8, strs).is_err());
+    }
+
+    #[test]
+    fn truncated_values_are_errors() {
+        for form in [AttrForm::Addr, AttrForm::Data2, AttrForm::Data4, AttrForm::Flag, AttrForm::Ref1,
+                     AttrForm::Ref8, AttrForm::RefSig8, AttrForm::StrP, AttrForm::Stringg] {
+            let value = AttrValue::from(&[], form.clone(), Format::Dwarf32, 8, &[]);
+            assert!(value.is_err(), "{} read {:?} from nothing", form, value);
+        }
+        assert!(AttrValue::from(&[0; 4], AttrForm::Addr, Format::Dwarf32, 8, &[]).is_err());
+        assert_eq!(AttrValue::from(&[0; 4], AttrForm::Addr, Format::Dwarf32, 3, &[]).unwrap_err(),
+            "can't read 3-byte addresses");
+        // An exprloc whose length runs past the end, or past usize.
+        assert!(AttrValue::from(&[0x03, 0x50], AttrForm::ExprLoc, Format::Dwarf32, 8, &[]).is_err());
+        let huge = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(AttrValue::from(&huge, AttrForm::ExprLoc, Format::Dwarf32, 8, &[]).is_err());
+        assert_eq!(AttrValue::from(&[0x01, 0x50, 0xff], AttrForm::ExprLoc, Format::Dwarf32, 8, &[]),
+            Ok((AttrValue::ExprLoc(vec![0x50]), 2)));
+    }
+
+    #[test]
+    fn unimplemented_forms_are_errors() {
+        assert_eq!(AttrValue::from(&[0; 8], AttrForm::Data8, Format::Dwarf32, 8, &[]),
+            Err("can't read values of form DW_FORM_data8 yet".to_string()));
+        assert_eq!(AttrValue::from(&[], AttrForm::FlagPresent, Format::Dwarf32, 8, &[]), Ok((AttrValue::Flag(true), 0)));
+
+        let abbrevs = vec![AbbrevDecl {
+            abbrev_code: 1,
+            tag: DIETag::Variable,
+            has_children: false,
+            attr_specs: vec![AttrSpec { name: AttrName::ConstValue, form: AttrForm::SData, implicit_const: None }],
+        }];
+        let err = DIE::from(&[0x01, 0x80, 0x01], Format::Dwarf32, 8, &AbbrevTable::new(&abbrevs), &[]).unwrap_err();
+        assert_eq!(err, "can't read values of form DW_FORM_sdata yet");
+    }
+
+    #[test]
+    fn die_resolved_attr() {
+        let reference = |name, offset| DIEAttribute { name, value: AttrValue::OffsetReference(offset) };
+        let mut unit = types_unit();
+        let declaration = typed(0x40, DIETag::Subprogram, Some("area"), Some(0x10));
+        let mut definition = typed(0x50, DIETag::Subprogram, None, None);
+        definition.attrs.push(reference(AttrName::Specification, 0x40));
+        let mut inlined = typed(0x60, DIETag::InlinedSubroutine, None, None);
+        inlined.attrs.push(reference(AttrName::AbstractOrigin, 0x50));
+        let mut looped = typed(0x70, DIETag::Subprogram, None, None);
+        looped.attrs.push(reference(AttrName::Specification, 0x78));
+        let mut looped_back = typed(0x78, DIETag::Subprogram, None, None);
+        looped_back.attrs.push(reference(AttrName::Specification, 0x70));
+        unit.dies[0].children.extend([declaration, definition, inlined, looped, looped_back]);
+
+        let name = |offset| unit.die_at(offset).unwrap()
+            .resolved_attr(&unit, &AttrName::Name)
+            .and_then(|name| name.as_str());
+        assert_eq!(unit.die_at(0x50).unwrap().attr(&AttrName::Name), None);
+        assert_eq!(name(0x50), Some("area"));
+        assert_eq!(name(0x60), Some("area"));
+        assert_eq!(name(0x70), None);
+    }
+
+    #[test]
+    fn attr_name_and_form_spellings() {
+        assert_eq!(AttrName::StmtList.to_string(), "DW_AT_stmt_list");
+        assert_eq!(AttrName::LowPc.to_string(), "DW_AT_low_pc");
+        assert_eq!(AttrName::UseUTF8.to_string(), "DW_AT_use_UTF8");
+        assert_eq!(AttrName::Unrecognized(0x3e01).to_string(), "DW_AT_unknown_0x3e01");
+        assert_eq!(AttrForm::StrP.to_string(), "DW_FORM_strp");
+        assert_eq!(AttrForm::Stringg.to_string(), "DW_FORM_string");
+        assert_eq!(AttrForm::RefSig8.to_string(), "DW_FORM_ref_sig8");
+        assert_eq!(AttrForm::FlagPresent.to_string(), "DW_FORM_flag_present");
+        assert_eq!(AttrForm::Unrecognized(0x2c).to_string(), "DW_FORM_unknown_0x2c");
+
+        let die = typed(0x10, DIETag::BaseType, Some("int"), None);
+        assert!(die.to_string().contains("\tDW_AT_name"), "{}", die);
+    }
+
+    #[test]
+    fn die_describe_exprloc_as_hex() {
+        let die = DIE {
+            offset: 0,
+            tag: DIETag::Variable,
+            attrs: vec![
+                DIEAttribute {
+                    name: AttrName::Location,
+                    value: AttrValue::ExprLoc(vec![0x03, 0x20, 0, 0, 0, 0, 0, 0, 0]),
+                },
+                DIEAttribute { name: AttrName::FrameBase, value: AttrValue::ExprLoc(vec![]) },
+            ],
+            children: vec![],
+        };
+        assert_eq!(die.describe(), format!(
+            "DW_TAG_Variable\n\t{:<26} 03 20 00 00 00 00 00 00 00\n\t{:<26} \n",
+            "DW_AT_location", "DW_AT_frame_base"));
+    }
+
+    #[test]
+    fn unit_at_second_unit() {
+        let mut sections = sample_sections();
+        let first = sections[2].1.clone();
+        // Name the second unit differently so it can be told apart.
+        let mut second = first.clone();
+        second[12..16].copy_from_slice(&(sections[1].1.len() as u32).to_ne_bytes());
+        sections[1].1.extend_from_slice(b"other.c\0");
+        sections[2].1.extend(second);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let segment = macho.segment("__DWARF").unwrap();
+
+        let unit = File::unit_at(&macho, segment, &bytes, first.len()).unwrap();
+        assert_eq!(unit.offset, first.len());
+        assert_eq!(unit.dies[0].attr(&AttrName::Name).and_then(|name| name.as_str()),
+            Some("other.c"));
+        assert!(unit.to_string().contains("other.c"));
+        let unit = File::unit_at(&macho, segment, &bytes, 0).unwrap();
+        assert_ne!(unit.dies[0].attr(&AttrName::Name).and_then(|name| name.as_str()),
+            Some("other.c"));
+
+        let err = File::unit_at(&macho, segment, &bytes, 2 * first.len()).unwrap_err();
+        assert_eq!(err, format!("offset {:#x} is past the end of __debug_info ({:#x} bytes)",
+            2 * first.len(), 2 * first.len()));
+    }
+
+    #[test]
+    fn nth_unit_of_three() {
+        let mut sections = sample_sections();
+        let first = sections[2].1.clone();
+        for name in ["second.c", "third.c"] {
+            let mut unit = first.clone();
+            unit[12..16].copy_from_slice(&(sections[1].1.len() as u32).to_ne_bytes());
+            sections[1].1.extend_from_slice(name.as_bytes());
+            sections[1].1.push(0);
+            sections[2].1.extend(unit);
+        }
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let segment = macho.segment("__DWARF").unwrap();
+
+        let unit = File::nth_unit(&macho, segment, &bytes, 1).unwrap();
+        assert_eq!(unit.offset, first.len());
+        assert_eq!(unit.name(), Some("second.c"));
+        assert_eq!(File::nth_unit(&macho, segment, &bytes, 2).unwrap().name(), Some("third.c"));
+        assert_eq!(File::nth_unit(&macho, segment, &bytes, 3).unwrap_err(),
+            "__debug_info has only 3 units");
+    }
+
+    #[test]
+    fn canonical_section_kinds() {
+        assert_eq!(canonical_section("__debug_info"), Some(DwarfSectionKind::Info));
+        assert_eq!(canonical_section(".debug_info"), Some(DwarfSectionKind::Info));
+        assert_eq!(canonical_section(".debug_str_offsets"), Some(DwarfSectionKind::StrOffsets));
+        assert_eq!(canonical_section("__apple_names"), Some(DwarfSectionKind::AppleNames));
+        assert_eq!(canonical_section("__debug_bogus"), None);
+        assert_eq!(canonical_section("__text"), None);
+        assert_eq!(canonical_section("debug_info"), None);
+    }
+
+    #[test]
+    fn truncated_section_names() {
+        assert_eq!(canonical_section("__debug_str_offs"), Some(DwarfSectionKind::StrOffsets));
+        assert_eq!(canonical_section("__apple_namespac"), Some(DwarfSectionKind::AppleNamespace));
+        assert_eq!(canonical_section("__debug_line_str"), Some(DwarfSectionKind::LineStr));
+
+        let contents = [8, 0, 0, 0, 5, 0, 0, 0, 1, 0, 0, 0];
+        let section = Section::from("__debug_str_offs", &contents, &[]).unwrap();
+        match &section {
+            Section::DebugStrOffsets { contents: parsed } => assert_eq!(parsed, &contents),
+            section => panic!("expected string offsets, got {:?}", section),
+        }
+        assert_eq!(section.name(), "__debug_str_offs");
+    }
+
+    #[test]
+    fn debug_info_with_two_units() {
+        let mut sections = sample_sections();
+        let info = sections[2].1.clone();
+        sections[2].1.extend(info);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        let units: Vec<&CompilationUnit> = file.units().collect();
+        assert_eq!(units.len(), 2);
+        assert_eq!(units[1].offset, units[0].header.unit_size());
+        assert_eq!(units[1].dies[0].offset, 11);
+        assert_eq!(units[1].dies[0].children[0].offset, 24);
+        assert_eq!(file.dies().count(), 4);
+    }
+
+    #[test]
+    fn missing_debug_abbrev() {
+        let mut sections = sample_sections();
+        sections.remove(0);
+        sections.push(("__debug_line", debug_line_bytes(&[0x00, 1, 0x01])));
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+
+        assert_eq!(file.warnings, ["__debug_info left unparsed, as there's no __debug_abbrev"]);
+        assert_eq!(file.units().count(), 0);
+        assert!(matches!(&file.sections[1],
+            Section::Unrecognized { name, contents } if name == "__debug_info" && *contents == sections[1].1));
+        assert_eq!(file.line_table().unwrap().rows.len(), 1);
+        assert!(file.to_string().contains("Unrecognized __debug_info"));
+    }
+
+    #[test]
+    fn die_at_debug_info_offset() {
+        let mut sections = sample_sections();
+        let info = sections[2].1.clone();
+        sections[2].1.extend(info);
+        let bytes = macho_with_dwarf(&sections);
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        let second = file.units().nth(1).unwrap();
+        let subprogram = &second.dies[0].children[0];
+
+        let found = file.die_at_offset(second.offset + subprogram.offset).unwrap();
+        assert!(std::ptr::eq(found, subprogram));
+        assert_eq!(file.die_at_offset(second.offset + 11).unwrap().tag, DIETag::CompileUnit);
+        assert_eq!(file.die_at_offset(11).unwrap().children.len(), 1);
+        assert!(file.die_at_offset(12).is_none());
+        assert!(file.die_at_offset(second.offset + second.header.unit_size()).is_none());
+    }
+
+    #[test]
+    fn verify_finds_unread_bytes() {
+        let parse = |sections: &[(&str, Vec<u8>)]| {
+            let bytes = macho_with_dwarf(sections);
+            let macho = macho::File::from(&bytes).unwrap();
+            File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap()
+        };
+        let mut sections = sample_sections();
+        assert_eq!(parse(&sections).verify(), Vec::<String>::new());
+
+        // Three bytes of padding inside the unit, after its DIEs.
+        let info = &mut sections[2].1;
+        let unit_length = u32::from_ne_bytes(info[0..4].try_into().unwrap());
+        info[0..4].copy_from_slice(&(unit_length + 3).to_ne_bytes());
+        info.extend_from_slice(&[0, 0, 0]);
+        let size = info.len();
+        assert_eq!(parse(&sections).verify(), [format!(
+            "__debug_info: unit at 0x0 is {:#x} bytes, but its DIEs end at {:#x}, leaving 3 bytes unread",
+            size, size - 3)]);
+    }
+
+    #[test]
+    fn short_debug_info() {
+        let mut sections = sample_sections();
+        let info = sections[2].1.clone();
+        let parse = |sections: &[(&str, Vec<u8>)]| {
+            let bytes = macho_with_dwarf(sections);
+            let macho = macho::File::from(&bytes).unwrap();
+            File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes)
+        };
+
+        sections[2].1.truncate(8);
+        assert_eq!(parse(&sections).unwrap_err(),
+            "unit at 0x0 in __debug_info: unit header needs 11 bytes, but only 8 remain");
+
+        // A truncated unit after a whole one.
+        sections[2].1 = info.clone();
+        sections[2].1.extend_from_slice(&info[..3]);
+        assert_eq!(parse(&sections).unwrap_err(), format!(
+            "unit at {:#x} in __debug_info: ran out of bytes reading unit_length", info.len()));
+
+        sections[2].1.clear();
+        assert_eq!(parse(&sections).unwrap().units().count(), 0);
+    }
+
+    #[test]
+    fn abbrev_decl_from() {
+        let bytes = [
+            0x02, 0x2e, 0x01, // [2] DW_TAG_subprogram, has children
+            0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+            0x3f, 0x19,       // DW_AT_external, DW_FORM_flag_present
+            0x00, 0x00,
+            0x03,             // Start of the next declaration.
+        ];
+        let (decl, size) = AbbrevDecl::from(&bytes).unwrap();
+        assert_eq!(size, 9);
+        assert_eq!(decl.abbrev_code, 2);
+        assert!(matches!(decl.tag, DIETag::Subprogram));
+        assert!(decl.has_children);
+        assert_eq!(decl.attr_specs.len(), 2);
+        assert!(matches!(decl.attr_specs[1].name, AttrName::External));
+        assert!(matches!(decl.attr_specs[1].form, AttrForm::FlagPresent));
+        assert_eq!(decl.attr_specs[1].implicit_const, None);
+
+        // DW_FORM_implicit_const carries an SLEB128 value after the form.
+        let bytes = [
+            0x01, 0x34, 0x00, // [1] DW_TAG_variable, no children
+            0x3b, 0x21, 0x7e, // DW_AT_decl_line, DW_FORM_implicit_const -2
+            0x03, 0x0b,       // DW_AT_name, DW_FORM_data1
+            0x00, 0x00,
+        ];
+        let (decl, size) = AbbrevDecl::from(&bytes).unwrap();
+        assert_eq!(size, bytes.len());
+        assert_eq!(decl.attr_specs[0].form, AttrForm::ImplicitConst);
+        assert_eq!(decl.attr_specs[0].implicit_const, Some(-2));
+        assert_eq!(decl.attr_specs[1].form, AttrForm::Data1);
+
+        // The DIE holds only the data1 value.
+        let (die, size) = DIE::from(&[0x01, 0x07], Format::Dwarf32, 8, &AbbrevTable::new(&[decl]), &[]).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(die.attrs[0].value, AttrValue::ImplicitConst(-2));
+        assert_eq!(die.attrs[1].value, AttrValue::Constant(7));
+    }
+
+    #[test]
+    fn die_tag_vendor_range() {
+        // DW_TAG_GNU_call_site
+        assert_eq!(DIETag::from(0x4109), DIETag::Unknown(0x4109));
+        assert_eq!(DIETag::from(0x4080), DIETag::LoUser);
+        assert_eq!(DIETag::from(0xffff), DIETag::HiUser);
+        let (decl, _) = AbbrevDecl::from(&[0x01, 0x89, 0x82, 0x01, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(decl.tag, DIETag::Unknown(0x4109));
+        assert_eq!(decl.tag.to_string(), "DW_TAG_unknown_0x4109");
+        assert_eq!(DIETag::LoUser.to_string(), "DW_TAG_LoUser");
+    }
+
+    #[test]
+    fn die_tags_in_hash_set() {
+        let tags: HashSet<DIETag> = [
+            DIETag::Subprogram,
+            DIETag::Variable,
+            DIETag::Subprogram,
+            DIETag::Unknown(0x4109),
+            DIETag::Unknown(0x4109),
+            DIETag::Unknown(0x410a),
+        ].into_iter().collect();
+        assert_eq!(tags.len(), 4);
+        assert!(tags.contains(&DIETag::Unknown(0x410a)));
+        assert!(!tags.contains(&DIETag::CompileUnit));
+    }
+
+    #[test]
+    fn die_tag_from_name() {
+        assert_eq!(DIETag::from_name("subprogram"), Some(DIETag::Subprogram));
+        assert_eq!(DIETag::from_name("base_type"), Some(DIETag::BaseType));
+        assert_eq!(DIETag::from_name("lo_user"), Some(DIETag::LoUser));
+        assert_eq!(DIETag::from_name("no_such_tag"), None);
+        assert_eq!(DIETag::from_name("DW_TAG_compile_unit"), Some(DIETag::CompileUnit));
+        assert_eq!(DIETag::from_name("dw_tag_Pointer_Type"), Some(DIETag::PointerType));
+        assert_eq!(DIETag::from_name("DW_TAG_"), None);
+    }
+
+    #[test]
+    fn attr_name_from_name() {
+        assert_eq!(AttrName::from_name("decl_file"), Some(AttrName::DeclFile));
+        assert_eq!(AttrName::from_name("DW_AT_name"), Some(AttrName::Name));
+        assert_eq!(AttrName::from_name("DW_AT_LOW_PC"), Some(AttrName::LowPc));
+        assert_eq!(AttrName::from_name("use_utf8"), Some(AttrName::UseUTF8));
+        assert_eq!(AttrName::from_name("dw_at_no_such_attr"), None);
+    }
+
+    #[test]
+    fn abbrev_decl_from_truncated() {
+        let err = AbbrevDecl::from(&[0x02, 0x2e, 0x01, 0x03]).unwrap_err();
+        assert_eq!(err, "ran out of bytes reading ULEB128 at offset 0x4");
+    }
+
+    #[test]
+    fn abbrev_table_missing_terminator() {
+        // A complete declaration, but no null entry after it.
+        let bytes = [0x01, 0x11, 0x00, 0x03, 0x0e, 0x00, 0x00];
+        let err = Section::from("__debug_abbrev", &bytes, &[]).unwrap_err();
+        assert_eq!(err, "__debug_abbrev ends without a null entry");
+
+        // Cut off partway through the attribute specs.
+        let err = Section::from("__debug_abbrev", &bytes[..4], &[]).unwrap_err();
+        assert_eq!(err, "ran out of bytes reading ULEB128 at offset 0x4");
+
+        let mut terminated = bytes.to_vec();
+        terminated.push(0x00);
+        let Section::DebugAbbrev { abbrevs, .. } =
+            Section::from("__debug_abbrev", &terminated, &[]).unwrap()
+        else { panic!("not abbrevs") };
+        assert_eq!(abbrevs.len(), 1);
+    }
+
+    #[test]
+    fn abbrev_table_duplicate_codes() {
+        let bytes = [
+            0x01, 0x11, 0x00, 0x03, 0x0e, 0x00, 0x00, // [1] DW_TAG_compile_unit
+            0x02, 0x24, 0x00, 0x00, 0x00,             // [2] DW_TAG_base_type
+            0x01, 0x2e, 0x00, 0x00, 0x00,             // [1] DW_TAG_subprogram
+            0x00,
+        ];
+        let err = Section::from("__debug_abbrev", &bytes, &[]).unwrap_err();
+        assert_eq!(err, "__debug_abbrev declares code 0x1 more than once");
+    }
+
+    // A DW_TAG_lexical_block with children and no attributes.
+    #[test]
+    fn parallel_units_match_sequential() {
+        let mut info = vec![];
+        let mut abbrev = vec![];
+        for language in 0..9 {
+            let built = build::unit(&DIE {
+                offset: 0,
+                tag: DIETag::CompileUnit,
+                attrs: vec![DIEAttribute { name: AttrName::Language, value: AttrValue::Constant(language) }],
+                children: vec![],
+            });
+            info.extend(built.info);
+            abbrev = built.abbrev;
+        }
+        let abbrevs = match Section::from("__debug_abbrev", &abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+        let table = AbbrevTables::new(&abbrevs, &[(0, 0)]);
+        let offsets = CompilationUnit::offsets(&info).unwrap();
+        assert_eq!(offsets.len(), 9);
+
+        let sequential = CompilationUnit::parse_all(&info, &offsets, &table, &[]).unwrap();
+        let parallel = CompilationUnit::parse_all_parallel(&info, &offsets, &table, &[]).unwrap();
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel[8].dies[0].language(), Some(DwLang::from(8)));
+
+        // Both stop at the same first error.
+        info[offsets[3] + 11] = 0x7f;
+        info[offsets[6] + 11] = 0x7e;
+        let err = CompilationUnit::parse_all(&info, &offsets, &table, &[]).unwrap_err();
+        assert_eq!(err, "found no abbrev matching code: 0x7f");
+        assert_eq!(CompilationUnit::parse_all_parallel(&info, &offsets, &table, &[]).unwrap_err(), err);
+    }
+
+    #[test]
+    fn abbrev_table_matches_linear_search() {
+        let decl = |abbrev_code, tag| AbbrevDecl { abbrev_code, tag, has_children: false, attr_specs: vec![] };
+        let decls = vec![
+            decl(3, DIETag::BaseType),
+            decl(1, DIETag::CompileUnit),
+            decl(0x200, DIETag::Variable),
+            decl(1, DIETag::Subprogram),
+        ];
+        let table = AbbrevTable::new(&decls);
+        for code in [0, 1, 2, 3, 0x200, 0x201] {
+            let linear = decls.iter().find(|decl| decl.abbrev_code == code);
+            assert_eq!(table.get(code).map(|decl| decl.tag), linear.map(|decl| decl.tag), "code {}", code);
+        }
+    }
+
+    fn lexical_block_abbrevs() -> Vec<AbbrevDecl> {
+        vec![AbbrevDecl {
+            abbrev_code: 1,
+            tag: DIETag::LexicalBlock,
+            has_children: true,
+            attr_specs: vec![],
+        }]
+    }
+
+    // `depth` lexical blocks, each nested in the last.
+    fn nested_blocks(depth: usize) -> Vec<u8> {
+        let mut bytes = vec![0x01; depth];
+        bytes.extend(vec![0x00; depth]);
+        bytes
+    }
+
+    #[test]
+    fn die_nesting_within_limit() {
+        let bytes = nested_blocks(3);
+        let (die, size) = DIE::from(&bytes, Format::Dwarf32, 8, &AbbrevTable::new(&lexical_block_abbrevs()), &[]).unwrap();
+        assert_eq!(size, 6);
+        assert_eq!(die.children.len(), 1);
+        assert_eq!(die.children[0].children.len(), 1);
+        assert!(die.children[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    fn die_nesting_past_limit() {
+        let bytes = nested_blocks(10_000);
+        let err = DIE::from(&bytes, Format::Dwarf32, 8, &AbbrevTable::new(&lexical_block_abbrevs()), &[]).unwrap_err();
+        assert_eq!(err, "DIE tree nested deeper than 256 levels");
+    }
+
+    #[test]
+    fn relocations_fix_up_low_pc_in_objects() {
+        use crate::macho::tests::*;
+
+        let abbrev = vec![
+            0x01, 0x11, 0x00, // [1] DW_TAG_compile_unit, no children
+            0x11, 0x01,       // DW_AT_low_pc, DW_FORM_addr
+            0x00, 0x00,
+            0x00,
+        ];
+        let strs = b"\0".to_vec();
+        let mut info = vec![];
+        info.extend_from_slice(&16u32.to_ne_bytes()); // unit_length
+        info.extend_from_slice(&4u16.to_ne_bytes());  // version
+        info.extend_from_slice(&0u32.to_ne_bytes());  // debug_abbrev_offset
+        info.push(8);                                 // address_size
+        info.push(0x01);                              // abbrev code
+        info.extend_from_slice(&0x10u64.to_ne_bytes()); // low_pc addend
+
+        let loads_size = (8 + 64 + 3 * 80) + 24;
+        let abbrev_off = 32 + loads_size;
+        let str_off = abbrev_off + abbrev.len();
+        let info_off = str_off + strs.len();
+        let reloc_off = info_off + info.len();
+        let sym_off = reloc_off + 8;
+        let segment = segment64_bytes("__DWARF", &[
+            ("__debug_abbrev", abbrev_off as u32, abbrev.len() as u64, 0, 0),
+            ("__debug_str", str_off as u32, strs.len() as u64, 0, 0),
+            ("__debug_info", info_off as u32, info.len() as u64, reloc_off as u32, 1),
+        ]);
+        let mut bytes = header_bytes(0x1, &[segment, symtab_bytes(sym_off as u32, 1, 0, 0)]);
+        bytes.extend(abbrev);
+        bytes.extend(strs);
+        bytes.extend(info);
+        // An extern, 8-byte UNSIGNED relocation at the low_pc's offset.
+        bytes.extend(relocation_bytes(12, 0, 0, 3, 1, 0));
+        bytes.extend(symbol_bytes(0, 0x0f, 1, 0, 0x100000f00));
+
+        let macho = macho::File::from(&bytes).unwrap();
+        let segment = macho.segment("__DWARF").unwrap();
+        let file = File::from(&macho, segment, &bytes).unwrap();
+        let die = file.sections.iter()
+            .find_map(|sec| match sec {
+                Section::DebugInfo { units } => Some(&units[0].dies[0]),
+                _ => None,
+            })
+            .unwrap();
+        assert!(matches!(die.attrs[0].value, AttrValue::Address(0x100000f10)));
+
+        // Moving __debug_info past the end of the file leaves the
+        // relocation nowhere to go.
+        let info_offset_field = 32 + 72 + 2 * 80 + 48;
+        bytes[info_offset_field..info_offset_field + 4].copy_from_slice(&u32::MAX.to_ne_bytes());
+        let macho = macho::File::from(&bytes).unwrap();
+        let err = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap_err();
+        assert_eq!(err, "relocation at 0xc in __debug_info is past the end of the file");
+    }
+
+    fn cu_header_bytes(address_size: u8) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&0x40u32.to_ne_bytes());
+        bytes.extend_from_slice(&4u16.to_ne_bytes());
+        bytes.extend_from_slice(&0u32.to_ne_bytes());
+        bytes.push(address_size);
+        bytes
+    }
+
+    #[test]
+    fn cu_header_accepts_known_address_sizes() {
+        for size in [1, 2, 4, 8] {
+            let header = CUHeader::from(&cu_header_bytes(size)).unwrap();
+            assert_eq!(header.address_size, size);
+        }
+    }
+
+    #[test]
+    fn cu_header_rejects_reserved_unit_length() {
+        let mut bytes = cu_header_bytes(8);
+        bytes[0..4].copy_from_slice(&0xfffffff0u32.to_ne_bytes());
+        assert_eq!(CUHeader::from(&bytes).unwrap_err(), "unit_length 0xfffffff0 is reserved");
+        bytes[0..4].copy_from_slice(&0xfffffffeu32.to_ne_bytes());
+        assert_eq!(CUHeader::from(&bytes).unwrap_err(), "unit_length 0xfffffffe is reserved");
+    }
+
+    #[test]
+    fn cu_header_dwarf64() {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&0xffffffffu32.to_ne_bytes());
+        bytes.extend_from_slice(&0x1_0000_0040u64.to_ne_bytes());
+        bytes.extend_from_slice(&4u16.to_ne_bytes());
+        bytes.extend_from_slice(&0x20u64.to_ne_bytes());
+        bytes.push(8);
+        let header = CUHeader::from(&bytes).unwrap();
+        assert_eq!(header.format, Format::Dwarf64);
+        assert_eq!(header.unit_length, 0x1_0000_0040);
+        assert_eq!(header.version, 4);
+        assert_eq!(header.debug_abbrev_offset, 0x20);
+        assert_eq!(header.address_size, 8);
+        assert_eq!(header.size(), 23);
+
+        assert_eq!(CUHeader::from(&bytes[..20]).unwrap_err(),
+            "unit header needs 23 bytes, but only 20 remain");
+    }
+
+    #[test]
+    fn cu_header_v5() {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&8u32.to_ne_bytes());
+        bytes.extend_from_slice(&5u16.to_ne_bytes());
+        bytes.push(DW_UT_COMPILE);
+        bytes.push(8);
+        bytes.extend_from_slice(&0x20u32.to_ne_bytes());
+        let header = CUHeader::from(&bytes).unwrap();
+        assert_eq!(header.version, 5);
+        assert_eq!(header.unit_type, Some(DW_UT_COMPILE));
+        assert_eq!(header.address_size, 8);
+        assert_eq!(header.debug_abbrev_offset, 0x20);
+        assert_eq!(header.size(), 12);
+    }
+
+    #[test]
+    fn compilation_unit_four_byte_addresses() {
+        let abbrev = [
+            0x01, 0x11, 0x00, // [1] DW_TAG_compile_unit, no children
+            0x11, 0x01,       // DW_AT_low_pc, DW_FORM_addr
+            0x12, 0x06,       // DW_AT_high_pc, DW_FORM_data4
+            0x00, 0x00, 0x00,
+        ];
+        let abbrevs = match Section::from("__debug_abbrev", &abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+        let mut body = vec![0x01];
+        body.extend_from_slice(&0x1000u32.to_ne_bytes());
+        body.extend_from_slice(&0x20u32.to_ne_bytes());
+        let mut info = ((7 + body.len()) as u32).to_ne_bytes().to_vec();
+        info.extend_from_slice(&4u16.to_ne_bytes());
+        info.extend_from_slice(&0u32.to_ne_bytes());
+        info.push(4);
+        info.extend(body);
+
+        let tables = AbbrevTables::new(&abbrevs, &[(0, 0)]);
+        let cu = CompilationUnit::from(&info, 0, &tables, &[]).unwrap();
+        assert_eq!(cu.dies[0].attr(&AttrName::LowPc), Some(&AttrValue::Address(0x1000)));
+        assert_eq!(cu.high_pc(&cu.dies[0]), Some(0x1020));
+        assert_eq!(cu.dies_end, info.len());
+    }
+
+    #[test]
+    fn compilation_unit_versions() {
+        let root = DIE {
+            offset: 0,
+            tag: DIETag::CompileUnit,
+            attrs: vec![
+                DIEAttribute { name: AttrName::LowPc, value: AttrValue::Address(0x1000) },
+                DIEAttribute { name: AttrName::HighPc, value: AttrValue::Constant(0x2000) },
+            ],
+            children: vec![],
+        };
+        let mut built = build::unit(&root);
+        let abbrevs = match Section::from("__debug_abbrev", &built.abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+
+        let cu = CompilationUnit::from(&built.info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &built.strs).unwrap();
+        assert_eq!(cu.version(), 4);
+        assert_eq!(cu.high_pc(&cu.dies[0]), Some(0x3000));
+        let overflowing = DIE {
+            attrs: vec![
+                DIEAttribute { name: AttrName::LowPc, value: AttrValue::Address(u64::MAX - 1) },
+                DIEAttribute { name: AttrName::HighPc, value: AttrValue::Constant(0x10) },
+            ],
+            ..root.clone()
+        };
+        assert_eq!(cu.high_pc(&overflowing), None);
+
+        // Before version 4, a constant high_pc is an address.
+        built.info[4..6].copy_from_slice(&3u16.to_ne_bytes());
+        let cu = CompilationUnit::from(&built.info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &built.strs).unwrap();
+        assert_eq!(cu.high_pc(&cu.dies[0]), Some(0x2000));
+
+        for version in [1, 6, 0xffff] {
+            built.info[4..6].copy_from_slice(&u16::to_ne_bytes(version));
+            let err = CompilationUnit::from(&built.info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &built.strs).unwrap_err();
+            assert_eq!(err, format!(
+                "DWARF version {} isn't supported (only versions 2 to 5 are)", version));
+        }
+    }
+
+    #[test]
+    fn compilation_unit_index() {
+        let mut info = vec![];
+        // A version 4 unit with three bytes of DIEs.
+        info.extend_from_slice(&10u32.to_ne_bytes());
+        info.extend_from_slice(&4u16.to_ne_bytes());
+        info.extend_from_slice(&0u32.to_ne_bytes());
+        info.extend_from_slice(&[8, 1, 2, 3]);
+        // An empty version 2 unit.
+        info.extend_from_slice(&7u32.to_ne_bytes());
+        info.extend_from_slice(&2u16.to_ne_bytes());
+        info.extend_from_slice(&0u32.to_ne_bytes());
+        info.push(8);
+        // A 64-bit version 5 unit with one byte of DIEs.
+        info.extend_from_slice(&0xffffffffu32.to_ne_bytes());
+        info.extend_from_slice(&13u64.to_ne_bytes());
+        info.extend_from_slice(&5u16.to_ne_bytes());
+        info.extend_from_slice(&[DW_UT_COMPILE, 8]);
+        info.extend_from_slice(&0u64.to_ne_bytes());
+        info.push(0);
+
+        let entry = |offset, length, version| CuIndexEntry { offset, length, version };
+        assert_eq!(CompilationUnit::index(&info).unwrap(), vec![
+            entry(0x00, 10, 4),
+            entry(0x0e, 7, 2),
+            entry(0x19, 13, 5),
+        ]);
+        assert_eq!(CompilationUnit::offsets(&info).unwrap(), [0x00, 0x0e, 0x19]);
+        assert_eq!(CompilationUnit::index(&[]).unwrap(), vec![]);
+
+        info.truncate(0x19 + 6);
+        assert_eq!(CompilationUnit::index(&info).unwrap_err(),
+            "unit at 0x19 in __debug_info: unit header needs 23 bytes, but only 6 remain");
+    }
+
+    #[test]
+    fn units_use_their_own_abbrev_tables() {
+        let leaf_unit = |tag, language| build::unit(&DIE {
+            offset: 0,
+            tag,
+            attrs: vec![DIEAttribute { name: AttrName::Language, value: AttrValue::Constant(language) }],
+            children: vec![],
+        });
+        let (first, second) = (leaf_unit(DIETag::CompileUnit, 1), leaf_unit(DIETag::PartialUnit, 2));
+        // Both use code 1, so only the right table gives the right tag.
+        let mut info = first.info.clone();
+        let second_offset = info.len();
+        info.extend(&second.info);
+        info[second_offset + 6..second_offset + 10].copy_from_slice(&(first.abbrev.len() as u32).to_ne_bytes());
+        let abbrev = [first.abbrev.clone(), second.abbrev.clone()].concat();
+
+        let others = [Section::from("__debug_abbrev", &abbrev, &[]).unwrap()];
+        let tables = Section::find_abbrevs(&others).unwrap();
+        let offsets = CompilationUnit::offsets(&info).unwrap();
+        let units = CompilationUnit::parse_all(&info, &offsets, &tables, &[]).unwrap();
+        assert_eq!(units[0].dies[0].tag, DIETag::CompileUnit);
+        assert_eq!(units[1].dies[0].tag, DIETag::PartialUnit);
+        assert!(matches!(units[1].dies[0].attrs[0].value, AttrValue::Constant(2)));
+
+        info[second_offset + 6..second_offset + 10].copy_from_slice(&1u32.to_ne_bytes());
+        assert_eq!(CompilationUnit::from(&info, second_offset, &tables, &[]).unwrap_err(),
+            "no abbrev table starts at 0x1 in __debug_abbrev");
+    }
+
+    #[test]
+    fn compilation_unit_rejects_bad_lengths() {
+        let tables = AbbrevTables::new(&[], &[(0, 0)]);
+        // A unit_length too short to hold the rest of the header, as when
+        // an offset lands in the middle of a unit.
+        let mut bytes = cu_header_bytes(8);
+        bytes[0..4].copy_from_slice(&2u32.to_ne_bytes());
+        assert_eq!(CompilationUnit::from(&bytes, 0, &tables, &[]).unwrap_err(),
+            "unit at 0x0 is 0x6 bytes, too short for its 0xb-byte header");
+
+        let mut bytes = vec![0xff; 4];
+        bytes.extend_from_slice(&u64::MAX.to_ne_bytes());
+        bytes.extend_from_slice(&4u16.to_ne_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.push(8);
+        assert!(CompilationUnit::from(&bytes, 0, &tables, &[]).unwrap_err()
+            .starts_with("unit at 0x0 runs past end of __debug_info"));
+        assert!(CompilationUnit::from(&bytes, 0x100, &tables, &[]).is_err());
+    }
+
+    #[test]
+    fn compilation_unit_rejects_v5_type_units() {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&8u32.to_ne_bytes());
+        bytes.extend_from_slice(&5u16.to_ne_bytes());
+        bytes.push(0x02); // DW_UT_type
+        bytes.push(8);
+        bytes.extend_from_slice(&0u32.to_ne_bytes());
+        let err = CompilationUnit::from(&bytes, 0, &AbbrevTables::new(&[], &[(0, 0)]), &[]).unwrap_err();
+        assert_eq!(err, "unit at 0x0 has unsupported unit_type 0x2");
+    }
+
+    #[test]
+    fn cu_header_rejects_bad_address_size() {
+        let err = CUHeader::from(&cu_header_bytes(3)).unwrap_err();
+        assert_eq!(err, "bad address_size in unit header: 3");
+    }
+
+    #[test]
+    fn file_listing() {
+        let file = File {
+            sections: vec![
+                Section::DebugStr(DebugStr { bytes: b"main\0".to_vec() }),
+                Section::DebugAbbrev { abbrevs: vec![], tables: vec![] },
+                Section::Unrecognized {
+                    name: "__apple_names".to_string(),
+                    contents: vec![0; 0x24],
+                },
+            ],
+            section_sizes: vec![5, 1, 0x24],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        assert_eq!(file.listing(), vec![
+            ("__debug_str", 5),
+            ("__debug_abbrev", 1),
+            ("__apple_names", 0x24),
+        ]);
+    }
+
+    #[test]
+    fn hexdump_rows() {
+        let bytes: Vec<u8> = (0x41..0x41 + 18).collect();
+        assert_eq!(format!("{}", HexDump(&bytes)),
+            "00000000 | 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f 50 | ABCDEFGHIJKLMNOP\n\
+             00000010 | 51 52                                           | QR\n");
+        assert_eq!(format!("{}", HexDump(&[0x00, 0x7f, b' '])),
+            "00000000 | 00 7f 20                                        | .. \n");
+    }
+
+    #[test]
+    fn unrecognized_section_formats_into_string() {
+        let sec = Section::Unrecognized {
+            name: "__debug_foo".to_string(),
+            contents: vec![0; 0x20],
+        };
+        let s = format!("{}", sec);
+        assert!(!s.is_empty());
+        assert_eq!(s, "Unrecognized __debug_foo      0x20 bytes\n");
+    }
+
+    // An address range set for the unit at `info_offset`, with 8-byte
+    // addresses.
+    fn arange_set_bytes(info_offset: u32, ranges: &[(u64, u64)]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&2u16.to_ne_bytes());
+        body.extend_from_slice(&info_offset.to_ne_bytes());
+        body.extend_from_slice(&[8, 0, 0, 0, 0, 0]); // address_size, seg_size, padding
+        for &(addr, length) in ranges.iter().chain([&(0, 0)]) {
+            body.extend_from_slice(&addr.to_ne_bytes());
+            body.extend_from_slice(&length.to_ne_bytes());
+        }
+        let mut bytes = (body.len() as u32).to_ne_bytes().to_vec();
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn cu_for_address_from_aranges() {
+        let mut bytes = arange_set_bytes(0x0, &[(0x1000, 0x100), (0x2000, 0x10)]);
+        bytes.extend(arange_set_bytes(0x40, &[(0x3000, 0x80)]));
+        let section = Section::from("__debug_aranges", &bytes, &[]).unwrap();
+        let Section::DebugAranges { sets } = &section else { panic!("not aranges") };
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].tuples.len(), 2);
+        assert_eq!(sets[1].debug_info_offset(), 0x40);
+        assert!(section.to_string().contains("[0x0000000000003000, 0x0000000000003080)"));
+
+        let file = File {
+            sections: vec![section],
+            section_sizes: vec![bytes.len()],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        assert_eq!(file.cu_for_address(0x1000), Some(0x0));
+        assert_eq!(file.cu_for_address(0x10ff), Some(0x0));
+        assert_eq!(file.cu_for_address(0x200f), Some(0x0));
+        assert_eq!(file.cu_for_address(0x3040), Some(0x40));
+        assert_eq!(file.cu_for_address(0x1100), None);
+        assert_eq!(file.cu_for_address(0x3080), None);
+
+        assert_eq!(Section::from("__debug_aranges", &bytes[..40], &[]).unwrap_err(),
+            "address range set at 0x0 in __debug_aranges: set of 0x40 bytes runs past the end of the section");
+
+        // A 0xff-byte segment selector makes 0x10f-byte tuples.
+        let mut wide = arange_set_bytes(0x0, &[]);
+        wide[11] = 0xff;
+        let set = ArangeSet::from(&wide).unwrap();
+        assert_eq!((set.segment_size, set.tuples.len()), (0xff, 0));
+    }
+
+    #[test]
+    fn file_display_hexdumps_unrecognized() {
+        let mut file = File {
+            sections: vec![Section::Unrecognized {
+                name: "__debug_foo".to_string(),
+                contents: vec![0xde, 0xad, 0xbe, 0xef],
+            }],
+            section_sizes: vec![4],
+            hexdump_len: 0,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        assert_eq!(format!("{}", file), "Unrecognized __debug_foo      0x4 bytes\n");
+        file.hexdump_len = 2;
+        assert_eq!(format!("{}", file),
+            "Unrecognized __debug_foo      0x4 bytes\n\
+             00000000 | de ad                                           | ..\n\
+             ... (2 more bytes)\n");
+        file.hexdump_len = 4;
+        assert!(format!("{}", file).ends_with("| ....\n"));
+    }
+
+    #[test]
+    fn file_display_caps_dumps() {
+        let mut file = File {
+            sections: vec![Section::Unrecognized {
+                name: "__debug_foo".to_string(),
+                contents: (0..=255).cycle().take(1000).collect(),
+            }],
+            section_sizes: vec![1000],
+            hexdump_len: usize::MAX,
+            max_dump_bytes: DEFAULT_MAX_DUMP_BYTES,
+            warnings: vec![],
+        };
+        let output = file.to_string();
+        assert_eq!(output.lines().count(), 1 + 256 / 16 + 1);
+        assert!(output.contains("\n000000f0 | f0 f1"), "{}", output);
+        assert!(output.ends_with("| ................\n... (744 more bytes)\n"), "{}", output);
+
+        file.max_dump_bytes = 20;
+        let output = file.to_string();
+        assert!(output.ends_with("00000010 | 10 11 12 13                                     | ....\n\
+            ... (980 more bytes)\n"), "{}", output);
     }
 }
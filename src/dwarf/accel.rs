@@ -0,0 +1,234 @@
+// Apple's accelerator tables: __apple_names, __apple_types, __apple_namespac
+// and __apple_objc. Each is a hash table from names to the offsets of the
+// DIEs that define them, so a lookup doesn't have to walk __debug_info.
+//
+// See "Accelerator Tables" in LLVM's SourceLevelDebugging documentation.
+
+use super::AttrForm;
+use crate::reader::Reader;
+
+// "HASH", read as a native-endian u32.
+const MAGIC: u32 = 0x48415348;
+// The only hash function defined, Dan Bernstein's.
+const HASH_DJB: u16 = 0;
+const EMPTY_BUCKET: u32 = u32::MAX;
+// The atom holding a DIE's offset in __debug_info.
+const DW_ATOM_DIE_OFFSET: u16 = 1;
+
+#[derive(Debug)]
+pub struct AppleAccelTable {
+    pub version: u16,
+    // Added to each DW_ATOM_die_offset value.
+    pub die_offset_base: u32,
+    // The (DW_ATOM_*, form) of each value stored per DIE.
+    pub atoms: Vec<(u16, AttrForm)>,
+    // For each bucket, the index of its first hash, or EMPTY_BUCKET.
+    buckets: Vec<u32>,
+    hashes: Vec<u32>,
+    // For each hash, the names with that hash and their DIE offsets.
+    entries: Vec<Vec<(String, Vec<u64>)>>,
+}
+
+impl AppleAccelTable {
+    // Parses the table in `bytes`, whose names are offsets into `strs`.
+    // The hash data is read up front so that lookups can't fail.
+    pub fn from(bytes: &[u8], strs: &[u8]) -> Result<AppleAccelTable, String> {
+        let mut reader = Reader::new(bytes);
+        let magic = reader.u32()?;
+        if magic != MAGIC {
+            return Err(format!("accelerator table has bad magic {:#010x}", magic));
+        }
+        let version = reader.u16()?;
+        let hash_function = reader.u16()?;
+        if hash_function != HASH_DJB {
+            return Err(format!("accelerator table uses unknown hash function {}", hash_function));
+        }
+        let bucket_count = reader.u32()?;
+        let hashes_count = reader.u32()?;
+        let header_data_len = reader.u32()? as usize;
+
+        let mut header_data = Reader::new(reader.bytes(header_data_len)?);
+        let die_offset_base = header_data.u32()?;
+        let atom_count = header_data.u32()?;
+        let mut atoms = vec![];
+        for _ in 0..atom_count {
+            let atom = header_data.u16()?;
+            let form = AttrForm::from(header_data.u16()? as u64);
+            if atom_size(&form).is_none() {
+                return Err(format!("accelerator table atom {:#x} has unsupported form {}", atom, form));
+            }
+            atoms.push((atom, form));
+        }
+
+        let words = |reader: &mut Reader, count| {
+            (0..count).map(|_| reader.u32()).collect::<Result<Vec<u32>, String>>()
+        };
+        let buckets = words(&mut reader, bucket_count)?;
+        let hashes = words(&mut reader, hashes_count)?;
+        let offsets = words(&mut reader, hashes_count)?;
+        if let Some(&bucket) = buckets.iter().find(|&&b| b != EMPTY_BUCKET && b >= hashes_count) {
+            return Err(format!("accelerator table bucket points at hash {} of {}", bucket, hashes_count));
+        }
+        let entries = offsets.iter()
+            .map(|&offset| Self::hash_data(bytes, offset as usize, die_offset_base, &atoms, strs))
+            .collect::<Result<_, String>>()?;
+        Ok(AppleAccelTable { version, die_offset_base, atoms, buckets, hashes, entries })
+    }
+
+    // Reads the chain of names at `offset`, which ends with a zero string
+    // offset.
+    fn hash_data(
+        bytes: &[u8], offset: usize, die_offset_base: u32, atoms: &[(u16, AttrForm)], strs: &[u8],
+    ) -> Result<Vec<(String, Vec<u64>)>, String> {
+        let mut reader = Reader::new(bytes.get(offset..)
+            .ok_or_else(|| format!("accelerator table hash data at {:#x} is out of bounds", offset))?);
+        let mut names = vec![];
+        loop {
+            let str_offset = reader.u32()? as usize;
+            if str_offset == 0 {
+                return Ok(names);
+            }
+            let name = Reader::new(strs.get(str_offset..).unwrap_or(&[])).cstr()
+                .map_err(|e| format!("accelerator table name at {:#x} in __debug_str: {}", str_offset, e))?;
+            let count = reader.u32()?;
+            let mut die_offsets = vec![];
+            for _ in 0..count {
+                for (atom, form) in atoms.iter() {
+                    let value = match atom_size(form) {
+                        Some(1) => reader.u8()? as u64,
+                        Some(2) => reader.u16()? as u64,
+                        Some(4) => reader.u32()? as u64,
+                        _ => reader.u64()?,
+                    };
+                    if *atom == DW_ATOM_DIE_OFFSET {
+                        die_offsets.push(die_offset_base as u64 + value);
+                    }
+                }
+            }
+            names.push((name.to_string(), die_offsets));
+        }
+    }
+
+    // The offsets of the DIEs called `name`.
+    pub fn lookup(&self, name: &str) -> Vec<u64> {
+        if self.buckets.is_empty() {
+            return vec![];
+        }
+        let hash = djb_hash(name);
+        let bucket = hash % self.buckets.len() as u32;
+        let first = self.buckets[bucket as usize];
+        if first == EMPTY_BUCKET {
+            return vec![];
+        }
+        // A bucket's hashes are contiguous, and end where the next
+        // bucket's begin.
+        (first as usize..self.hashes.len())
+            .take_while(|&i| self.hashes[i] % self.buckets.len() as u32 == bucket)
+            .filter(|&i| self.hashes[i] == hash)
+            .flat_map(|i| self.entries[i].iter())
+            .filter(|(entry_name, _)| entry_name == name)
+            .flat_map(|(_, offsets)| offsets.iter().copied())
+            .collect()
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn hash_count(&self) -> usize {
+        self.hashes.len()
+    }
+}
+
+pub fn djb_hash(name: &str) -> u32 {
+    name.bytes().fold(5381u32, |hash, b| hash.wrapping_mul(33).wrapping_add(b as u32))
+}
+
+// The size of an atom value in `form`, for the fixed-size forms tables use.
+fn atom_size(form: &AttrForm) -> Option<usize> {
+    match form {
+        AttrForm::Data1 | AttrForm::Ref1 | AttrForm::Flag => Some(1),
+        AttrForm::Data2 | AttrForm::Ref2 => Some(2),
+        AttrForm::Data4 | AttrForm::Ref4 => Some(4),
+        AttrForm::Data8 | AttrForm::Ref8 => Some(8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRS: &[u8] = b"\0main\0helper\0other\0";
+
+    // Lays out a table with one bucket per name, holding each name's DIE
+    // offsets as DW_FORM_data4 die_offset atoms.
+    fn table_bytes(names: &[(u32, &[u32])]) -> Vec<u8> {
+        let name_at = |offset: u32| {
+            let rest = &STRS[offset as usize..];
+            std::str::from_utf8(&rest[..rest.iter().position(|&b| b == 0).unwrap()]).unwrap()
+        };
+        let bucket_count = names.len() as u32;
+        let mut hashed: Vec<(u32, u32, &[u32])> = names.iter()
+            .map(|&(str_offset, dies)| (djb_hash(name_at(str_offset)), str_offset, dies))
+            .collect();
+        hashed.sort_by_key(|&(hash, _, _)| hash % bucket_count);
+
+        let mut bytes = vec![];
+        let word = |bytes: &mut Vec<u8>, w: u32| bytes.extend_from_slice(&w.to_ne_bytes());
+        word(&mut bytes, MAGIC);
+        bytes.extend_from_slice(&1u16.to_ne_bytes()); // version
+        bytes.extend_from_slice(&0u16.to_ne_bytes()); // hash_function
+        word(&mut bytes, bucket_count);
+        word(&mut bytes, hashed.len() as u32);
+        word(&mut bytes, 12);                          // header_data_len
+        word(&mut bytes, 0);                           // die_offset_base
+        word(&mut bytes, 1);                           // atom_count
+        bytes.extend_from_slice(&DW_ATOM_DIE_OFFSET.to_ne_bytes());
+        bytes.extend_from_slice(&0x06u16.to_ne_bytes()); // DW_FORM_data4
+        for bucket in 0..bucket_count {
+            let first = hashed.iter().position(|&(hash, _, _)| hash % bucket_count == bucket);
+            word(&mut bytes, first.map_or(EMPTY_BUCKET, |i| i as u32));
+        }
+        for &(hash, _, _) in hashed.iter() {
+            word(&mut bytes, hash);
+        }
+        let mut data_offset = bytes.len() + 4 * hashed.len();
+        for &(_, _, dies) in hashed.iter() {
+            word(&mut bytes, data_offset as u32);
+            data_offset += 4 * (3 + dies.len());
+        }
+        for &(_, str_offset, dies) in hashed.iter() {
+            word(&mut bytes, str_offset);
+            word(&mut bytes, dies.len() as u32);
+            for &die in dies {
+                word(&mut bytes, die);
+            }
+            word(&mut bytes, 0);
+        }
+        bytes
+    }
+
+    #[test]
+    fn djb_hashes() {
+        assert_eq!(djb_hash(""), 5381);
+        assert_eq!(djb_hash("main"), 0x7c9a_7f6a);
+    }
+
+    #[test]
+    fn lookup_names() {
+        let bytes = table_bytes(&[(1, &[0x2a]), (6, &[0x40, 0x50])]);
+        let table = AppleAccelTable::from(&bytes, STRS).unwrap();
+        assert_eq!((table.version, table.bucket_count(), table.hash_count()), (1, 2, 2));
+        assert_eq!(table.atoms, [(DW_ATOM_DIE_OFFSET, AttrForm::Data4)]);
+        assert_eq!(table.lookup("main"), [0x2a]);
+        assert_eq!(table.lookup("helper"), [0x40, 0x50]);
+        assert_eq!(table.lookup("other"), Vec::<u64>::new());
+        assert_eq!(table.lookup("mai"), Vec::<u64>::new());
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert!(AppleAccelTable::from(&bad, STRS).unwrap_err().starts_with("accelerator table has bad magic"));
+        assert!(AppleAccelTable::from(&bytes[..bytes.len() - 4], STRS).is_err());
+    }
+}
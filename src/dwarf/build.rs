@@ -0,0 +1,248 @@
+// Builds DWARF sections from DIE trees, so tests can describe their inputs
+// as DIEs rather than as hand-assembled bytes.
+//
+// Each DIE gets an abbreviation of its own, and each attribute value is
+// written in a form that parses back to the same value:
+//
+//   Address          DW_FORM_addr (8 bytes)
+//   Constant         DW_FORM_data1, data2 or data4, whichever fits
+//   ExprLoc          DW_FORM_exprloc
+//   Flag             DW_FORM_flag
+//   MacPtr           DW_FORM_sec_offset
+//   OffsetReference  DW_FORM_ref4
+//   SectionReference DW_FORM_ref_addr
+//   StrP             DW_FORM_strp, with the string added to __debug_str
+//   TypeSignature    DW_FORM_ref_sig8
+//   StrIndex         DW_FORM_strx
+//   AddrIndex        DW_FORM_addrx
+//   ListIndex        DW_FORM_rnglistx
+//   ImplicitConst    DW_FORM_implicit_const, with the value in the abbrev
+
+use super::*;
+
+// The sections for one compilation unit.
+pub struct Unit {
+    pub abbrev: Vec<u8>,
+    pub strs: Vec<u8>,
+    pub info: Vec<u8>,
+    // The DIE tree as written, with each DIE's offset filled in.
+    pub root: DIE,
+}
+
+impl Unit {
+    // The sections, named as in a Mach-O __DWARF segment.
+    pub fn sections(&self) -> Vec<(&'static str, Vec<u8>)> {
+        vec![
+            ("__debug_abbrev", self.abbrev.clone()),
+            ("__debug_str", self.strs.clone()),
+            ("__debug_info", self.info.clone()),
+        ]
+    }
+}
+
+// Writes a DWARF 4 compilation unit, with 8-byte addresses, holding `root`
+// and its descendants.
+pub fn unit(root: &DIE) -> Unit {
+    let mut builder = Builder { abbrev: vec![], strs: vec![0], body: vec![], next_code: 1 };
+    let root = builder.die(root);
+    builder.abbrev.push(0);
+
+    let header_size = 11;
+    let mut info = vec![];
+    info.extend_from_slice(&((header_size - 4 + builder.body.len()) as u32).to_ne_bytes());
+    info.extend_from_slice(&4u16.to_ne_bytes()); // version
+    info.extend_from_slice(&0u32.to_ne_bytes()); // debug_abbrev_offset
+    info.push(8);                                 // address_size
+    info.extend(builder.body);
+    Unit { abbrev: builder.abbrev, strs: builder.strs, info, root: shift(root, header_size) }
+}
+
+struct Builder {
+    abbrev: Vec<u8>,
+    strs: Vec<u8>,
+    // The unit's DIEs, without the header.
+    body: Vec<u8>,
+    next_code: u64,
+}
+
+impl Builder {
+    // Writes `die` and its children, returning a copy with offsets
+    // relative to the start of the body.
+    fn die(&mut self, die: &DIE) -> DIE {
+        let code = self.next_code;
+        self.next_code += 1;
+        let has_children = !die.children.is_empty();
+        self.abbrev.extend_from_slice(&uleb128_encode(code));
+        self.abbrev.extend_from_slice(&uleb128_encode(tag_code(die.tag)));
+        self.abbrev.push(has_children as u8);
+
+        let offset = self.body.len();
+        self.body.extend_from_slice(&uleb128_encode(code));
+        for attr in die.attrs.iter() {
+            let form = self.value(&attr.value);
+            self.abbrev.extend_from_slice(&uleb128_encode(name_code(&attr.name)));
+            self.abbrev.extend_from_slice(&uleb128_encode(form_code(&form)));
+            if let AttrValue::ImplicitConst(n) = attr.value {
+                self.abbrev.extend_from_slice(&ileb128_encode(n));
+            }
+        }
+        self.abbrev.extend_from_slice(&[0, 0]);
+
+        let children = die.children.iter().map(|child| self.die(child)).collect();
+        if has_children {
+            self.body.push(0);
+        }
+        DIE { offset, tag: die.tag, attrs: die.attrs.clone(), children }
+    }
+
+    // Writes an attribute value, returning the form it was written in.
+    fn value(&mut self, value: &AttrValue) -> AttrForm {
+        match value {
+            AttrValue::Address(x) => {
+                self.body.extend_from_slice(&x.to_ne_bytes());
+                AttrForm::Addr
+            },
+            AttrValue::Constant(x) => match u8::try_from(*x) {
+                Ok(x) => { self.body.push(x); AttrForm::Data1 },
+                Err(_) => match u16::try_from(*x) {
+                    Ok(x) => { self.body.extend_from_slice(&x.to_ne_bytes()); AttrForm::Data2 },
+                    Err(_) => {
+                        let x = u32::try_from(*x).expect("constants over 32 bits aren't supported");
+                        self.body.extend_from_slice(&x.to_ne_bytes());
+                        AttrForm::Data4
+                    },
+                },
+            },
+            AttrValue::ExprLoc(bytes) => {
+                self.body.extend_from_slice(&uleb128_encode(bytes.len() as u64));
+                self.body.extend_from_slice(bytes);
+                AttrForm::ExprLoc
+            },
+            AttrValue::Flag(b) => {
+                self.body.push(*b as u8);
+                AttrForm::Flag
+            },
+            AttrValue::MacPtr(x) => {
+                self.body.extend_from_slice(&(*x as u32).to_ne_bytes());
+                AttrForm::SecOffset
+            },
+            AttrValue::OffsetReference(x) => {
+                self.body.extend_from_slice(&(*x as u32).to_ne_bytes());
+                AttrForm::Ref4
+            },
+            AttrValue::SectionReference(x) => {
+                self.body.extend_from_slice(&(*x as u32).to_ne_bytes());
+                AttrForm::RefAddr
+            },
+            AttrValue::StrP(s) => {
+                self.body.extend_from_slice(&(self.strs.len() as u32).to_ne_bytes());
+                self.strs.extend_from_slice(s.as_bytes());
+                self.strs.push(0);
+                AttrForm::StrP
+            },
+            AttrValue::TypeSignature(x) => {
+                self.body.extend_from_slice(&x.to_ne_bytes());
+                AttrForm::RefSig8
+            },
+            AttrValue::StrIndex(x) => {
+                self.body.extend_from_slice(&uleb128_encode(*x));
+                AttrForm::Strx
+            },
+            AttrValue::AddrIndex(x) => {
+                self.body.extend_from_slice(&uleb128_encode(*x));
+                AttrForm::Addrx
+            },
+            // Both list forms read back as a ListIndex.
+            AttrValue::ListIndex(x) => {
+                self.body.extend_from_slice(&uleb128_encode(*x));
+                AttrForm::Rnglistx
+            },
+            // `die` writes the value into the abbrev.
+            AttrValue::ImplicitConst(_) => AttrForm::ImplicitConst,
+        }
+    }
+}
+
+// Moves every offset in the tree along by `by`.
+fn shift(die: DIE, by: usize) -> DIE {
+    DIE {
+        offset: die.offset + by,
+        children: die.children.into_iter().map(|child| shift(child, by)).collect(),
+        ..die
+    }
+}
+
+// The mappers only go from codes to names, so these search them for the
+// code instead of keeping second copies of the tables in step.
+
+fn tag_code(tag: DIETag) -> u64 {
+    if let DIETag::Unknown(code) = tag {
+        return code;
+    }
+    (0..=0xffff).find(|&code| DIETag::from(code) == tag)
+        .unwrap_or_else(|| panic!("no code for DW_TAG_{:?}", tag))
+}
+
+fn name_code(name: &AttrName) -> u64 {
+    if let AttrName::Unrecognized(code) = name {
+        return *code;
+    }
+    (0..=0x3fff).find(|&code| AttrName::from(code) == *name)
+        .unwrap_or_else(|| panic!("no code for {}", name))
+}
+
+fn form_code(form: &AttrForm) -> u64 {
+    (0..=0xff).find(|&code| AttrForm::from(code) == *form)
+        .unwrap_or_else(|| panic!("no code for {}", form))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(name: AttrName, value: AttrValue) -> DIEAttribute {
+        DIEAttribute { name, value }
+    }
+
+    #[test]
+    fn unit_round_trips() {
+        let root = DIE {
+            offset: 0,
+            tag: DIETag::CompileUnit,
+            attrs: vec![
+                attr(AttrName::Name, AttrValue::StrP("main.c".to_string())),
+                attr(AttrName::Language, AttrValue::Constant(0x0c)),
+                attr(AttrName::LowPc, AttrValue::Address(0x100003f20)),
+            ],
+            children: vec![DIE {
+                offset: 0,
+                tag: DIETag::Subprogram,
+                attrs: vec![
+                    attr(AttrName::Name, AttrValue::StrP("main".to_string())),
+                    attr(AttrName::HighPc, AttrValue::Constant(0x1234)),
+                    attr(AttrName::FrameBase, AttrValue::ExprLoc(vec![0x56])),
+                    attr(AttrName::External, AttrValue::Flag(true)),
+                    attr(AttrName::DeclLine, AttrValue::ImplicitConst(-3)),
+                ],
+                children: vec![],
+            }],
+        };
+        let built = unit(&root);
+
+        let abbrevs = match Section::from("__debug_abbrev", &built.abbrev, &[]).unwrap() {
+            Section::DebugAbbrev { abbrevs, .. } => abbrevs,
+            section => panic!("expected abbrevs, got {:?}", section),
+        };
+        let parsed = CompilationUnit::from(&built.info, 0, &AbbrevTables::new(&abbrevs, &[(0, 0)]), &built.strs).unwrap();
+        assert_eq!(parsed.header.unit_size(), built.info.len());
+        assert_eq!(parsed.dies, vec![built.root.clone()]);
+        assert_eq!(parsed.dies[0].children[0].offset, 11 + 1 + 4 + 1 + 8);
+
+        // And through a whole Mach-O file.
+        let bytes = crate::dwarf::tests::macho_with_dwarf(&built.sections());
+        let macho = macho::File::from(&bytes).unwrap();
+        let file = File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+        assert_eq!(file.dies().cloned().collect::<Vec<_>>(),
+            vec![built.root.clone(), built.root.children[0].clone()]);
+    }
+}
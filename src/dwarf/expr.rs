@@ -0,0 +1,262 @@
+// Decoding for DWARF expressions and location descriptions (DW_OP_*).
+//
+// These appear as the contents of exprloc attributes such as DW_AT_location
+// and DW_AT_frame_base. See section 2.5 and 7.7 of the DWARF 4 spec.
+
+use crate::leb::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Addr(u64),
+    Deref,
+    // DW_OP_const1u through DW_OP_const8u, and DW_OP_constu.
+    Constu(u64),
+    // DW_OP_const1s through DW_OP_const8s, and DW_OP_consts.
+    Consts(i64),
+    Dup,
+    Drop,
+    Over,
+    Pick(u8),
+    Swap,
+    Rot,
+    Xderef,
+    Abs,
+    And,
+    Div,
+    Minus,
+    Mod,
+    Mul,
+    Neg,
+    Not,
+    Or,
+    Plus,
+    PlusUconst(u64),
+    Shl,
+    Shr,
+    Shra,
+    Xor,
+    Bra(i16),
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Ne,
+    Skip(i16),
+    // DW_OP_lit0 through DW_OP_lit31.
+    Lit(u8),
+    // DW_OP_reg0 through DW_OP_reg31.
+    Reg(u8),
+    // DW_OP_breg0 through DW_OP_breg31, with their offset.
+    Breg(u8, i64),
+    Regx(u64),
+    Fbreg(i64),
+    Bregx(u64, i64),
+    Piece(u64),
+    DerefSize(u8),
+    XderefSize(u8),
+    Nop,
+    PushObjectAddress,
+    Call2(u16),
+    Call4(u32),
+    CallRef(u32),
+    FormTlsAddress,
+    CallFrameCfa,
+    BitPiece(u64, u64),
+    ImplicitValue(Vec<u8>),
+    StackValue,
+}
+
+// Decodes a whole expression. `address_size` is the size of an address on
+// the target, as given by the unit header.
+pub fn decode(bytes: &[u8], address_size: u8) -> Result<Vec<Op>, String> {
+    let mut ops = vec![];
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let (op, size) = Op::from(&bytes[offset..], address_size)?;
+        ops.push(op);
+        offset += size;
+    }
+    Ok(ops)
+}
+
+impl Op {
+    // Decodes one operation, returning it and the number of bytes consumed.
+    pub fn from(bytes: &[u8], address_size: u8) -> Result<(Op, usize), String> {
+        let opcode = bytes[0];
+        let operands = &bytes[1..];
+        let (op, size) = match opcode {
+            0x03 => (Op::Addr(fixed(operands, address_size as usize)?), address_size as usize),
+            0x06 => (Op::Deref, 0),
+            0x08 => (Op::Constu(fixed(operands, 1)?), 1),
+            0x09 => (Op::Consts(fixed(operands, 1)? as i8 as i64), 1),
+            0x0a => (Op::Constu(fixed(operands, 2)?), 2),
+            0x0b => (Op::Consts(fixed(operands, 2)? as i16 as i64), 2),
+            0x0c => (Op::Constu(fixed(operands, 4)?), 4),
+            0x0d => (Op::Consts(fixed(operands, 4)? as i32 as i64), 4),
+            0x0e => (Op::Constu(fixed(operands, 8)?), 8),
+            0x0f => (Op::Consts(fixed(operands, 8)? as i64), 8),
+            0x10 => {
+                let (n, size) = uleb128_decode(operands)?;
+                (Op::Constu(n), size)
+            },
+            0x11 => {
+                let (n, size) = ileb128_decode(operands)?;
+                (Op::Consts(n), size)
+            },
+            0x12 => (Op::Dup, 0),
+            0x13 => (Op::Drop, 0),
+            0x14 => (Op::Over, 0),
+            0x15 => (Op::Pick(fixed(operands, 1)? as u8), 1),
+            0x16 => (Op::Swap, 0),
+            0x17 => (Op::Rot, 0),
+            0x18 => (Op::Xderef, 0),
+            0x19 => (Op::Abs, 0),
+            0x1a => (Op::And, 0),
+            0x1b => (Op::Div, 0),
+            0x1c => (Op::Minus, 0),
+            0x1d => (Op::Mod, 0),
+            0x1e => (Op::Mul, 0),
+            0x1f => (Op::Neg, 0),
+            0x20 => (Op::Not, 0),
+            0x21 => (Op::Or, 0),
+            0x22 => (Op::Plus, 0),
+            0x23 => {
+                let (n, size) = uleb128_decode(operands)?;
+                (Op::PlusUconst(n), size)
+            },
+            0x24 => (Op::Shl, 0),
+            0x25 => (Op::Shr, 0),
+            0x26 => (Op::Shra, 0),
+            0x27 => (Op::Xor, 0),
+            0x28 => (Op::Bra(fixed(operands, 2)? as i16), 2),
+            0x29 => (Op::Eq, 0),
+            0x2a => (Op::Ge, 0),
+            0x2b => (Op::Gt, 0),
+            0x2c => (Op::Le, 0),
+            0x2d => (Op::Lt, 0),
+            0x2e => (Op::Ne, 0),
+            0x2f => (Op::Skip(fixed(operands, 2)? as i16), 2),
+            0x30..=0x4f => (Op::Lit(opcode - 0x30), 0),
+            0x50..=0x6f => (Op::Reg(opcode - 0x50), 0),
+            0x70..=0x8f => {
+                let (n, size) = ileb128_decode(operands)?;
+                (Op::Breg(opcode - 0x70, n), size)
+            },
+            0x90 => {
+                let (n, size) = uleb128_decode(operands)?;
+                (Op::Regx(n), size)
+            },
+            0x91 => {
+                let (n, size) = ileb128_decode(operands)?;
+                (Op::Fbreg(n), size)
+            },
+            0x92 => {
+                let (reg, reg_size) = uleb128_decode(operands)?;
+                let (n, size) = ileb128_decode(&operands[reg_size..])?;
+                (Op::Bregx(reg, n), reg_size + size)
+            },
+            0x93 => {
+                let (n, size) = uleb128_decode(operands)?;
+                (Op::Piece(n), size)
+            },
+            0x94 => (Op::DerefSize(fixed(operands, 1)? as u8), 1),
+            0x95 => (Op::XderefSize(fixed(operands, 1)? as u8), 1),
+            0x96 => (Op::Nop, 0),
+            0x97 => (Op::PushObjectAddress, 0),
+            0x98 => (Op::Call2(fixed(operands, 2)? as u16), 2),
+            0x99 => (Op::Call4(fixed(operands, 4)? as u32), 4),
+            // NOTE: In DWARF64, this operand would be 8 bytes.
+            0x9a => (Op::CallRef(fixed(operands, 4)? as u32), 4),
+            0x9b => (Op::FormTlsAddress, 0),
+            0x9c => (Op::CallFrameCfa, 0),
+            0x9d => {
+                let (bits, bits_size) = uleb128_decode(operands)?;
+                let (offset, size) = uleb128_decode(&operands[bits_size..])?;
+                (Op::BitPiece(bits, offset), bits_size + size)
+            },
+            0x9e => {
+                let (len, size) = uleb128_decode(operands)?;
+                let end = usize::try_from(len).ok()
+                    .and_then(|len| size.checked_add(len))
+                    .filter(|&end| end <= operands.len())
+                    .ok_or("ran out of bytes reading DW_OP_implicit_value")?;
+                (Op::ImplicitValue(operands[size..end].to_vec()), end)
+            },
+            0x9f => (Op::StackValue, 0),
+            _ => return Err(format!("unknown DW_OP {:#04x}", opcode)),
+        };
+        Ok((op, 1 + size))
+    }
+}
+
+// Reads an n-byte unsigned operand.
+fn fixed(bytes: &[u8], n: usize) -> Result<u64, String> {
+    if bytes.len() < n {
+        return Err(format!("expected {}-byte operand but only {} bytes remain", n, bytes.len()));
+    }
+    Ok(match n {
+        1 => bytes[0] as u64,
+        2 => u16::from_ne_bytes(bytes[0..2].try_into().unwrap()) as u64,
+        4 => u32::from_ne_bytes(bytes[0..4].try_into().unwrap()) as u64,
+        8 => u64::from_ne_bytes(bytes[0..8].try_into().unwrap()),
+        _ => return Err(format!("bad operand size {}", n)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_addr() {
+        let mut bytes = vec![0x03];
+        bytes.extend_from_slice(&0x100003f20u64.to_ne_bytes());
+        assert_eq!(decode(&bytes, 8), Ok(vec![Op::Addr(0x100003f20)]));
+
+        let mut bytes = vec![0x03];
+        bytes.extend_from_slice(&0x3f20u32.to_ne_bytes());
+        assert_eq!(decode(&bytes, 4), Ok(vec![Op::Addr(0x3f20)]));
+    }
+
+    #[test]
+    fn decode_frame_base_relative() {
+        // DW_OP_fbreg -20
+        assert_eq!(decode(&[0x91, 0x6c], 8), Ok(vec![Op::Fbreg(-20)]));
+        // DW_OP_breg7 +8
+        assert_eq!(decode(&[0x77, 0x08], 8), Ok(vec![Op::Breg(7, 8)]));
+        // DW_OP_bregx 33 -1
+        assert_eq!(decode(&[0x92, 0x21, 0x7f], 8), Ok(vec![Op::Bregx(33, -1)]));
+    }
+
+    #[test]
+    fn decode_registers_and_cfa() {
+        assert_eq!(decode(&[0x56], 8), Ok(vec![Op::Reg(6)]));
+        assert_eq!(decode(&[0x9c], 8), Ok(vec![Op::CallFrameCfa]));
+        assert_eq!(decode(&[0x90, 0x80, 0x01], 8), Ok(vec![Op::Regx(128)]));
+    }
+
+    #[test]
+    fn decode_sequence() {
+        // DW_OP_lit4 DW_OP_const1s -2 DW_OP_plus DW_OP_plus_uconst 16 DW_OP_stack_value
+        assert_eq!(decode(&[0x34, 0x09, 0xfe, 0x22, 0x23, 0x10, 0x9f], 8), Ok(vec![
+            Op::Lit(4),
+            Op::Consts(-2),
+            Op::Plus,
+            Op::PlusUconst(16),
+            Op::StackValue,
+        ]));
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(decode(&[0x03, 0x00, 0x00], 8),
+            Err("expected 8-byte operand but only 2 bytes remain".to_string()));
+        assert_eq!(decode(&[0xe0], 8), Err("unknown DW_OP 0xe0".to_string()));
+        // An implicit value whose length runs past the end, or past usize.
+        assert_eq!(decode(&[0x9e, 0x04, 0x01], 8),
+            Err("ran out of bytes reading DW_OP_implicit_value".to_string()));
+        assert_eq!(decode(&[0x9e, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01], 8),
+            Err("ran out of bytes reading DW_OP_implicit_value".to_string()));
+    }
+}
@@ -0,0 +1,357 @@
+// The DWARF 5 name index in .debug_names, which maps names to the DIEs
+// that define them. It replaces __apple_names and the like; see section
+// 6.1.1 of the DWARF 5 spec.
+
+use super::{accel::djb_hash, AttrForm, DIETag, Format};
+use crate::reader::Reader;
+
+// DW_IDX_* attributes of an index entry.
+const DW_IDX_COMPILE_UNIT: u64 = 1;
+const DW_IDX_TYPE_UNIT: u64 = 2;
+const DW_IDX_DIE_OFFSET: u64 = 3;
+
+// One index from .debug_names. A section can hold several, one after
+// another, such as one per unit before they're merged by the linker.
+#[derive(Debug)]
+pub struct NameIndex {
+    pub format: Format,
+    pub version: u16,
+    // The offsets in __debug_info of the units the index covers.
+    pub comp_units: Vec<u64>,
+    // The offsets in __debug_info of its local type units. Foreign type
+    // units live in other files, so entries in them aren't resolved.
+    pub type_units: Vec<u64>,
+    // For each bucket, the 1-based index of its first name, or 0 if it's
+    // empty. There may be no buckets at all, leaving only linear search.
+    buckets: Vec<u32>,
+    hashes: Vec<u32>,
+    // Each name and the entries for it, in name table order.
+    names: Vec<(String, Vec<NameEntry>)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NameEntry {
+    pub tag: DIETag,
+    // The offset in __debug_info of the entry's DIE, when it's in one of
+    // the index's compile units or local type units.
+    pub die_offset: Option<u64>,
+}
+
+// An entry's shape, from the abbreviation table: its tag and the DW_IDX_*
+// attributes that follow its code, with their forms.
+struct NameAbbrev {
+    code: u64,
+    tag: DIETag,
+    attrs: Vec<(u64, AttrForm)>,
+}
+
+impl NameIndex {
+    // Parses every index in `bytes`, resolving names in `strs`.
+    pub fn parse_all(bytes: &[u8], strs: &[u8]) -> Result<Vec<NameIndex>, String> {
+        let mut indexes = vec![];
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let (index, size) = Self::from(&bytes[offset..], strs)
+                .map_err(|e| format!("name index at {:#x} in .debug_names: {}", offset, e))?;
+            indexes.push(index);
+            offset += size;
+        }
+        Ok(indexes)
+    }
+
+    // Parses the index at the start of `bytes`, returning it and its size.
+    pub fn from(bytes: &[u8], strs: &[u8]) -> Result<(NameIndex, usize), String> {
+        let mut reader = Reader::new(bytes);
+        let (format, unit_length) = match reader.u32()? {
+            0xffffffff => (Format::Dwarf64, reader.u64()?),
+            n @ 0xfffffff0..=0xfffffffe =>
+                return Err(format!("unit_length {:#010x} is reserved", n)),
+            n => (Format::Dwarf32, n as u64),
+        };
+        let header_size = reader.position();
+        let size = usize::try_from(unit_length).ok()
+            .and_then(|length| length.checked_add(header_size))
+            .filter(|&size| size <= bytes.len())
+            .ok_or_else(|| format!("index of {:#x} bytes runs past the end of the section", unit_length))?;
+        let mut reader = Reader::new(&bytes[..size]);
+        reader.skip(header_size)?;
+
+        let version = reader.u16()?;
+        if version != 5 {
+            return Err(format!("name index has version {}, which isn't supported", version));
+        }
+        reader.u16()?; // padding
+        let comp_unit_count = reader.u32()?;
+        let local_type_unit_count = reader.u32()?;
+        let foreign_type_unit_count = reader.u32()?;
+        let bucket_count = reader.u32()?;
+        let name_count = reader.u32()?;
+        let abbrev_table_size = reader.u32()? as usize;
+        let augmentation_string_size = reader.u32()? as usize;
+        reader.skip(augmentation_string_size.next_multiple_of(4))?;
+
+        let offset = |reader: &mut Reader| match format {
+            Format::Dwarf32 => reader.u32().map(u64::from),
+            Format::Dwarf64 => reader.u64(),
+        };
+        let comp_units = (0..comp_unit_count).map(|_| offset(&mut reader)).collect::<Result<Vec<_>, _>>()?;
+        let type_units = (0..local_type_unit_count).map(|_| offset(&mut reader)).collect::<Result<Vec<_>, _>>()?;
+        reader.skip(8 * foreign_type_unit_count as usize)?;
+        let buckets = (0..bucket_count).map(|_| reader.u32()).collect::<Result<Vec<_>, _>>()?;
+        let hashes = match bucket_count {
+            0 => vec![],
+            _ => (0..name_count).map(|_| reader.u32()).collect::<Result<Vec<_>, _>>()?,
+        };
+        let string_offsets = (0..name_count).map(|_| offset(&mut reader)).collect::<Result<Vec<_>, _>>()?;
+        let entry_offsets = (0..name_count).map(|_| offset(&mut reader)).collect::<Result<Vec<_>, _>>()?;
+        let abbrevs = Self::abbrevs(reader.bytes(abbrev_table_size)?)?;
+        let pool = reader.rest();
+
+        let mut names = vec![];
+        for (&str_offset, &entry_offset) in string_offsets.iter().zip(entry_offsets.iter()) {
+            let name = Reader::new(strs.get(str_offset as usize..).unwrap_or(&[])).cstr()
+                .map_err(|e| format!("name at {:#x} in __debug_str: {}", str_offset, e))?;
+            let entries = Self::entries(pool, entry_offset as usize, &abbrevs, &comp_units, &type_units)?;
+            names.push((name.to_string(), entries));
+        }
+        Ok((NameIndex { format, version, comp_units, type_units, buckets, hashes, names }, size))
+    }
+
+    fn abbrevs(bytes: &[u8]) -> Result<Vec<NameAbbrev>, String> {
+        let mut reader = Reader::new(bytes);
+        let mut abbrevs = vec![];
+        loop {
+            let code = reader.uleb()?;
+            if code == 0 {
+                return Ok(abbrevs);
+            }
+            let tag = DIETag::from(reader.uleb()?);
+            let mut attrs = vec![];
+            loop {
+                let (idx, form) = (reader.uleb()?, reader.uleb()?);
+                if (idx, form) == (0, 0) {
+                    break;
+                }
+                attrs.push((idx, AttrForm::from(form)));
+            }
+            abbrevs.push(NameAbbrev { code, tag, attrs });
+        }
+    }
+
+    // Reads the entries for one name, starting `offset` bytes into the
+    // entry pool and ending with a zero code.
+    fn entries(
+        pool: &[u8], offset: usize, abbrevs: &[NameAbbrev], comp_units: &[u64], type_units: &[u64],
+    ) -> Result<Vec<NameEntry>, String> {
+        let mut reader = Reader::new(pool.get(offset..)
+            .ok_or_else(|| format!("entry offset {:#x} is past the end of the pool", offset))?);
+        let mut entries = vec![];
+        loop {
+            let code = reader.uleb()?;
+            if code == 0 {
+                return Ok(entries);
+            }
+            let abbrev = abbrevs.iter().find(|abbrev| abbrev.code == code)
+                .ok_or_else(|| format!("entry uses undeclared abbreviation {:#x}", code))?;
+            // With a single unit, entries needn't say which they're in.
+            let mut unit = (comp_units.len() == 1).then_some(0);
+            let mut type_unit = None;
+            let mut die_offset = None;
+            for (idx, form) in abbrev.attrs.iter() {
+                let value = match form {
+                    AttrForm::Data1 | AttrForm::Ref1 => reader.u8()? as u64,
+                    AttrForm::Data2 | AttrForm::Ref2 => reader.u16()? as u64,
+                    AttrForm::Data4 | AttrForm::Ref4 => reader.u32()? as u64,
+                    AttrForm::Data8 | AttrForm::Ref8 | AttrForm::RefSig8 => reader.u64()?,
+                    AttrForm::Udata | AttrForm::RefUdata => reader.uleb()?,
+                    AttrForm::FlagPresent => 1,
+                    form => return Err(format!("can't read index attributes of form {} yet", form)),
+                };
+                match *idx {
+                    DW_IDX_COMPILE_UNIT => unit = Some(value as usize),
+                    DW_IDX_TYPE_UNIT => type_unit = Some(value as usize),
+                    DW_IDX_DIE_OFFSET => die_offset = Some(value),
+                    _ => {},
+                }
+            }
+            // DW_IDX_die_offset is relative to the unit. Type unit indexes
+            // count the local ones first, then the foreign ones.
+            let unit_offset = match type_unit {
+                Some(unit) => type_units.get(unit),
+                None => unit.and_then(|unit| comp_units.get(unit)),
+            };
+            let die_offset = match die_offset.zip(unit_offset) {
+                Some((die, unit)) => Some(unit.checked_add(die)
+                    .ok_or_else(|| format!("DIE offset {:#x} in unit at {:#x} overflows", die, unit))?),
+                None => None,
+            };
+            entries.push(NameEntry { tag: abbrev.tag, die_offset });
+        }
+    }
+
+    // The offsets in __debug_info of the DIEs called `name`.
+    pub fn lookup(&self, name: &str) -> Vec<u64> {
+        self.names_matching(name)
+            .flat_map(|(_, entries)| entries.iter())
+            .filter_map(|entry| entry.die_offset)
+            .collect()
+    }
+
+    fn names_matching<'a>(&'a self, name: &'a str) -> Box<dyn Iterator<Item = &'a (String, Vec<NameEntry>)> + 'a> {
+        // Names are hashed case-folded. Only ASCII folding is done here, so
+        // other names are searched for linearly, as they are when the index
+        // has no hash table.
+        if self.buckets.is_empty() || !name.is_ascii() {
+            return Box::new(self.names.iter().filter(move |(n, _)| n == name));
+        }
+        let hash = djb_hash(&name.to_ascii_lowercase());
+        let bucket = hash % self.buckets.len() as u32;
+        let Some(first) = (self.buckets[bucket as usize] as usize).checked_sub(1) else {
+            return Box::new(std::iter::empty());
+        };
+        Box::new((first..self.names.len().min(self.hashes.len()))
+            .take_while(move |&i| self.hashes[i] % self.buckets.len() as u32 == bucket)
+            .filter(move |&i| self.hashes[i] == hash && self.names[i].0 == name)
+            .map(|i| &self.names[i]))
+    }
+
+    pub fn name_count(&self) -> usize {
+        self.names.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRS: &[u8] = b"\0main\0";
+
+    // An index over two units, at 0x0 and 0x40 in __debug_info, naming
+    // main as a subprogram 0x2a bytes into the second.
+    fn index_bytes(bucket_count: u32) -> Vec<u8> {
+        let abbrev = [
+            0x01, 0x2e,       // [1] DW_TAG_subprogram
+            0x01, 0x0b,       // DW_IDX_compile_unit, DW_FORM_data1
+            0x03, 0x13,       // DW_IDX_die_offset, DW_FORM_ref4
+            0x00, 0x00,
+            0x00,
+        ];
+        let mut body = vec![];
+        let word = |body: &mut Vec<u8>, w: u32| body.extend_from_slice(&w.to_ne_bytes());
+        body.extend_from_slice(&5u16.to_ne_bytes()); // version
+        body.extend_from_slice(&0u16.to_ne_bytes()); // padding
+        for w in [2, 0, 0, bucket_count, 1, abbrev.len() as u32, 0] {
+            word(&mut body, w);
+        }
+        word(&mut body, 0x00);                       // comp unit 0
+        word(&mut body, 0x40);                       // comp unit 1
+        if bucket_count > 0 {
+            for bucket in 0..bucket_count {
+                let hash = djb_hash("main");
+                word(&mut body, (hash % bucket_count == bucket) as u32);
+            }
+            word(&mut body, djb_hash("main"));
+        }
+        word(&mut body, 1);                          // string offset of "main"
+        word(&mut body, 0);                          // entry offset
+        body.extend_from_slice(&abbrev);
+        body.extend_from_slice(&[0x01, 0x01]);       // abbrev 1, unit 1
+        word(&mut body, 0x2a);
+        body.push(0);
+
+        let mut bytes = (body.len() as u32).to_ne_bytes().to_vec();
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn lookup_one_name() {
+        for bucket_count in [0, 1, 3] {
+            let bytes = index_bytes(bucket_count);
+            let (index, size) = NameIndex::from(&bytes, STRS).unwrap();
+            assert_eq!(size, bytes.len());
+            assert_eq!((index.format, index.version), (Format::Dwarf32, 5));
+            assert_eq!(index.comp_units, [0x00, 0x40]);
+            assert_eq!(index.names[0].1, [NameEntry { tag: DIETag::Subprogram, die_offset: Some(0x6a) }]);
+            assert_eq!(index.lookup("main"), [0x6a]);
+            assert_eq!(index.lookup("Main"), Vec::<u64>::new());
+            assert_eq!(index.lookup("other"), Vec::<u64>::new());
+        }
+
+        let mut bytes = index_bytes(1);
+        bytes.extend(index_bytes(0));
+        let indexes = NameIndex::parse_all(&bytes, STRS).unwrap();
+        assert_eq!(indexes.len(), 2);
+
+        let mut bytes = index_bytes(1);
+        bytes[4..6].copy_from_slice(&4u16.to_ne_bytes());
+        assert_eq!(NameIndex::parse_all(&bytes, STRS).unwrap_err(),
+            "name index at 0x0 in .debug_names: name index has version 4, which isn't supported");
+
+        let mut bytes = index_bytes(1);
+        bytes[0..4].copy_from_slice(&0xfffffff0u32.to_ne_bytes());
+        assert!(NameIndex::from(&bytes, STRS).is_err());
+        let mut bytes = vec![0xff; 4];
+        bytes.extend_from_slice(&u64::MAX.to_ne_bytes());
+        assert_eq!(NameIndex::from(&bytes, STRS).unwrap_err(),
+            "index of 0xffffffffffffffff bytes runs past the end of the section");
+    }
+
+    // An index over one compile unit at 0x0 and one local type unit at
+    // 0x80, naming a structure in the type unit and one in a foreign type
+    // unit.
+    #[test]
+    fn type_unit_entries() {
+        let abbrev = [
+            0x01, 0x13,       // [1] DW_TAG_structure_type
+            0x02, 0x0b,       // DW_IDX_type_unit, DW_FORM_data1
+            0x03, 0x13,       // DW_IDX_die_offset, DW_FORM_ref4
+            0x00, 0x00,
+            0x00,
+        ];
+        let mut body = vec![];
+        let word = |body: &mut Vec<u8>, w: u32| body.extend_from_slice(&w.to_ne_bytes());
+        body.extend_from_slice(&5u16.to_ne_bytes());
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        for w in [1, 1, 1, 0, 1, abbrev.len() as u32, 0] {
+            word(&mut body, w);
+        }
+        word(&mut body, 0x00);                       // comp unit 0
+        word(&mut body, 0x80);                       // local type unit 0
+        body.extend_from_slice(&[0xaa; 8]);          // foreign type unit 1
+        word(&mut body, 1);
+        word(&mut body, 0);
+        body.extend_from_slice(&abbrev);
+        body.extend_from_slice(&[0x01, 0x00]);       // in local type unit 0
+        word(&mut body, 0x1e);
+        body.extend_from_slice(&[0x01, 0x01]);       // in foreign type unit 1
+        word(&mut body, 0x1e);
+        body.push(0);
+        let mut bytes = (body.len() as u32).to_ne_bytes().to_vec();
+        bytes.extend(body);
+
+        let (index, _) = NameIndex::from(&bytes, STRS).unwrap();
+        assert_eq!(index.type_units, [0x80]);
+        assert_eq!(index.names[0].1, [
+            NameEntry { tag: DIETag::StructureType, die_offset: Some(0x9e) },
+            NameEntry { tag: DIETag::StructureType, die_offset: None },
+        ]);
+        assert_eq!(index.lookup("main"), [0x9e]);
+    }
+
+    #[test]
+    fn die_offset_overflow() {
+        let abbrevs = [NameAbbrev {
+            code: 1,
+            tag: DIETag::Variable,
+            attrs: vec![(DW_IDX_DIE_OFFSET, AttrForm::Data8)],
+        }];
+        let mut pool = vec![0x01];
+        pool.extend_from_slice(&0x10u64.to_ne_bytes());
+        pool.push(0);
+        assert_eq!(NameIndex::entries(&pool, 0, &abbrevs, &[0x40], &[]).unwrap(),
+            [NameEntry { tag: DIETag::Variable, die_offset: Some(0x50) }]);
+        assert_eq!(NameIndex::entries(&pool, 0, &abbrevs, &[u64::MAX - 8], &[]).unwrap_err(),
+            "DIE offset 0x10 in unit at 0xfffffffffffffff7 overflows");
+    }
+}
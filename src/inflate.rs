@@ -0,0 +1,308 @@
+// Decompresses zlib streams (RFC 1950), which wrap DEFLATE data (RFC
+// 1951). Compressed debug sections are zlib streams, and this is all the
+// decompression they need, so it's done here rather than with a crate.
+// It follows the structure of zlib's contrib/puff.
+
+// Where the length and distance codes start, and how many extra bits
+// follow each, from RFC 1951 section 3.2.5.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// The order code length code lengths are sent in.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+// Decompresses the zlib stream `bytes`, checking its header and checksum.
+// A tiny stream can expand enormously, so it fails rather than produce
+// more than `limit` bytes.
+pub fn zlib_decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let [cmf, flg, ..] = *bytes else {
+        return Err("zlib stream is too short".to_string());
+    };
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return Err(format!("zlib stream has unsupported method {:#04x}", cmf));
+    }
+    if !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) {
+        return Err("zlib stream has a bad header check".to_string());
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib stream needs a preset dictionary".to_string());
+    }
+    let mut bits = Bits { bytes: &bytes[2..], position: 0, buffer: 0, count: 0 };
+    let output = inflate(&mut bits, limit)?;
+    let checksum = bits.bytes.get(bits.position..bits.position + 4)
+        .ok_or("zlib stream is missing its checksum")?;
+    let expected = u32::from_be_bytes(checksum.try_into().unwrap());
+    if adler32(&output) != expected {
+        return Err(format!("zlib stream fails its checksum {:#010x}", expected));
+    }
+    Ok(output)
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.bytes.get(self.position).ok_or("deflate stream ends early")?;
+            self.buffer |= (byte as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Drops the bits left in the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// A canonical Huffman code: how many codes there are of each length, and
+// the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman, String> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        // Each length doubles the codes available; more codes than that
+        // can't be decoded.
+        let mut left = 1i32;
+        for &count in counts[1..].iter() {
+            left = 2 * left - count as i32;
+            if left < 0 {
+                return Err("deflate stream has an oversubscribed code".to_string());
+            }
+        }
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in self.counts[1..].iter() {
+            code |= bits.bits(1)? as i32;
+            let count = count as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("deflate stream has an invalid code".to_string())
+    }
+}
+
+fn inflate(bits: &mut Bits, limit: usize) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(bits, &mut output, limit)?,
+            1 => {
+                let (lengths, distances) = fixed_codes()?;
+                codes(bits, &mut output, limit, &lengths, &distances)?
+            },
+            2 => {
+                let (lengths, distances) = dynamic_codes(bits)?;
+                codes(bits, &mut output, limit, &lengths, &distances)?
+            },
+            _ => return Err("deflate stream has a block of reserved type 3".to_string()),
+        }
+        if last {
+            bits.align();
+            return Ok(output);
+        }
+    }
+}
+
+// Copies a block that was stored without compression.
+fn stored(bits: &mut Bits, output: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    bits.align();
+    let header = bits.bytes.get(bits.position..bits.position + 4)
+        .ok_or("deflate stream ends early")?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err("deflate stream has a stored block with a bad length".to_string());
+    }
+    bits.position += 4;
+    let block = bits.bytes.get(bits.position..bits.position + len as usize)
+        .ok_or("deflate stream ends early")?;
+    check_limit(output.len() + block.len(), limit)?;
+    output.extend_from_slice(block);
+    bits.position += len as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> Result<(Huffman, Huffman), String> {
+    let mut lengths = [8u8; 288];
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    Ok((Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let nlen = bits.bits(5)? as usize + 257;
+    let ndist = bits.bits(5)? as usize + 1;
+    let ncode = bits.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err("deflate stream has too many codes".to_string());
+    }
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER[..ncode].iter() {
+        code_lengths[i] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < nlen + ndist {
+        let (length, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or("deflate stream repeats a length before the first")?,
+                3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths.len() > nlen + ndist {
+        return Err("deflate stream repeats lengths past the end".to_string());
+    }
+    if lengths[256] == 0 {
+        return Err("deflate stream has no end of block code".to_string());
+    }
+    Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+}
+
+// Decodes a block's literals and back references up to its end code.
+fn codes(
+    bits: &mut Bits, output: &mut Vec<u8>, limit: usize, lengths: &Huffman, distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        match symbol {
+            0..=255 => {
+                check_limit(output.len() + 1, limit)?;
+                output.push(symbol as u8)
+            },
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(format!("deflate stream has invalid length code {}", symbol));
+                }
+                let length = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let i = distances.decode(bits)? as usize;
+                if i >= DISTANCE_BASE.len() {
+                    return Err(format!("deflate stream has invalid distance code {}", i));
+                }
+                let distance = DISTANCE_BASE[i] as usize + bits.bits(DISTANCE_EXTRA[i] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(format!("deflate stream refers {} bytes back, before its start", distance));
+                }
+                check_limit(output.len() + length, limit)?;
+                // The copy can overlap what it's producing, so go a byte
+                // at a time.
+                for _ in 0..length {
+                    output.push(output[output.len() - distance]);
+                }
+            },
+        }
+    }
+}
+
+fn check_limit(len: usize, limit: usize) -> Result<(), String> {
+    if len > limit {
+        return Err(format!("deflate stream inflates past the {:#x} bytes expected", limit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompress_zlib() {
+        // From Python's zlib.compress, which uses the fixed codes for
+        // input this short, with a back reference for the repeat.
+        let compressed = [
+            0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00,
+            0x3a, 0x2e, 0x06, 0x7d,
+        ];
+        assert_eq!(zlib_decompress(&compressed, 17).unwrap(), b"hello hello hello");
+
+        // Skewed enough that zlib builds its own codes for it.
+        let dynamic = [
+            0x78, 0xda, 0x1d, 0x88, 0xc1, 0x11, 0x00, 0x30, 0x0c, 0x40, 0x66, 0x25,
+            0xf6, 0x9f, 0xa1, 0x69, 0x1e, 0xee, 0x20, 0x03, 0xf2, 0x59, 0x09, 0x26,
+            0xdb, 0xd6, 0xfb, 0xa9, 0xe1, 0xf4, 0x00, 0x39, 0x8c, 0x0f, 0x51,
+        ];
+        assert_eq!(zlib_decompress(&dynamic, 40).unwrap(), b"bacaabaaabacaadaacdbdbaabbcaabadbbbdabcd");
+
+        // A stored block, as zlib.compress(..., level=0) makes.
+        let stored = [0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c', 0x02, 0x4d, 0x01, 0x27];
+        assert_eq!(zlib_decompress(&stored, 3).unwrap(), b"abc");
+
+        let mut bad_checksum = stored;
+        bad_checksum[13] ^= 1;
+        assert_eq!(zlib_decompress(&bad_checksum, 3).unwrap_err(), "zlib stream fails its checksum 0x024d0126");
+        assert_eq!(zlib_decompress(&[0x78, 0x9d], 0).unwrap_err(), "zlib stream has a bad header check");
+        assert_eq!(zlib_decompress(&compressed[..8], 17).unwrap_err(), "deflate stream ends early");
+
+        // Each kind of block stops at the limit, literals and back
+        // references alike.
+        assert_eq!(zlib_decompress(&stored, 2).unwrap_err(), "deflate stream inflates past the 0x2 bytes expected");
+        assert_eq!(zlib_decompress(&compressed, 3).unwrap_err(), "deflate stream inflates past the 0x3 bytes expected");
+        assert_eq!(zlib_decompress(&compressed, 16).unwrap_err(), "deflate stream inflates past the 0x10 bytes expected");
+        assert_eq!(zlib_decompress(&dynamic, 39).unwrap_err(), "deflate stream inflates past the 0x27 bytes expected");
+    }
+}
@@ -3,21 +3,33 @@
 // The Wiki article gives a good description of the format:
 //   https://en.wikipedia.org/wiki/LEB128
 // You can also find it documented in the DWARF documents at dwarfstd.org.
+//
+// The decoders only need `core`, so this file also builds on its own as a
+// no_std crate, with `--cfg leb_no_std`. The encoders and the conversion
+// to String need `alloc`, which `--cfg leb_alloc` adds back. Within this
+// program, which has std, neither is set and everything is there.
 
+#![cfg_attr(leb_no_std, no_std)]
 #![allow(dead_code)]
 
+#[cfg(all(leb_no_std, leb_alloc))]
+extern crate alloc;
+#[cfg(all(leb_no_std, leb_alloc))]
+use alloc::{boxed::Box, string::{String, ToString}, vec};
 
 #[derive(PartialEq, Debug)]
 pub enum Error {
     LastByteHasContinueBit,
 }
 
+#[cfg(any(not(leb_no_std), leb_alloc))]
 impl From<Error> for String {
     fn from(_: Error) -> String {
         "last byte in LEB has continue bit set".to_string()
     }
 }
 
+#[cfg(any(not(leb_no_std), leb_alloc))]
 pub fn uleb128_encode(mut n: u64) -> Box<[u8]> {
     let mut out = vec![];
     loop {
@@ -30,6 +42,12 @@ pub fn uleb128_encode(mut n: u64) -> Box<[u8]> {
     out.into_boxed_slice()
 }
 
+// The number of bytes uleb128_encode would produce for `n`.
+pub fn uleb128_len(n: u64) -> usize {
+    let bits = 64 - n.leading_zeros() as usize;
+    bits.div_ceil(7).max(1)
+}
+
 // Reads a ULEB128-encoded value from the input,
 // and returns the value and the number of bytes consumed.
 pub fn uleb128_decode(bytes: &[u8]) -> Result<(u64, usize), Error> {
@@ -37,13 +55,18 @@ pub fn uleb128_decode(bytes: &[u8]) -> Result<(u64, usize), Error> {
     let mut shift = 0;
     for (i, b) in bytes.iter().enumerate() {
         let byte = (b & 0x7f) as u64;
-        val |= byte << shift;
+        // Past 64 bits, padded encodings only add zeros, and shifting by
+        // that much would overflow.
+        if shift < 64 {
+            val |= byte << shift;
+        }
         if b & 0x80 == 0 { return Ok((val, i+1)); }
         shift += 7;
     }
     Err(Error::LastByteHasContinueBit)
 }
 
+#[cfg(any(not(leb_no_std), leb_alloc))]
 pub fn ileb128_encode(mut n: i64) -> Box<[u8]> {
     let mut out = vec![];
     let mut more = true;
@@ -61,15 +84,27 @@ pub fn ileb128_encode(mut n: i64) -> Box<[u8]> {
     out.into_boxed_slice()
 }
 
+// The number of bytes ileb128_encode would produce for `n`.
+pub fn ileb128_len(n: i64) -> usize {
+    // Every value bit plus a sign bit must fit in the 7-bit groups.
+    let redundant = if n < 0 { n.leading_ones() } else { n.leading_zeros() };
+    let bits = 64 - redundant as usize + 1;
+    bits.div_ceil(7)
+}
+
 pub fn ileb128_decode(bytes: &[u8]) -> Result<(i64, usize), Error> {
     let mut result = 0;
     let mut shift = 0;
     let mut last_byte = 0;
     let mut last_i = 0;
-    for (i, b) in bytes.into_iter().enumerate() {
+    for (i, b) in bytes.iter().enumerate() {
         (last_i, last_byte) = (i, *b);
         let data = (b & 0x7f) as i64;
-        result |= data << shift;
+        // Past 64 bits, padded encodings only repeat the sign, and
+        // shifting by that much would overflow.
+        if shift < 64 {
+            result |= data << shift;
+        }
         shift += 7;
         if b & 0x80 == 0 { break; }
     }
@@ -78,13 +113,15 @@ pub fn ileb128_decode(bytes: &[u8]) -> Result<(i64, usize), Error> {
     }
     // If last byte's sign bit is set..
     if shift < 64 && 0x40 & last_byte != 0 {
-        // ..sign extend the result.
-        result |= -(1 << shift);
+        // ..sign extend the result. Building the mask from all ones avoids
+        // negating 1 << 63, which overflows.
+        result |= (!0u64 << shift) as i64;
     }
     Ok((result, last_i+1))
 }
 
 #[cfg(test)]
+#[allow(clippy::identity_op)]
 mod tests {
     use super::*;
 
@@ -107,6 +144,10 @@ mod tests {
         assert_eq!(uleb128_decode(&[0x80|1,  1]),   Ok((129, 2)));
         assert_eq!(uleb128_decode(&[0x80|2,  1]),   Ok((130, 2)));
         assert_eq!(uleb128_decode(&[0x80|57, 100]), Ok((12857, 2)));
+        assert_eq!(uleb128_decode(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]),
+            Ok((u64::MAX, 10)));
+        assert_eq!(uleb128_decode(&[0x82, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]),
+            Ok((2, 12)));
     }
 
     #[test]
@@ -123,6 +164,43 @@ mod tests {
         assert_eq!(*ileb128_encode(-129), [0x80|0x7f, 0x7e]);
     }
 
+    #[test]
+    fn uleb128_len_matches_encoder() {
+        for n in [0, 1, 127, 128, 16383, 16384, u32::MAX as u64, 1 << 63, u64::MAX] {
+            assert_eq!(uleb128_len(n), uleb128_encode(n).len(), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn ileb128_len_matches_encoder() {
+        for n in [0, 1, -1, 63, 64, -64, -65, 127, 128, -128, -129, 8191, 8192, -8192, -8193,
+                  i64::MAX, i64::MIN] {
+            assert_eq!(ileb128_len(n), ileb128_encode(n).len(), "n = {}", n);
+        }
+    }
+
+    // Builds this file as a crate of its own, without std, and with and
+    // without alloc.
+    #[test]
+    fn builds_without_std() {
+        let src = concat!(env!("CARGO_MANIFEST_DIR"), "/src/leb.rs");
+        for cfgs in [&["--cfg", "leb_no_std"][..], &["--cfg", "leb_no_std", "--cfg", "leb_alloc"][..]] {
+            let out_dir = std::env::temp_dir()
+                .join(format!("mecha-dwarf-leb-{}-{}", std::process::id(), cfgs.len()));
+            let status = std::process::Command::new(
+                    std::env::var("RUSTC").unwrap_or("rustc".to_string()))
+                .args(["--edition", "2021", "--crate-type", "rlib", "--crate-name", "leb"])
+                .args(["--emit", "metadata", "-D", "warnings", "--out-dir"])
+                .arg(&out_dir)
+                .args(cfgs)
+                .arg(src)
+                .status()
+                .unwrap();
+            std::fs::remove_dir_all(&out_dir).ok();
+            assert!(status.success(), "leb.rs failed to build with {:?}", cfgs);
+        }
+    }
+
     #[test]
     fn ileb128_decode_works() {
         assert_eq!(ileb128_decode(&[2]),               Ok(( 2, 1)));
@@ -134,4 +212,31 @@ mod tests {
         assert_eq!(ileb128_decode(&[0x80|0,    0x7f]), Ok((-128, 2)));
         assert_eq!(ileb128_decode(&[0x80|0x7f, 0x7e]), Ok((-129, 2)));
     }
+
+    #[test]
+    fn ileb128_decode_nine_bytes() {
+        // -2^62 takes nine bytes, so the sign extension starts at bit 63.
+        let bytes = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x40];
+        assert_eq!(*ileb128_encode(-1 << 62), bytes);
+        assert_eq!(ileb128_decode(&bytes), Ok((-1 << 62, 9)));
+        assert_eq!(ileb128_decode(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]), Ok((-1, 9)));
+    }
+
+    #[test]
+    fn ileb128_decode_extremes() {
+        // The sign bit of both sits in the tenth byte, the last one with
+        // any bits of an i64.
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+        let min = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f];
+        assert_eq!(*ileb128_encode(i64::MAX), max);
+        assert_eq!(*ileb128_encode(i64::MIN), min);
+        assert_eq!(ileb128_decode(&max), Ok((i64::MAX, 10)));
+        assert_eq!(ileb128_decode(&min), Ok((i64::MIN, 10)));
+
+        // Padded out past 64 bits.
+        assert_eq!(ileb128_decode(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f]),
+            Ok((-1, 11)));
+        assert_eq!(ileb128_decode(&[0x81, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]),
+            Ok((1, 11)));
+    }
 }
@@ -1,35 +1,364 @@
+#![allow(dead_code)]
+
 use std::mem;
+use std::path::Path;
+
+use memmap::{ Mmap, MmapOptions };
+
+use crate::reader::Reader;
 
 #[derive(Debug)]
 pub struct File {
     pub header: Header,
     pub load_commands: Vec<LoadCommand>,
+    // Things the parser skipped over rather than understood, like load
+    // commands it doesn't know.
+    pub warnings: Vec<String>,
 }
 
 impl File {
     pub fn from(bytes: &[u8]) -> Result<File, String> {
-        let header = Header::from_bytes(&bytes[0..32])?;
-        let mut bytes_read = 32;
+        let (header, mut bytes_read) = Header::from_bytes(bytes)?;
+        let mut warnings = vec![];
         let load_commands = {
             let start_of_loads = bytes_read;
             let mut vec: Vec<LoadCommand> = vec![];
             for _ in 0..header.loads_count {
                 let (load, read) = LoadCommand::from(&bytes[bytes_read..])?;
+                if let LoadCommandDetails::UnrecognizedLoad(ttype) = load.details {
+                    warnings.push(format!("skipped unrecognized load command {:#x} at offset {:#x}",
+                        ttype, bytes_read));
+                }
                 vec.push(load);
                 bytes_read += read;
             }
             let loads_size = bytes_read - start_of_loads;
             if loads_size != header.loads_size.try_into().unwrap() {
-                return Err(format!("expected loads to be {}B, but instead found {}B",
-                        header.loads_size, loads_size));
+                return Err(format!("expected loads to be {}B, but instead found {}B; {}",
+                        header.loads_size, loads_size,
+                        Self::describe_drift(&vec, start_of_loads, header.loads_size as usize)));
             }
             vec
         };
+        // Names that weren't UTF-8 were read lossily.
+        for load in load_commands.iter() {
+            if let LoadCommandDetails::Segment64(seg) = &load.details {
+                let names = std::iter::once(&seg.segname)
+                    .chain(seg.sections.iter().map(|sec| &sec.sectname));
+                for name in names.filter(|name| name.contains(char::REPLACEMENT_CHARACTER)) {
+                    warnings.push(format!("name {:?} in segment {:?} isn't valid UTF-8", name, seg.segname));
+                }
+            }
+        }
         Ok(File {
             header,
             load_commands,
+            warnings,
         })
     }
+
+    // Explains where the load commands stopped adding up to the header's
+    // loads_size. The culprit is the first command whose size disagrees
+    // with what its contents need, or failing that, the first command that
+    // runs past loads_size (or the last one, if they stop short).
+    fn describe_drift(loads: &[LoadCommand], start_of_loads: usize, loads_size: usize) -> String {
+        let mut offsets = vec![];
+        let mut offset = 0;
+        for load in loads {
+            offsets.push(offset);
+            offset += load.size as usize;
+        }
+        let describe = |i: usize| {
+            let load = &loads[i];
+            format!("drift begins at load command {} ({}) at offset {:#x}, which is {}B",
+                i, load.details.name(), start_of_loads + offsets[i], load.size)
+        };
+        if let Some((i, expected)) = loads.iter().enumerate()
+            .find_map(|(i, load)| match load.details.expected_size() {
+                Some(expected) if expected != load.size => Some((i, expected)),
+                _ => None,
+            })
+        {
+            return format!("{} but should be {}B", describe(i), expected);
+        }
+        match (0..loads.len()).find(|&i| offsets[i] + loads[i].size as usize > loads_size) {
+            Some(i) => format!("{} and runs past the end of the loads", describe(i)),
+            None if loads.is_empty() => "there are no load commands".to_string(),
+            None => format!("{} and ends before the end of the loads", describe(loads.len() - 1)),
+        }
+    }
+
+    // Maps the file at `path` and parses it. The mapping is returned
+    // alongside the File so that section contents can still be read.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<(File, Mmap), String> {
+        let mmap = map_file(path)?;
+        let parsed = File::from(&mmap[..])
+            .map_err(|e| format!("error parsing macho: {}", e))?;
+        Ok((parsed, mmap))
+    }
+
+    // Iterates over the load commands picked out by `select`.
+    pub fn load_commands_of<'a, T: 'a>(
+        &'a self, select: impl Fn(&'a LoadCommandDetails) -> Option<T> + 'a
+    ) -> impl Iterator<Item = T> + 'a {
+        self.load_commands.iter().filter_map(move |cmd| select(&cmd.details))
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &Segment64> {
+        self.load_commands_of(|details| match details {
+            LoadCommandDetails::Segment64(seg) => Some(seg),
+            _ => None,
+        })
+    }
+
+    pub fn segment(&self, name: &str) -> Option<&Segment64> {
+        self.segments().find(|seg| seg.segname == name)
+    }
+
+    // The segment holding the DWARF: __DWARF in linked files and dSYMs, or
+    // else the first with __debug_* sections, as in object files, whose
+    // sections all share one unnamed segment.
+    pub fn dwarf_segment(&self) -> Option<&Segment64> {
+        self.segment("__DWARF").or_else(|| self.segments()
+            .find(|seg| seg.sections.iter()
+                .any(|sec| sec.sectname.starts_with("__debug_") || sec.sectname.starts_with("__zdebug_"))))
+    }
+
+    // The section called `sect` in the segment called `seg`.
+    pub fn section(&self, seg: &str, sect: &str) -> Option<&Section64> {
+        self.segment(seg)?.section(sect)
+    }
+
+    // The address execution starts at. That's LC_MAIN's entry offset
+    // mapped through __TEXT, or else the program counter in LC_UNIXTHREAD,
+    // for which only x86_64 and arm64 thread states are understood.
+    pub fn entry_point(&self) -> Option<u64> {
+        let main = self.load_commands_of(|details| match *details {
+            LoadCommandDetails::Main { entryoff, .. } => Some(entryoff),
+            _ => None,
+        }).next();
+        if let Some(entryoff) = main {
+            let text = self.segment("__TEXT")?;
+            return text.vmaddr.checked_add(entryoff)?.checked_sub(text.fileoff);
+        }
+        self.load_commands_of(|details| match details {
+            LoadCommandDetails::UnixThread { flavor, registers } =>
+                thread_pc(&self.header.cpu_type, *flavor, registers),
+            _ => None,
+        }).next()
+    }
+
+    // The platform and minimum OS version the file was built for, from
+    // LC_BUILD_VERSION or else one of the older LC_VERSION_MIN_* commands.
+    pub fn min_os(&self) -> Option<(BuildPlatform, u32)> {
+        let build_version = self.load_commands_of(|details| match *details {
+            LoadCommandDetails::BuildVersion { platform, minos, .. } => Some((platform, minos)),
+            _ => None,
+        }).next();
+        build_version.or_else(|| self.load_commands_of(|details| match *details {
+            LoadCommandDetails::VersionMin { platform, version, .. } => Some((platform, version)),
+            _ => None,
+        }).next())
+    }
+
+    // Decodes the addresses in LC_FUNCTION_STARTS, if present. The data is
+    // a list of ULEB128 deltas: the first from the start of __TEXT, and
+    // each following one from the previous function. A zero ends the list.
+    pub fn function_starts(&self, bytes: &[u8]) -> Result<Vec<u64>, String> {
+        let Some((dataoff, datasize)) = self.load_commands_of(|details| match *details {
+            LoadCommandDetails::FunctionStarts { dataoff, datasize } => Some((dataoff, datasize)),
+            _ => None,
+        }).next() else { return Ok(vec![]) };
+        let start = dataoff as usize;
+        let end = start.checked_add(datasize as usize)
+            .filter(|&end| end <= bytes.len())
+            .ok_or("function starts run past end of file")?;
+        let mut addr = self.segment("__TEXT").map(|seg| seg.vmaddr).unwrap_or(0);
+        let mut reader = Reader::new(&bytes[start..end]);
+        let mut starts = vec![];
+        while !reader.is_empty() {
+            let delta = reader.uleb()?;
+            if delta == 0 { break; }
+            addr = addr.checked_add(delta)
+                .ok_or_else(|| format!("function start {:#x} + {:#x} overflows", addr, delta))?;
+            starts.push(addr);
+        }
+        Ok(starts)
+    }
+
+    // Parses the symbol table entries, if the file has a symbol table.
+    pub fn symbols(&self, bytes: &[u8]) -> Result<Vec<Symbol>, String> {
+        let Some((symoff, nsyms)) = self.load_commands_of(|details| match *details {
+            LoadCommandDetails::SymbolTable { symoff, nsyms, .. } => Some((symoff, nsyms)),
+            _ => None,
+        }).next() else { return Ok(vec![]) };
+        let start = symoff as usize;
+        let end = (nsyms as usize).checked_mul(Symbol::SIZE)
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= bytes.len())
+            .ok_or("symbol table runs past end of file")?;
+        Ok(bytes[start..end].chunks(Symbol::SIZE).map(Symbol::from).collect())
+    }
+
+    // The names of the loaded dylibs, in load command order. Library
+    // ordinal n refers to the nth of these.
+    pub fn dylibs(&self) -> impl Iterator<Item = &str> {
+        self.load_commands_of(|details| match details {
+            LoadCommandDetails::LoadDylib(dylib) => Some(dylib.name.as_str()),
+            _ => None,
+        })
+    }
+
+    // Each loaded dylib's name, current version and compatibility
+    // version, in load command order. format_version reads the versions.
+    pub fn linked_dylibs(&self) -> Vec<(String, u32, u32)> {
+        self.load_commands_of(|details| match details {
+            LoadCommandDetails::LoadDylib(dylib) => Some(dylib),
+            _ => None,
+        })
+        .map(|dylib| (dylib.name.clone(), dylib.current_version, dylib.compatibility_version))
+        .collect()
+    }
+
+    // The symbol table's strings, if the file has a symbol table.
+    pub fn string_table<'a>(&self, bytes: &'a [u8]) -> Result<Option<StringTable<'a>>, String> {
+        let Some((stroff, strsize)) = self.load_commands_of(|details| match *details {
+            LoadCommandDetails::SymbolTable { stroff, strsize, .. } => Some((stroff, strsize)),
+            _ => None,
+        }).next() else { return Ok(None) };
+        let start = stroff as usize;
+        let end = start.checked_add(strsize as usize)
+            .filter(|&end| end <= bytes.len())
+            .ok_or("string table runs past end of file")?;
+        Ok(Some(StringTable::new(&bytes[start..end])))
+    }
+
+    // The section with the given 1-based number, as in a symbol's n_sect.
+    // Sections are numbered across all segments, in load command order.
+    pub fn numbered_section(&self, n_sect: u8) -> Option<&Section64> {
+        let index = (n_sect as usize).checked_sub(1)?;
+        self.segments().flat_map(|seg| seg.sections.iter()).nth(index)
+    }
+
+    // The name of the symbol defined in a section at or closest below
+    // `address`, if there is one. A symbol covers up to the next symbol in
+    // its section, or failing that the end of the section, so addresses
+    // past both aren't attributed to it.
+    pub fn symbol_at<'a>(&self, bytes: &'a [u8], address: u64) -> Result<Option<&'a str>, String> {
+        let Some(strings) = self.string_table(bytes)? else { return Ok(None) };
+        let symbols: Vec<Symbol> = self.symbols(bytes)?.into_iter()
+            .filter(Symbol::is_section_defined)
+            .collect();
+        let Some(symbol) = symbols.iter()
+            .filter(|symbol| symbol.n_value <= address)
+            .max_by_key(|symbol| symbol.n_value)
+        else { return Ok(None) };
+        let next = symbols.iter()
+            .filter(|other| other.n_sect == symbol.n_sect && other.n_value > symbol.n_value)
+            .map(|other| other.n_value)
+            .min();
+        let section_end = self.numbered_section(symbol.n_sect)
+            .and_then(|sec| sec.addr.checked_add(sec.size));
+        let end = next.into_iter().chain(section_end).min();
+        if end.is_some_and(|end| address >= end) {
+            return Ok(None);
+        }
+        Ok(symbol.name(&strings))
+    }
+
+    // Applies the relocations of each section in `segment` to `bytes`,
+    // which should be a copy of the whole file.
+    //
+    // Only UNSIGNED relocations (type 0 on both x86_64 and arm64) against
+    // external symbols need patching: the symbol's value is added to the
+    // addend stored in place. Section-relative relocations already hold
+    // the target address, and other types are left untouched.
+    pub fn apply_relocations(&self, segment: &Segment64, bytes: &mut [u8]) -> Result<(), String> {
+        let symbols = self.symbols(bytes)?;
+        for sec in segment.sections.iter() {
+            for reloc in sec.relocations(bytes)? {
+                // Scattered relocations point at an address, which the
+                // addend in place already holds.
+                let Relocation::Plain(reloc) = reloc else { continue };
+                if !reloc.r_extern || reloc.r_type != 0 { continue; }
+                let symbol = symbols.get(reloc.r_symbolnum as usize)
+                    .ok_or_else(|| format!("relocation in {} references missing symbol {}",
+                        sec.sectname, reloc.r_symbolnum))?;
+                let width = match reloc.r_length {
+                    2 => 4,
+                    3 => 8,
+                    n => return Err(format!("unsupported relocation length {} in {}", n, sec.sectname)),
+                };
+                if reloc.r_address < 0 || reloc.r_address as u64 + width as u64 > sec.size {
+                    return Err(format!("relocation at {:#x} is outside {}", reloc.r_address, sec.sectname));
+                }
+                let target = (sec.offset as usize).checked_add(reloc.r_address as usize)
+                    .and_then(|start| bytes.get_mut(start..start.checked_add(width)?))
+                    .ok_or_else(|| format!("relocation at {:#x} in {} is past the end of the file",
+                        reloc.r_address, sec.sectname))?;
+                if width == 4 {
+                    let addend = u32::from_ne_bytes((*target).try_into().unwrap());
+                    let value = addend.wrapping_add(symbol.n_value as u32);
+                    target.copy_from_slice(&value.to_ne_bytes());
+                } else {
+                    let addend = u64::from_ne_bytes((*target).try_into().unwrap());
+                    let value = addend.wrapping_add(symbol.n_value);
+                    target.copy_from_slice(&value.to_ne_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// A short summary of the header and segments, along the lines of
+// `otool -h` and the basics of `otool -l`.
+impl std::fmt::Display for File {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "cpu type:      {:?}", self.header.cpu_type)?;
+        writeln!(f, "file type:     {}", self.header.file_type)?;
+        writeln!(f, "64-bit:        {}", if self.header.is_64_bit { "yes" } else { "no" })?;
+        writeln!(f, "load commands: {} ({}B)", self.header.loads_count, self.header.loads_size)?;
+        writeln!(f, "segments:")?;
+        for seg in self.segments() {
+            writeln!(f, "  {:<16} vmaddr {:#018x} vmsize {:#010x} filesize {:#010x} sections {}",
+                seg.segname, seg.vmaddr, seg.vmsize, seg.filesize, seg.sections.len())?;
+        }
+        let dylibs = self.linked_dylibs();
+        if !dylibs.is_empty() {
+            writeln!(f, "dylibs:")?;
+        }
+        for (name, current, compat) in dylibs {
+            writeln!(f, "  {} (compatibility version {}, current version {})",
+                name, format_version(compat), format_version(current))?;
+        }
+        Ok(())
+    }
+}
+
+// Finds the program counter in a thread state from <mach/*/thread_status.h>.
+fn thread_pc(cpu_type: &CpuType, flavor: u32, registers: &[u32]) -> Option<u64> {
+    // The index of the 64-bit PC register among the state's 64-bit words.
+    let index = match (cpu_type, flavor) {
+        // x86_THREAD_STATE64: rax through r15, then rip.
+        (CpuType::X86(_), 4) => 16,
+        // ARM_THREAD_STATE64: x0 through x28, fp, lr, sp, then pc.
+        (CpuType::Arm(_), 6) => 32,
+        _ => return None,
+    };
+    let low  = *registers.get(2*index)? as u64;
+    let high = *registers.get(2*index + 1)? as u64;
+    Some(if cfg!(target_endian = "little") { high << 32 | low } else { low << 32 | high })
+}
+
+// Maps the file at `path` into memory.
+pub fn map_file(path: impl AsRef<Path>) -> Result<Mmap, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("error opening file: {}", e))?;
+    unsafe {
+        MmapOptions::new().map(&file)
+            .map_err(|e| format!("error mmaping file: {}", e))
+    }
 }
 
 #[derive(Debug)]
@@ -39,15 +368,38 @@ pub struct Header {
     pub file_type: FileType,
     pub loads_count: u32,
     pub loads_size: u32,
-    // TODO: Add flag: Flags, and deal with transmuting.
+    pub flags: Flags,
 }
 
 impl Header {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Header, String> {
-        Self::from_header(RawHeader::from(bytes))
+    // Parses the header at the start of the file, returning it along with
+    // its size: 32 bytes for 64-bit files, or 28 for 32-bit ones, which
+    // lack the reserved field.
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Header, usize), String> {
+        if bytes.len() < 28 {
+            return Err(format!("Mach-O header needs at least 28 bytes, but file is {}B",
+                bytes.len()));
+        }
+        let mut raw = [0; 32];
+        let len = bytes.len().min(32);
+        raw[..len].copy_from_slice(&bytes[..len]);
+        let header = Self::from_header(RawHeader::from(&raw))?;
+        if header.is_64_bit && bytes.len() < 32 {
+            return Err(format!("64-bit Mach-O header needs 32 bytes, but file is {}B",
+                bytes.len()));
+        }
+        let size = if header.is_64_bit { 32 } else { 28 };
+        Ok((header, size))
     }
 
     pub fn from_header(raw: RawHeader) -> Result<Header, String> {
+        // The cpu type is byte-swapped along with the magic, so check
+        // for a swapped magic before trusting the arch bit.
+        if raw.magic == 0xcefaedfe || raw.magic == 0xcffaedfe {
+            return Err(format!(
+                "file is byte-swapped relative to host (magic {:#010x}); endianness support needed",
+                raw.magic));
+        }
         let is_64_bit = (0x01000000 & raw.cpu_type) != 0;
         match raw.magic {
             0xfeedface if !is_64_bit => {},
@@ -61,11 +413,12 @@ impl Header {
         }
         Ok(Header {
             cpu_type: CpuType::from(raw.cpu_type, raw.cpu_subtype)?,
-            is_64_bit: is_64_bit,
+            is_64_bit,
             file_type: FileType::from(raw.file_type)
                 .ok_or(format!("bad file type: {}", raw.file_type))?,
             loads_count: raw.loads_count,
             loads_size: raw.loads_size,
+            flags: Flags::from_bits_truncate(raw.flags),
         })
     }
 }
@@ -207,7 +560,7 @@ impl ArmSubtype {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     RelocatableObj,
     DemandPagedExe,
@@ -241,10 +594,44 @@ impl FileType {
             _ => None,
         }
     }
+
+    pub fn is_executable(&self) -> bool {
+        matches!(self, FileType::DemandPagedExe | FileType::PreloadedExe)
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, FileType::RelocatableObj)
+    }
+
+    pub fn is_dylib(&self) -> bool {
+        matches!(self, FileType::DynamicallyBoundSharedLib | FileType::SharedLibraryStub)
+    }
+}
+
+// The MH_* name of the file type, as in <mach-o/loader.h>.
+impl std::fmt::Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FileType::RelocatableObj            => "MH_OBJECT",
+            FileType::DemandPagedExe            => "MH_EXECUTE",
+            FileType::FixedVmSharedLib          => "MH_FVMLIB",
+            FileType::Core                      => "MH_CORE",
+            FileType::PreloadedExe              => "MH_PRELOAD",
+            FileType::DynamicallyBoundSharedLib => "MH_DYLIB",
+            FileType::DynamicLinkEditor         => "MH_DYLINKER",
+            FileType::DynamicallyBoundBundle    => "MH_BUNDLE",
+            FileType::SharedLibraryStub         => "MH_DYLIB_STUB",
+            FileType::CompanionDebugOnly        => "MH_DSYM",
+            FileType::X8664Kexts                => "MH_KEXT_BUNDLE",
+            FileType::CompositeMacho            => "MH_FILESET",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 use bitflags::bitflags;
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Flags: u32 {
         // The object file has no undefined references.
         const NO_UNDEFINED_REFERENCES = 0b0000_0000_0000_0000_0000_0000_0000_0001;
@@ -301,6 +688,30 @@ pub enum LoadCommandDetails {
 
     Uuid([u8; 16]),
 
+    LoadDylinker(String),
+
+    // LC_LOAD_DYLIB and its weak, reexport and upward variants. Their
+    // order sets the library ordinals that symbols refer to.
+    LoadDylib(Dylib),
+
+    // LC_ID_DYLIB, which a dylib uses to name itself.
+    IdDylib(Dylib),
+
+    FunctionStarts {
+        dataoff: u32,   /* file offset of data in __LINKEDIT segment */
+        datasize: u32,  /* file size of data in __LINKEDIT segment  */
+    },
+
+    Rpath(String),
+
+    // Commands that point at a blob in __LINKEDIT, such as
+    // LC_CODE_SIGNATURE and LC_DATA_IN_CODE. `cmd` says which.
+    LinkeditData {
+        cmd: u32,
+        dataoff: u32,   /* file offset of data in __LINKEDIT segment */
+        datasize: u32,  /* file size of data in __LINKEDIT segment  */
+    },
+
     BuildVersion {
         platform: BuildPlatform,
         minos: u32,
@@ -308,6 +719,30 @@ pub enum LoadCommandDetails {
         tools: Vec<BuildToolVersion>,
     },
 
+    // LC_VERSION_MIN_MACOSX, _IPHONEOS, _TVOS and _WATCHOS, which older
+    // toolchains emit instead of LC_BUILD_VERSION.
+    VersionMin {
+        platform: BuildPlatform,
+        version: u32,
+        sdk: u32,
+    },
+
+    // LC_MAIN: where the main thread starts, as a file offset, and the
+    // size of its stack, or 0 for the default.
+    Main {
+        entryoff: u64,
+        stacksize: u64,
+    },
+
+    // LC_UNIXTHREAD: the initial register state of the main thread, which
+    // is how executables from before LC_MAIN give their entry point. The
+    // registers are the thread_state words of `flavor`, which depends on
+    // the CPU type.
+    UnixThread {
+        flavor: u32,
+        registers: Vec<u32>,
+    },
+
     UnrecognizedLoad(u32),
 }
 
@@ -326,7 +761,14 @@ pub struct Segment64 {
     pub sections: Vec<Section64>,
 }
 
-#[derive(Debug)]
+impl Segment64 {
+    // The first of the segment's sections called `name`.
+    pub fn section(&self, name: &str) -> Option<&Section64> {
+        self.sections.iter().find(|sec| sec.sectname == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BuildPlatform {
     MacOS,
     IOS,
@@ -347,12 +789,85 @@ impl BuildPlatform {
     }
 }
 
+// Formats a version packed as xxxx.yy.zz nibbles, as in LC_BUILD_VERSION
+// and LC_VERSION_MIN_*.
+pub fn format_version(version: u32) -> String {
+    format!("{}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff)
+}
+
 #[derive(Debug)]
 pub struct BuildToolVersion {
     pub tool: u32,
     pub version: u32,
 }
 
+impl std::fmt::Display for BuildToolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.tool {
+            1 => write!(f, "clang")?,
+            2 => write!(f, "swift")?,
+            3 => write!(f, "ld")?,
+            4 => write!(f, "lld")?,
+            tool => write!(f, "tool {:#x}", tool)?,
+        }
+        write!(f, " {}", format_version(self.version))
+    }
+}
+
+impl LoadCommandDetails {
+    // The LC_* name of the command.
+    pub fn name(&self) -> String {
+        match self {
+            LoadCommandDetails::SymbolTable { .. }    => "LC_SYMTAB".to_string(),
+            LoadCommandDetails::Segment64(_)          => "LC_SEGMENT_64".to_string(),
+            LoadCommandDetails::Uuid(_)               => "LC_UUID".to_string(),
+            LoadCommandDetails::LoadDylinker(_)       => "LC_LOAD_DYLINKER".to_string(),
+            LoadCommandDetails::LoadDylib(_)          => "LC_LOAD_DYLIB".to_string(),
+            LoadCommandDetails::IdDylib(_)            => "LC_ID_DYLIB".to_string(),
+            LoadCommandDetails::FunctionStarts { .. } => "LC_FUNCTION_STARTS".to_string(),
+            LoadCommandDetails::Rpath(_)              => "LC_RPATH".to_string(),
+            LoadCommandDetails::LinkeditData { cmd, .. } => match cmd {
+                0x1d => "LC_CODE_SIGNATURE".to_string(),
+                0x1e => "LC_SEGMENT_SPLIT_INFO".to_string(),
+                0x29 => "LC_DATA_IN_CODE".to_string(),
+                0x2b => "LC_DYLIB_CODE_SIGN_DRS".to_string(),
+                0x2e => "LC_LINKER_OPTIMIZATION_HINT".to_string(),
+                0x80000033 => "LC_DYLD_EXPORTS_TRIE".to_string(),
+                0x80000034 => "LC_DYLD_CHAINED_FIXUPS".to_string(),
+                cmd => format!("load command {:#x}", cmd),
+            },
+            LoadCommandDetails::BuildVersion { .. }   => "LC_BUILD_VERSION".to_string(),
+            LoadCommandDetails::VersionMin { platform, .. } => match platform {
+                BuildPlatform::MacOS   => "LC_VERSION_MIN_MACOSX".to_string(),
+                BuildPlatform::IOS     => "LC_VERSION_MIN_IPHONEOS".to_string(),
+                BuildPlatform::TVOS    => "LC_VERSION_MIN_TVOS".to_string(),
+                BuildPlatform::WatchOS => "LC_VERSION_MIN_WATCHOS".to_string(),
+                BuildPlatform::Other(_) => "LC_VERSION_MIN".to_string(),
+            },
+            LoadCommandDetails::Main { .. }           => "LC_MAIN".to_string(),
+            LoadCommandDetails::UnixThread { .. }     => "LC_UNIXTHREAD".to_string(),
+            LoadCommandDetails::UnrecognizedLoad(ttype) => format!("load command {:#x}", ttype),
+        }
+    }
+
+    // The size in bytes the command should be, for commands with a fixed
+    // layout. Commands carrying strings may be padded, so have no one size.
+    pub fn expected_size(&self) -> Option<u32> {
+        match self {
+            LoadCommandDetails::SymbolTable { .. } => Some(24),
+            LoadCommandDetails::Segment64(seg) =>
+                Some((8 + SEGMENT64_SIZE + seg.sections.len() * Section64::SIZE) as u32),
+            LoadCommandDetails::Uuid(_) => Some(24),
+            LoadCommandDetails::FunctionStarts { .. } => Some(16),
+            LoadCommandDetails::LinkeditData { .. } => Some(16),
+            LoadCommandDetails::BuildVersion { tools, .. } => Some(24 + 8 * tools.len() as u32),
+            LoadCommandDetails::VersionMin { .. } => Some(16),
+            LoadCommandDetails::Main { .. } => Some(24),
+            _ => None,
+        }
+    }
+}
+
 impl LoadCommand {
     pub fn from(bytes: &[u8]) -> Result<(LoadCommand, usize), String> {
         if bytes.len() < 8 { return Err("ran out of bytes reading load command".to_string()); }
@@ -361,8 +876,27 @@ impl LoadCommand {
         let (size_bytes, bytes) = bytes.split_at(mem::size_of::<u32>());
         let size = u32::from_ne_bytes(size_bytes.try_into().unwrap());
 
-        if bytes.len() < size as usize {
-            return Err("ran out of bytes reading load command".to_string());
+        // A size under 8 can't even cover the type and size, and would
+        // leave the caller stuck on the same bytes.
+        if size < 8 {
+            return Err(format!("load command {:#x} has size {}B, which is less than its 8B header",
+                ttype, size));
+        }
+        if bytes.len() < size as usize - 8 {
+            return Err(format!("load command {:#x} is {}B, but only {}B remain",
+                ttype, size, bytes.len() + 8));
+        }
+        // Keep every read below within this command.
+        let bytes = &bytes[..size as usize - 8];
+        // The fixed-layout commands' bodies must hold all their fields.
+        let min_body_size = match ttype {
+            0x02 | 0x1b | 0x32 | 0x80000028 => 16,
+            0x19 => SEGMENT64_SIZE,
+            0x05 | 0x24 | 0x25 | 0x2f | 0x30 | 0x26 | 0x1d | 0x1e | 0x29 | 0x2b | 0x2e | 0x80000033 | 0x80000034 => 8,
+            _ => 0,
+        };
+        if bytes.len() < min_body_size {
+            return Err(format!("load command {:#x} of {}B is too short for its fields", ttype, size));
         }
         let details = match ttype {
             0x02 => Ok::<LoadCommandDetails, String>(LoadCommandDetails::SymbolTable {
@@ -377,14 +911,12 @@ impl LoadCommand {
                 let mut sections = vec![];
                 for i in 0..nsects as usize {
                     let start = SEGMENT64_SIZE + i*Section64::SIZE;
-                    let end = start + Section64::SIZE;
-                    sections.push(Section64::from(&bytes[start..end]));
+                    let section = Section64::from(bytes.get(start..).unwrap_or(&[]))
+                        .map_err(|e| format!("section {} of segment: {}", i, e))?;
+                    sections.push(section);
                 }
                 Ok(LoadCommandDetails::Segment64(Segment64 {
-                    segname:  std::str::from_utf8(&bytes[0..16])
-                        .map_err(|e| format!("{}", e))?
-                        .trim_matches(char::from(0))
-                        .to_string(),
+                    segname:  fixed_name(&bytes[0..16]),
                     vmaddr:   u64::from_ne_bytes(bytes[16..24].try_into().unwrap()),
                     vmsize:   u64::from_ne_bytes(bytes[24..32].try_into().unwrap()),
                     fileoff:  u64::from_ne_bytes(bytes[32..40].try_into().unwrap()),
@@ -397,15 +929,71 @@ impl LoadCommand {
                 }))
             }
 
+            // LC_LOAD_DYLIB, LC_LAZY_LOAD_DYLIB, LC_LOAD_WEAK_DYLIB,
+            // LC_REEXPORT_DYLIB and LC_LOAD_UPWARD_DYLIB all count towards
+            // library ordinals.
+            0x0c | 0x20 | 0x80000018 | 0x8000001f | 0x80000023 =>
+                Ok(LoadCommandDetails::LoadDylib(Dylib::from(bytes, size)?)),
+
+            0x0d => Ok(LoadCommandDetails::IdDylib(Dylib::from(bytes, size)?)),
+
+            0x0e => Ok(LoadCommandDetails::LoadDylinker(lc_str(bytes, size)?)),
+
             0x1b => Ok(LoadCommandDetails::Uuid(bytes[0..16].try_into().unwrap())),
 
+            0x24 | 0x25 | 0x2f | 0x30 => Ok(LoadCommandDetails::VersionMin {
+                platform: match ttype {
+                    0x24 => BuildPlatform::MacOS,
+                    0x25 => BuildPlatform::IOS,
+                    0x2f => BuildPlatform::TVOS,
+                    _    => BuildPlatform::WatchOS,
+                },
+                version: u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
+                sdk:     u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+            }),
+
+            0x26 => Ok(LoadCommandDetails::FunctionStarts {
+                dataoff:  u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
+                datasize: u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+            }),
+
+            0x1d | 0x1e | 0x29 | 0x2b | 0x2e | 0x80000033 | 0x80000034 =>
+                Ok(LoadCommandDetails::LinkeditData {
+                    cmd: ttype,
+                    dataoff:  u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
+                    datasize: u32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+                }),
+
+            0x8000001c => Ok(LoadCommandDetails::Rpath(lc_str(bytes, size)?)),
+
+            0x80000028 => Ok(LoadCommandDetails::Main {
+                entryoff:  u64::from_ne_bytes(bytes[0.. 8].try_into().unwrap()),
+                stacksize: u64::from_ne_bytes(bytes[8..16].try_into().unwrap()),
+            }),
+
+            0x05 => {
+                // Only the first thread state is read. Executables have
+                // just the one.
+                let flavor = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
+                let count  = u32::from_ne_bytes(bytes[4..8].try_into().unwrap()) as usize;
+                let state = bytes.get(8..8 + 4*count)
+                    .ok_or(format!("LC_UNIXTHREAD state of {} words runs past the end of its {}B command",
+                        count, size))?;
+                Ok(LoadCommandDetails::UnixThread {
+                    flavor,
+                    registers: state.chunks_exact(4)
+                        .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+                        .collect(),
+                })
+            }
+
             0x32 => {
                 let platform = BuildPlatform::from(u32::from_ne_bytes(bytes[0..4].try_into().unwrap()));
                 let minos  = u32::from_ne_bytes(bytes[ 4.. 8].try_into().unwrap());
                 let sdk    = u32::from_ne_bytes(bytes[ 8..12].try_into().unwrap());
                 let ntools = u32::from_ne_bytes(bytes[12..16].try_into().unwrap());
-                let expected_size = 0x18 + ntools * 8;
-                if size != expected_size {
+                let expected_size = 0x18 + ntools as u64 * 8;
+                if size as u64 != expected_size {
                     return Err(format!("BuildCommand is {}B, but should be {}B. possible corruption", size, expected_size));
                 }
                 let mut tools: Vec<BuildToolVersion> = vec![];
@@ -434,6 +1022,49 @@ impl LoadCommand {
     }
 }
 
+// Reads the string referenced by the lc_str offset at the start of a load
+// command body. The offset is from the start of the load command, and the
+// string runs to the first null or the end of the command.
+fn lc_str(body: &[u8], cmd_size: u32) -> Result<String, String> {
+    let offset = body.get(0..4)
+        .ok_or_else(|| format!("load command of {}B is too short for its lc_str offset", cmd_size))?;
+    let offset = u32::from_ne_bytes(offset.try_into().unwrap()) as usize;
+    let end = body.len();
+    if offset < 8 || offset - 8 > end {
+        return Err(format!("lc_str offset {:#x} is outside its load command", offset));
+    }
+    if offset - 8 == end {
+        return Ok(String::new());
+    }
+    StringTable::new(&body[..end]).get(offset - 8)
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("lc_str at offset {:#x} isn't valid UTF-8", offset))
+}
+
+// The dylib named by a dylib_command.
+#[derive(Debug)]
+pub struct Dylib {
+    pub name: String,
+    pub timestamp: u32,              /* library's build time stamp */
+    pub current_version: u32,        /* library's current version number */
+    pub compatibility_version: u32,  /* library's compatibility vers number */
+}
+
+impl Dylib {
+    fn from(body: &[u8], cmd_size: u32) -> Result<Dylib, String> {
+        if body.len() < 16 {
+            return Err(format!("dylib command of {}B is too short", cmd_size));
+        }
+        let word = |i: usize| u32::from_ne_bytes(body[4*i..4*i + 4].try_into().unwrap());
+        Ok(Dylib {
+            name: lc_str(body, cmd_size)?,
+            timestamp: word(1),
+            current_version: word(2),
+            compatibility_version: word(3),
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct Section64 {
     pub sectname: String,
@@ -449,18 +1080,22 @@ pub struct Section64 {
     pub reserved2: u32,
 }
 
+// Reads a segment or section name, which is padded with NULs to 16 bytes.
+// Bytes that aren't UTF-8 become U+FFFD rather than failing the parse.
+fn fixed_name(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim_matches('\0').to_string()
+}
+
 impl Section64 {
     const SIZE: usize = 80; // Round up from 76 to word boundary.
-    pub fn from(bytes: &[u8]) -> Section64 {
-        Section64 {
-            sectname:  std::str::from_utf8(&bytes[ 0..16])
-                .unwrap()
-                .trim_matches(char::from(0))
-                .to_string(),
-            segname:  std::str::from_utf8(&bytes[16..32])
-                .unwrap()
-                .trim_matches(char::from(0))
-                .to_string(),
+    pub fn from(bytes: &[u8]) -> Result<Section64, String> {
+        if bytes.len() < Self::SIZE {
+            return Err(format!("section header needs {} bytes, but only {} remain",
+                Self::SIZE, bytes.len()));
+        }
+        Ok(Section64 {
+            sectname:  fixed_name(&bytes[ 0..16]),
+            segname:   fixed_name(&bytes[16..32]),
             addr:      u64::from_ne_bytes(bytes[32..40].try_into().unwrap()),
             size:      u64::from_ne_bytes(bytes[40..48].try_into().unwrap()),
             offset:    u32::from_ne_bytes(bytes[48..52].try_into().unwrap()),
@@ -470,10 +1105,197 @@ impl Section64 {
             flags:     u32::from_ne_bytes(bytes[64..68].try_into().unwrap()),
             reserved1: u32::from_ne_bytes(bytes[68..72].try_into().unwrap()),
             reserved2: u32::from_ne_bytes(bytes[72..76].try_into().unwrap()),
+        })
+    }
+
+    // Returns the section's contents out of the whole file.
+    pub fn contents<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], String> {
+        let start = self.offset as usize;
+        let end = usize::try_from(self.size).ok()
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| format!("{} runs past end of file", self.sectname))?;
+        Ok(&bytes[start..end])
+    }
+
+    // Parses the section's relocation entries out of the whole file.
+    pub fn relocations(&self, bytes: &[u8]) -> Result<Vec<Relocation>, String> {
+        let start = self.reloff as usize;
+        let end = (self.nreloc as usize).checked_mul(RelocationInfo::SIZE)
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= bytes.len())
+            .ok_or_else(|| format!("relocations for {} run past end of file", self.sectname))?;
+        Ok(bytes[start..end]
+            .chunks(RelocationInfo::SIZE)
+            .map(Relocation::from)
+            .collect())
+    }
+}
+
+// A relocation entry, in one of its two layouts. The high bit of the first
+// word (R_SCATTERED) says which.
+#[derive(Debug, PartialEq)]
+pub enum Relocation {
+    Plain(RelocationInfo),
+    Scattered(ScatteredRelocationInfo),
+}
+
+impl Relocation {
+    const R_SCATTERED: u32 = 0x8000_0000;
+
+    pub fn from(bytes: &[u8]) -> Relocation {
+        let first = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
+        if first & Self::R_SCATTERED != 0 {
+            Relocation::Scattered(ScatteredRelocationInfo::from(bytes))
+        } else {
+            Relocation::Plain(RelocationInfo::from(bytes))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RelocationInfo {
+    pub r_address: i32,     /* offset in the section to what is being relocated */
+    pub r_symbolnum: u32,   /* symbol index if r_extern == 1 or section ordinal if r_extern == 0 */
+    pub r_pcrel: bool,      /* was relocated pc relative already */
+    pub r_length: u8,       /* 0=byte, 1=word, 2=long, 3=quad */
+    pub r_extern: bool,     /* does not include value of sym referenced */
+    pub r_type: u8,         /* if not 0, machine specific relocation type */
+}
+
+impl RelocationInfo {
+    const SIZE: usize = 8;
+    pub fn from(bytes: &[u8]) -> RelocationInfo {
+        let r_address = i32::from_ne_bytes(bytes[0..4].try_into().unwrap());
+        let info = u32::from_ne_bytes(bytes[4..8].try_into().unwrap());
+        RelocationInfo {
+            r_address,
+            r_symbolnum: info & 0x00ff_ffff,
+            r_pcrel:     (info >> 24) & 1 != 0,
+            r_length:    ((info >> 25) & 3) as u8,
+            r_extern:    (info >> 27) & 1 != 0,
+            r_type:      (info >> 28) as u8,
         }
     }
 }
 
+// A scattered_relocation_info, used by 32-bit objects to relocate
+// against an address rather than a symbol or section.
+#[derive(Debug, PartialEq)]
+pub struct ScatteredRelocationInfo {
+    pub r_pcrel: bool,      /* was relocated pc relative already */
+    pub r_length: u8,       /* 0=byte, 1=word, 2=long, 3=quad */
+    pub r_type: u8,         /* if not 0, machine specific relocation type */
+    pub r_address: u32,     /* offset in the section to what is being relocated */
+    pub r_value: i32,       /* the value the item to be relocated is refering to */
+}
+
+impl ScatteredRelocationInfo {
+    pub fn from(bytes: &[u8]) -> ScatteredRelocationInfo {
+        let info = u32::from_ne_bytes(bytes[0..4].try_into().unwrap());
+        ScatteredRelocationInfo {
+            r_address: info & 0x00ff_ffff,
+            r_type:    ((info >> 24) & 0xf) as u8,
+            r_length:  ((info >> 28) & 3) as u8,
+            r_pcrel:   (info >> 30) & 1 != 0,
+            r_value:   i32::from_ne_bytes(bytes[4..8].try_into().unwrap()),
+        }
+    }
+}
+
+// An nlist_64 symbol table entry.
+#[derive(Debug)]
+pub struct Symbol {
+    pub n_strx: u32,   /* index into the string table */
+    pub n_type: u8,    /* type flag */
+    pub n_sect: u8,    /* section number or NO_SECT */
+    pub n_desc: u16,   /* see <mach-o/stab.h> */
+    pub n_value: u64,  /* value of this symbol (or stab offset) */
+}
+
+impl Symbol {
+    const SIZE: usize = 16;
+
+    // Ordinals that don't name a loaded dylib.
+    const SELF_LIBRARY_ORDINAL: u8 = 0x00;
+    const EXECUTABLE_ORDINAL: u8 = 0xfe;
+    const DYNAMIC_LOOKUP_ORDINAL: u8 = 0xff;
+
+    pub fn from(bytes: &[u8]) -> Symbol {
+        Symbol {
+            n_strx:  u32::from_ne_bytes(bytes[0..4].try_into().unwrap()),
+            n_type:  bytes[4],
+            n_sect:  bytes[5],
+            n_desc:  u16::from_ne_bytes(bytes[6..8].try_into().unwrap()),
+            n_value: u64::from_ne_bytes(bytes[8..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn is_undefined(&self) -> bool {
+        // N_TYPE bits are N_UNDF, and the symbol isn't a debugging entry.
+        self.n_type & 0xe0 == 0 && self.n_type & 0x0e == 0
+    }
+
+    // Whether the symbol is defined in a section (N_SECT), and so has an
+    // address, rather than being a debugging entry or undefined.
+    pub fn is_section_defined(&self) -> bool {
+        self.n_type & 0xe0 == 0 && self.n_type & 0x0e == 0x0e
+    }
+
+    // The library ordinal in the high byte of n_desc (GET_LIBRARY_ORDINAL).
+    pub fn library_ordinal(&self) -> u8 {
+        (self.n_desc >> 8) as u8
+    }
+
+    // The symbol's name, looked up in the file's string table. An n_strx
+    // of zero means the symbol has no name.
+    pub fn name<'a>(&self, strings: &StringTable<'a>) -> Option<&'a str> {
+        if self.n_strx == 0 {
+            return None;
+        }
+        strings.get(self.n_strx as usize)
+    }
+
+    // The dylib an undefined symbol binds to, for files using two-level
+    // namespace bindings. Returns None for defined symbols, flat namespace
+    // files, and ordinals that refer to the image itself, the main
+    // executable, or dynamic lookup.
+    pub fn library_name<'a>(&self, file: &'a File) -> Option<&'a str> {
+        if !self.is_undefined() || !file.header.flags.contains(Flags::TWO_LEVEL_NS_BINDINGS) {
+            return None;
+        }
+        match self.library_ordinal() {
+            Self::SELF_LIBRARY_ORDINAL | Self::EXECUTABLE_ORDINAL | Self::DYNAMIC_LOOKUP_ORDINAL => None,
+            ordinal => file.dylibs().nth(ordinal as usize - 1),
+        }
+    }
+}
+
+// A block of null-terminated strings, such as the symbol table's, read
+// by byte index.
+#[derive(Clone, Copy, Debug)]
+pub struct StringTable<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StringTable<'a> {
+    pub fn new(bytes: &'a [u8]) -> StringTable<'a> {
+        StringTable { bytes }
+    }
+
+    // The string starting at byte `index`, running to the next null or
+    // the end of the table. None if `index` is out of range or the string
+    // isn't UTF-8.
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        let string = self.bytes.get(index..)?;
+        if string.is_empty() {
+            return None;
+        }
+        let len = string.iter().position(|b| *b == 0).unwrap_or(string.len());
+        std::str::from_utf8(&string[..len]).ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct RawHeader {
     pub magic: u32,
@@ -494,3 +1316,786 @@ impl RawHeader {
         }
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn header_bytes(file_type: u32, loads: &[Vec<u8>]) -> Vec<u8> {
+        let loads_size: usize = loads.iter().map(|l| l.len()).sum();
+        let mut bytes = vec![];
+        for word in [
+            0xfeedfacf,                 // magic
+            0x01000007,                 // cpu_type: x86_64
+            0x03,                       // cpu_subtype: all
+            file_type,
+            loads.len() as u32,
+            loads_size as u32,
+            0,                          // flags
+            0,                          // reserved
+        ] {
+            bytes.extend_from_slice(&u32::to_ne_bytes(word));
+        }
+        for load in loads {
+            bytes.extend_from_slice(load);
+        }
+        bytes
+    }
+
+    fn padded_name(name: &str) -> [u8; 16] {
+        let mut out = [0; 16];
+        out[..name.len()].copy_from_slice(name.as_bytes());
+        out
+    }
+
+    // Builds an LC_SEGMENT_64 command holding sections given as
+    // (sectname, offset, size, reloff, nreloc).
+    pub fn segment64_bytes(segname: &str, sections: &[(&str, u32, u64, u32, u32)]) -> Vec<u8> {
+        let size = 8 + SEGMENT64_SIZE + sections.len() * Section64::SIZE;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&0x19u32.to_ne_bytes());
+        bytes.extend_from_slice(&(size as u32).to_ne_bytes());
+        bytes.extend_from_slice(&padded_name(segname));
+        bytes.extend_from_slice(&0x1000u64.to_ne_bytes()); // vmaddr
+        bytes.extend_from_slice(&0x2000u64.to_ne_bytes()); // vmsize
+        bytes.extend_from_slice(&0u64.to_ne_bytes());      // fileoff
+        bytes.extend_from_slice(&0x2000u64.to_ne_bytes()); // filesize
+        bytes.extend_from_slice(&7u32.to_ne_bytes());      // maxprot
+        bytes.extend_from_slice(&5u32.to_ne_bytes());      // initprot
+        bytes.extend_from_slice(&(sections.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&0u32.to_ne_bytes());      // flags
+        for (sectname, offset, sect_size, reloff, nreloc) in sections {
+            bytes.extend_from_slice(&padded_name(sectname));
+            bytes.extend_from_slice(&padded_name(segname));
+            bytes.extend_from_slice(&0x1000u64.to_ne_bytes()); // addr
+            bytes.extend_from_slice(&sect_size.to_ne_bytes());
+            bytes.extend_from_slice(&offset.to_ne_bytes());
+            bytes.extend_from_slice(&0u32.to_ne_bytes());      // align
+            bytes.extend_from_slice(&reloff.to_ne_bytes());
+            bytes.extend_from_slice(&nreloc.to_ne_bytes());
+            bytes.extend_from_slice(&[0; 16]); // flags through reserved3
+        }
+        assert_eq!(bytes.len(), size);
+        bytes
+    }
+
+    fn raw_header(magic: u32, cpu_type: u32) -> RawHeader {
+        RawHeader {
+            magic,
+            cpu_type,
+            cpu_subtype: 0x03,
+            file_type: 0x01,
+            loads_count: 0,
+            loads_size: 0,
+            flags: 0,
+            reserved: 0,
+        }
+    }
+
+    #[test]
+    fn header_accepts_native_magics() {
+        assert!(!Header::from_header(raw_header(0xfeedface, 0x00000007)).unwrap().is_64_bit);
+        assert!( Header::from_header(raw_header(0xfeedfacf, 0x01000007)).unwrap().is_64_bit);
+    }
+
+    #[test]
+    fn header_reports_swapped_magic_32() {
+        let err = Header::from_header(raw_header(0xcefaedfe, 0x07000000)).unwrap_err();
+        assert_eq!(err, "file is byte-swapped relative to host (magic 0xcefaedfe); endianness support needed");
+    }
+
+    #[test]
+    fn header_reports_swapped_magic_64() {
+        let err = Header::from_header(raw_header(0xcffaedfe, 0x07000001)).unwrap_err();
+        assert_eq!(err, "file is byte-swapped relative to host (magic 0xcffaedfe); endianness support needed");
+    }
+
+    #[test]
+    fn header_reports_arch_magic_mismatch() {
+        let err = Header::from_header(raw_header(0xfeedface, 0x01000007)).unwrap_err();
+        assert_eq!(err, "arch is 64-bit, but magic number is 0xfeedface");
+        let err = Header::from_header(raw_header(0x12345678, 0x00000007)).unwrap_err();
+        assert_eq!(err, "arch is 32-bit, but magic number is 0x12345678");
+    }
+
+    // Builds a load command whose body is an lc_str offset followed by
+    // `string`, padded with nulls to a multiple of 8 bytes.
+    fn lc_str_command(cmd: u32, string: &str) -> Vec<u8> {
+        let mut body = 12u32.to_ne_bytes().to_vec();
+        body.extend_from_slice(string.as_bytes());
+        body.push(0);
+        body.resize(body.len().next_multiple_of(8), 0);
+        let mut bytes = cmd.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[test]
+    fn load_command_rpath() {
+        let bytes = lc_str_command(0x8000001c, "@loader_path/../lib");
+        let size = bytes.len();
+        let (load, read) = LoadCommand::from(&bytes).unwrap();
+        assert_eq!(read, size);
+        match load.details {
+            LoadCommandDetails::Rpath(path) => assert_eq!(path, "@loader_path/../lib"),
+            details => panic!("expected an rpath, got {:?}", details),
+        }
+    }
+
+    #[test]
+    fn load_command_load_dylinker() {
+        let bytes = lc_str_command(0x0e, "/usr/lib/dyld");
+        let (load, _) = LoadCommand::from(&bytes).unwrap();
+        match load.details {
+            LoadCommandDetails::LoadDylinker(path) => assert_eq!(path, "/usr/lib/dyld"),
+            details => panic!("expected a dylinker, got {:?}", details),
+        }
+    }
+
+    #[test]
+    fn load_command_bad_sizes() {
+        let bytes = words(&[0x1234, 0, 0, 0]);
+        assert_eq!(LoadCommand::from(&bytes).unwrap_err(),
+            "load command 0x1234 has size 0B, which is less than its 8B header");
+        let bytes = words(&[0x1234, 24, 0, 0]);
+        assert_eq!(LoadCommand::from(&bytes).unwrap_err(),
+            "load command 0x1234 is 24B, but only 16B remain");
+        let (_, read) = LoadCommand::from(&words(&[0x1234, 16, 0, 0])).unwrap();
+        assert_eq!(read, 16);
+
+        let bytes = header_bytes(0x1, &[words(&[0x1234, 0])]);
+        assert!(File::from(&bytes).is_err());
+    }
+
+    #[test]
+    fn load_command_data_in_code() {
+        let bytes = words(&[0x29, 16, 0x4000, 0x20]);
+        let (load, read) = LoadCommand::from(&bytes).unwrap();
+        assert_eq!(read, 16);
+        assert_eq!(load.details.name(), "LC_DATA_IN_CODE");
+        match load.details {
+            LoadCommandDetails::LinkeditData { cmd, dataoff, datasize } =>
+                assert_eq!((cmd, dataoff, datasize), (0x29, 0x4000, 0x20)),
+            details => panic!("expected linkedit data, got {:?}", details),
+        }
+    }
+
+    #[test]
+    fn load_command_lc_str_out_of_bounds() {
+        let mut bytes = lc_str_command(0x0e, "/usr/lib/dyld");
+        bytes[8..12].copy_from_slice(&0x100u32.to_ne_bytes());
+        let err = LoadCommand::from(&bytes).unwrap_err();
+        assert_eq!(err, "lc_str offset 0x100 is outside its load command");
+
+        // Commands too small for their fields, with the rest of the buffer
+        // after them, which mustn't be read in their place.
+        for (cmd, size) in [(0x8000001c, 8), (0x0e, 11), (0x02, 16), (0x19, 24)] {
+            let mut bytes = lc_str_command(cmd, "/usr/lib/dyld");
+            bytes[4..8].copy_from_slice(&u32::to_ne_bytes(size));
+            bytes.extend_from_slice(&[0; 64]);
+            let err = LoadCommand::from(&bytes).unwrap_err();
+            assert!(err.ends_with("is too short for its lc_str offset") || err.ends_with("is too short for its fields"),
+                "{:#x}: {}", cmd, err);
+        }
+    }
+
+    // Builds an LC_LOAD_DYLIB-style command for `name`, with its timestamp
+    // and versions.
+    pub fn dylib_bytes(cmd: u32, name: &str, current: u32, compat: u32) -> Vec<u8> {
+        let mut body = vec![];
+        for word in [24, 2, current, compat] {
+            body.extend_from_slice(&word.to_ne_bytes());
+        }
+        body.extend_from_slice(name.as_bytes());
+        body.push(0);
+        body.resize(body.len().next_multiple_of(8), 0);
+        let mut bytes = cmd.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&(8 + body.len() as u32).to_ne_bytes());
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+
+    #[test]
+    fn load_command_dylib_versions() {
+        let bytes = dylib_bytes(0x0c, "/usr/lib/libSystem.B.dylib", 0x051f_0302, 0x0001_0000);
+        let (load, read) = LoadCommand::from(&bytes).unwrap();
+        assert_eq!(read, bytes.len());
+        match load.details {
+            LoadCommandDetails::LoadDylib(dylib) => {
+                assert_eq!(dylib.name, "/usr/lib/libSystem.B.dylib");
+                assert_eq!(dylib.timestamp, 2);
+                assert_eq!(format_version(dylib.current_version), "1311.3.2");
+                assert_eq!(format_version(dylib.compatibility_version), "1.0.0");
+            },
+            details => panic!("expected a dylib, got {:?}", details),
+        }
+
+        let (load, _) = LoadCommand::from(&dylib_bytes(0x0d, "@rpath/libfoo.dylib", 0x20100, 0x20000)).unwrap();
+        assert_eq!(load.details.name(), "LC_ID_DYLIB");
+
+        let file = File::from(&header_bytes(0x2, &[
+            dylib_bytes(0x0c, "/usr/lib/libSystem.B.dylib", 0x051f_0302, 0x0001_0000),
+            dylib_bytes(0x0d, "@rpath/libfoo.dylib", 0x20100, 0x20000),
+            dylib_bytes(0x80000018, "/usr/lib/libobjc.A.dylib", 0x00e4_0000, 0x0001_0000),
+        ])).unwrap();
+        assert_eq!(file.linked_dylibs(), vec![
+            ("/usr/lib/libSystem.B.dylib".to_string(), 0x051f_0302, 0x0001_0000),
+            ("/usr/lib/libobjc.A.dylib".to_string(), 0x00e4_0000, 0x0001_0000),
+        ]);
+        assert!(file.to_string().contains(
+            "  /usr/lib/libSystem.B.dylib (compatibility version 1.0.0, current version 1311.3.2)\n"));
+
+        let mut short = 0x0cu32.to_ne_bytes().to_vec();
+        short.extend_from_slice(&16u32.to_ne_bytes());
+        short.extend_from_slice(&[0; 8]);
+        assert_eq!(LoadCommand::from(&short).unwrap_err(), "dylib command of 16B is too short");
+    }
+
+    #[test]
+    fn symbol_library_name() {
+        let loads_size = 24 + 48 + 56 + 56;
+        let symoff = 32 + loads_size as u32;
+        let mut bytes = header_bytes(0x2, &[
+            symtab_bytes(symoff, 5, 0, 0),
+            dylib_bytes(0x0c, "/usr/lib/libSystem.B.dylib", 0, 0),
+            dylib_bytes(0x80000018, "/usr/lib/libobjc.A.dylib", 0, 0),
+            dylib_bytes(0x20, "/usr/lib/libc++.1.dylib", 0, 0),
+        ]);
+        bytes[24..28].copy_from_slice(&Flags::TWO_LEVEL_NS_BINDINGS.bits().to_ne_bytes());
+        bytes.extend(symbol_bytes(0, 0x01, 0, 2 << 8, 0));    // undefined, from dylib 2
+        bytes.extend(symbol_bytes(0, 0x01, 0, 0xff << 8, 0)); // dynamic lookup
+        bytes.extend(symbol_bytes(0, 0x0f, 1, 0, 0x1000));    // defined
+        bytes.extend(symbol_bytes(0, 0x01, 0, 9 << 8, 0));    // ordinal out of range
+        bytes.extend(symbol_bytes(0, 0x01, 0, 3 << 8, 0));    // from the lazy dylib 3
+        let file = File::from(&bytes).unwrap();
+        let symbols = file.symbols(&bytes).unwrap();
+        let names: Vec<Option<&str>> = symbols.iter()
+            .map(|symbol| symbol.library_name(&file))
+            .collect();
+        assert_eq!(names, [Some("/usr/lib/libobjc.A.dylib"), None, None, None, Some("/usr/lib/libc++.1.dylib")]);
+
+        // Without two-level namespace bindings, the ordinals mean nothing.
+        bytes[24..28].copy_from_slice(&0u32.to_ne_bytes());
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(symbols[0].library_name(&file), None);
+    }
+
+    pub fn symtab_bytes(symoff: u32, nsyms: u32, stroff: u32, strsize: u32) -> Vec<u8> {
+        let mut bytes = vec![];
+        for word in [0x02, 24, symoff, nsyms, stroff, strsize] {
+            bytes.extend_from_slice(&u32::to_ne_bytes(word));
+        }
+        bytes
+    }
+
+    pub fn symbol_bytes(n_strx: u32, n_type: u8, n_sect: u8, n_desc: u16, n_value: u64) -> Vec<u8> {
+        let mut bytes = n_strx.to_ne_bytes().to_vec();
+        bytes.push(n_type);
+        bytes.push(n_sect);
+        bytes.extend_from_slice(&n_desc.to_ne_bytes());
+        bytes.extend_from_slice(&n_value.to_ne_bytes());
+        bytes
+    }
+
+    pub fn relocation_bytes(address: i32, symbolnum: u32, pcrel: u32, length: u32, ext: u32, ty: u32) -> Vec<u8> {
+        let info = symbolnum | pcrel << 24 | length << 25 | ext << 27 | ty << 28;
+        let mut bytes = address.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&info.to_ne_bytes());
+        bytes
+    }
+
+    #[test]
+    fn section_relocations() {
+        let mut bytes = vec![0xaa; 4]; // Padding before the relocations.
+        bytes.extend(relocation_bytes(0x10, 3, 0, 3, 1, 0));
+        bytes.extend(relocation_bytes(0x24, 1, 1, 2, 0, 2));
+        bytes.extend(relocation_bytes(0x30, 0xabcdef, 0, 0, 1, 0xf));
+        let mut section = Section64::from(&[0; Section64::SIZE]).unwrap();
+        section.sectname = "__debug_info".to_string();
+        section.reloff = 4;
+        section.nreloc = 3;
+        assert_eq!(section.relocations(&bytes).unwrap(), vec![
+            Relocation::Plain(RelocationInfo {
+                r_address: 0x10, r_symbolnum: 3, r_pcrel: false,
+                r_length: 3, r_extern: true, r_type: 0,
+            }),
+            Relocation::Plain(RelocationInfo {
+                r_address: 0x24, r_symbolnum: 1, r_pcrel: true,
+                r_length: 2, r_extern: false, r_type: 2,
+            }),
+            Relocation::Plain(RelocationInfo {
+                r_address: 0x30, r_symbolnum: 0xabcdef, r_pcrel: false,
+                r_length: 0, r_extern: true, r_type: 0xf,
+            }),
+        ]);
+
+        section.nreloc = 4;
+        assert_eq!(section.relocations(&bytes).unwrap_err(),
+            "relocations for __debug_info run past end of file");
+    }
+
+    #[test]
+    fn scattered_relocation() {
+        // r_scattered, r_pcrel, r_length 2, r_type 4 (SECTDIFF), r_address 0x1c.
+        let info: u32 = 1 << 31 | 1 << 30 | 2 << 28 | 4 << 24 | 0x1c;
+        let mut bytes = info.to_ne_bytes().to_vec();
+        bytes.extend_from_slice(&0x1f20i32.to_ne_bytes());
+        assert_eq!(Relocation::from(&bytes), Relocation::Scattered(ScatteredRelocationInfo {
+            r_pcrel: true, r_length: 2, r_type: 4, r_address: 0x1c, r_value: 0x1f20,
+        }));
+        assert!(matches!(Relocation::from(&relocation_bytes(0x1c, 0, 1, 2, 0, 4)),
+            Relocation::Plain(_)));
+    }
+
+    #[test]
+    fn file_segment_accessors() {
+        let bytes = header_bytes(0x2, &[
+            segment64_bytes("__TEXT", &[("__text", 0, 0, 0, 0)]),
+            symtab_bytes(0, 0, 0, 0),
+            segment64_bytes("__DWARF", &[
+                ("__debug_info", 0, 0, 0, 0),
+                ("__debug_str", 0, 0, 0, 0),
+            ]),
+        ]);
+        let file = File::from(&bytes).unwrap();
+
+        let names: Vec<&str> = file.segments().map(|seg| seg.segname.as_str()).collect();
+        assert_eq!(names, vec!["__TEXT", "__DWARF"]);
+        assert_eq!(file.segment("__DWARF").unwrap().sections.len(), 2);
+        assert!(file.segment("__LINKEDIT").is_none());
+
+        let dwarf = file.segment("__DWARF").unwrap();
+        assert_eq!(dwarf.section("__debug_str").unwrap().sectname, "__debug_str");
+        assert_eq!(dwarf.section("__debug_info").unwrap().sectname, "__debug_info");
+        assert!(dwarf.section("__text").is_none());
+        assert_eq!(file.section("__TEXT", "__text").unwrap().segname, "__TEXT");
+        assert!(file.section("__TEXT", "__debug_info").is_none());
+        assert!(file.section("__DATA", "__data").is_none());
+
+        assert_eq!(file.dwarf_segment().unwrap().segname, "__DWARF");
+        let object = File::from(&header_bytes(0x1, &[
+            segment64_bytes("", &[("__text", 0, 0, 0, 0), ("__debug_info", 0, 0, 0, 0)]),
+        ])).unwrap();
+        assert_eq!(object.dwarf_segment().unwrap().segname, "");
+        let stripped = File::from(&header_bytes(0x2, &[
+            segment64_bytes("__TEXT", &[("__text", 0, 0, 0, 0)]),
+        ])).unwrap();
+        assert!(stripped.dwarf_segment().is_none());
+
+        let symtabs = file.load_commands_of(|details| match details {
+            LoadCommandDetails::SymbolTable { .. } => Some(()),
+            _ => None,
+        });
+        assert_eq!(symtabs.count(), 1);
+    }
+
+    #[test]
+    fn section_contents() {
+        let mut section = Section64::from(&[0; Section64::SIZE]).unwrap();
+        section.sectname = "__debug_str".to_string();
+        section.offset = 2;
+        section.size = 3;
+        assert_eq!(section.contents(b"..abc..").unwrap(), b"abc");
+        section.size = 6;
+        assert_eq!(section.contents(b"..abc..").unwrap_err(),
+            "__debug_str runs past end of file");
+        section.size = u64::MAX;
+        assert_eq!(section.contents(b"..abc..").unwrap_err(),
+            "__debug_str runs past end of file");
+    }
+
+    #[test]
+    fn loads_size_mismatch_names_command() {
+        let mut segment = segment64_bytes("__TEXT", &[("__text", 0, 0, 0, 0)]);
+        segment[4..8].copy_from_slice(&u32::to_ne_bytes(160));
+        let mut bytes = header_bytes(0x2, &[symtab_bytes(0, 0, 0, 0), segment]);
+        bytes.extend_from_slice(&[0; 16]);
+        assert_eq!(File::from(&bytes).unwrap_err(),
+            "expected loads to be 176B, but instead found 184B; drift begins at load command 1 \
+            (LC_SEGMENT_64) at offset 0x38, which is 160B but should be 152B");
+    }
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn header_sizes() {
+        let bytes = header_bytes(0x2, &[]);
+        let (header, size) = Header::from_bytes(&bytes).unwrap();
+        assert!(header.is_64_bit);
+        assert_eq!(size, 32);
+
+        let mut bytes = vec![];
+        for word in [0xfeedface, 0x00000007, 0x03, 0x2, 0, 0, 0] {
+            bytes.extend_from_slice(&u32::to_ne_bytes(word));
+        }
+        let (header, size) = Header::from_bytes(&bytes).unwrap();
+        assert!(!header.is_64_bit);
+        assert_eq!(size, 28);
+
+        assert_eq!(Header::from_bytes(&bytes[..20]).unwrap_err(),
+            "Mach-O header needs at least 28 bytes, but file is 20B");
+    }
+
+    #[test]
+    fn file_32_bit_loads_start_after_28_bytes() {
+        let uuid = words(&[0x1b, 24, 0x01234567, 0x89abcdef, 0x01234567, 0x89abcdef]);
+        let mut bytes = vec![];
+        for word in [0xfeedface, 0x00000007, 0x03, 0x2, 1, uuid.len() as u32, 0] {
+            bytes.extend_from_slice(&u32::to_ne_bytes(word));
+        }
+        bytes.extend_from_slice(&uuid);
+        let file = File::from(&bytes).unwrap();
+        assert!(matches!(file.load_commands[0].details, LoadCommandDetails::Uuid(_)));
+    }
+
+    #[test]
+    fn min_os_from_version_min() {
+        let bytes = header_bytes(0x2, &[words(&[0x25, 16, 0x000e0500, 0x00100000])]);
+        let file = File::from(&bytes).unwrap();
+        assert!(matches!(file.load_commands[0].details,
+            LoadCommandDetails::VersionMin { platform: BuildPlatform::IOS, sdk: 0x00100000, .. }));
+        assert_eq!(file.min_os(), Some((BuildPlatform::IOS, 0x000e0500)));
+        assert_eq!(format_version(file.min_os().unwrap().1), "14.5.0");
+    }
+
+    #[test]
+    fn min_os_prefers_build_version() {
+        let bytes = header_bytes(0x2, &[
+            words(&[0x24, 16, 0x000a0e00, 0x000a0e00]),
+            words(&[0x32, 24, 1, 0x000d0100, 0x000e0000, 0]),
+        ]);
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(file.min_os(), Some((BuildPlatform::MacOS, 0x000d0100)));
+        assert_eq!(format_version(0x000d0100), "13.1.0");
+    }
+
+    #[test]
+    fn format_versions() {
+        assert_eq!(format_version(0x000d0100), "13.1.0");
+        assert_eq!(format_version(0x000a0f06), "10.15.6");
+        assert_eq!(format_version(0x03340100), "820.1.0");
+        assert_eq!(format_version(0), "0.0.0");
+    }
+
+    #[test]
+    fn build_tool_version_display() {
+        assert_eq!(BuildToolVersion { tool: 3, version: 0x03340100 }.to_string(), "ld 820.1.0");
+        assert_eq!(BuildToolVersion { tool: 9, version: 0x00010000 }.to_string(), "tool 0x9 1.0.0");
+    }
+
+    #[test]
+    fn section64_too_short() {
+        assert_eq!(Section64::from(&[0; 40]).unwrap_err(),
+            "section header needs 80 bytes, but only 40 remain");
+    }
+
+    #[test]
+    fn segment_with_truncated_section() {
+        let mut segment = segment64_bytes("__DWARF", &[
+            ("__debug_info", 0, 0, 0, 0),
+            ("__debug_abbrev", 0, 0, 0, 0),
+        ]);
+        // Claim a second section but cut the command off halfway through it.
+        segment.truncate(8 + 64 + 80 + 40);
+        let size = segment.len() as u32;
+        segment[4..8].copy_from_slice(&size.to_ne_bytes());
+        let mut bytes = header_bytes(0x1, &[segment]);
+        bytes.extend_from_slice(&[0; 48]);
+        assert_eq!(File::from(&bytes).unwrap_err(),
+            "section 1 of segment: section header needs 80 bytes, but only 40 remain");
+    }
+
+    #[test]
+    fn file_function_starts() {
+        // A file whose LC_FUNCTION_STARTS data is `data`.
+        let file_bytes = |data: &[u8]| {
+            let dataoff = 32 + (8 + 64 + 80) + 16;
+            let mut function_starts = vec![];
+            for word in [0x26, 16, dataoff, data.len() as u32] {
+                function_starts.extend_from_slice(&u32::to_ne_bytes(word));
+            }
+            let mut bytes = header_bytes(0x2, &[
+                segment64_bytes("__TEXT", &[("__text", 0, 0, 0, 0)]),
+                function_starts,
+            ]);
+            bytes.extend_from_slice(data);
+            bytes
+        };
+        // Deltas 0xf20, 0x30, 0x100, then the terminator and padding.
+        let bytes = file_bytes(&[0xa0, 0x1e, 0x30, 0x80, 0x02, 0x00, 0x00, 0x00]);
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(file.function_starts(&bytes).unwrap(), vec![0x1f20, 0x1f50, 0x2050]);
+
+        // A delta that carries the address past the top of the address space.
+        let bytes = file_bytes(&[0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00]);
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(file.function_starts(&bytes).unwrap_err(),
+            "function start 0x1010 + 0xffffffffffffffff overflows");
+    }
+
+    #[test]
+    fn file_symbols() {
+        let symoff = 32 + 24;
+        let mut bytes = header_bytes(0x1, &[symtab_bytes(symoff, 2, 0, 0)]);
+        bytes.extend(symbol_bytes(1, 0x0f, 1, 0, 0x100000f00));
+        bytes.extend(symbol_bytes(6, 0x01, 0, 0x0100, 0));
+        let file = File::from(&bytes).unwrap();
+        let symbols = file.symbols(&bytes).unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!((symbols[0].n_strx, symbols[0].n_sect, symbols[0].n_value), (1, 1, 0x100000f00));
+        assert_eq!((symbols[1].n_type, symbols[1].n_desc), (0x01, 0x0100));
+    }
+
+    #[test]
+    fn string_table_get() {
+        let strings = StringTable::new(b"\0_main\0_printf\0_tail");
+        assert_eq!(strings.get(0), Some(""));
+        assert_eq!(strings.get(1), Some("_main"));
+        assert_eq!(strings.get(3), Some("ain"));
+        assert_eq!(strings.get(7), Some("_printf"));
+        assert_eq!(strings.get(15), Some("_tail"));
+        assert_eq!(strings.get(20), None);
+        assert_eq!(strings.get(100), None);
+        assert_eq!(StringTable::new(b"\xff\0").get(0), None);
+    }
+
+    #[test]
+    fn symbol_names() {
+        let symoff = 32 + 24;
+        let strs = b"\0_main\0_printf\0";
+        let stroff = symoff + 3 * Symbol::SIZE as u32;
+        let mut bytes = header_bytes(0x1, &[symtab_bytes(symoff, 3, stroff, strs.len() as u32)]);
+        bytes.extend(symbol_bytes(1, 0x0f, 1, 0, 0x100000f00));
+        bytes.extend(symbol_bytes(0, 0x0f, 1, 0, 0x100000f80));
+        bytes.extend(symbol_bytes(99, 0x01, 0, 0, 0));
+        bytes.extend_from_slice(strs);
+        let file = File::from(&bytes).unwrap();
+        let strings = file.string_table(&bytes).unwrap().unwrap();
+        let names: Vec<Option<&str>> = file.symbols(&bytes).unwrap().iter()
+            .map(|symbol| symbol.name(&strings))
+            .collect();
+        assert_eq!(names, [Some("_main"), None, None]);
+
+        bytes.truncate(bytes.len() - 1);
+        assert_eq!(file.string_table(&bytes).unwrap_err(), "string table runs past end of file");
+    }
+
+    #[test]
+    fn symbol_at_stops_at_next_symbol_or_section_end() {
+        // _a and _b split __text, at 0x1000 for 0x20 bytes.
+        let segment = segment64_bytes("__TEXT", &[("__text", 0, 0x20, 0, 0)]);
+        let symoff = (32 + segment.len() + 24) as u32;
+        let strs = b"\0_a\0_b\0";
+        let stroff = symoff + 2 * Symbol::SIZE as u32;
+        let mut bytes = header_bytes(0x1, &[segment, symtab_bytes(symoff, 2, stroff, strs.len() as u32)]);
+        bytes.extend(symbol_bytes(4, 0x0f, 1, 0, 0x1010));
+        bytes.extend(symbol_bytes(1, 0x0f, 1, 0, 0x1000));
+        bytes.extend_from_slice(strs);
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(file.numbered_section(1).map(|sec| sec.sectname.as_str()), Some("__text"));
+        assert!(file.numbered_section(0).is_none() && file.numbered_section(2).is_none());
+
+        let symbol_at = |address| file.symbol_at(&bytes, address).unwrap();
+        assert_eq!(symbol_at(0xfff), None);
+        assert_eq!(symbol_at(0x1000), Some("_a"));
+        assert_eq!(symbol_at(0x100f), Some("_a"));
+        assert_eq!(symbol_at(0x1010), Some("_b"));
+        assert_eq!(symbol_at(0x101f), Some("_b"));
+        assert_eq!(symbol_at(0x1020), None);
+    }
+
+    fn unixthread_bytes(flavor: u32, registers: &[u64]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for word in [0x05, 16 + 8 * registers.len() as u32, flavor, 2 * registers.len() as u32] {
+            bytes.extend_from_slice(&word.to_ne_bytes());
+        }
+        for register in registers {
+            bytes.extend_from_slice(&register.to_ne_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn unixthread_entry_point() {
+        // x86_THREAD_STATE64 has 21 registers, rip being the 17th.
+        let mut registers = [0u64; 21];
+        registers[16] = 0x100000f50;
+        let bytes = header_bytes(0x2, &[unixthread_bytes(4, &registers)]);
+        let file = File::from(&bytes).unwrap();
+        let details = &file.load_commands[0].details;
+        assert_eq!(details.name(), "LC_UNIXTHREAD");
+        let LoadCommandDetails::UnixThread { flavor, registers } = details else {
+            panic!("not a thread command: {:?}", details);
+        };
+        assert_eq!((*flavor, registers.len()), (4, 42));
+        assert_eq!(file.entry_point(), Some(0x100000f50));
+
+        // ARM_THREAD_STATE64 has 34 64-bit words, pc being the 33rd.
+        let mut registers = [0u64; 34];
+        registers[32] = 0x100003f7c;
+        let mut bytes = header_bytes(0x2, &[unixthread_bytes(6, &registers)]);
+        bytes[4..8].copy_from_slice(&0x0100000cu32.to_ne_bytes());
+        bytes[8..12].copy_from_slice(&0u32.to_ne_bytes());
+        assert_eq!(File::from(&bytes).unwrap().entry_point(), Some(0x100003f7c));
+
+        // Flavors that don't fit their CPU have no known PC.
+        let bytes = header_bytes(0x2, &[unixthread_bytes(6, &registers)]);
+        assert_eq!(File::from(&bytes).unwrap().entry_point(), None);
+
+        let mut bytes = unixthread_bytes(4, &[0; 21]);
+        bytes[12..16].copy_from_slice(&100u32.to_ne_bytes());
+        assert_eq!(LoadCommand::from(&bytes).unwrap_err(),
+            "LC_UNIXTHREAD state of 100 words runs past the end of its 184B command");
+    }
+
+    #[test]
+    fn truncated_unixthread() {
+        // A cmdsize that cuts the command off before its flavor and count,
+        // or partway through its state, with more bytes following.
+        let full = unixthread_bytes(4, &[0; 21]);
+        for (size, err) in [
+            (12, "load command 0x5 of 12B is too short for its fields"),
+            (40, "LC_UNIXTHREAD state of 42 words runs past the end of its 40B command"),
+        ] {
+            let mut bytes = full.clone();
+            bytes[4..8].copy_from_slice(&u32::to_ne_bytes(size));
+            assert_eq!(LoadCommand::from(&bytes).unwrap_err(), err);
+            assert_eq!(LoadCommand::from(&bytes[..size as usize]).unwrap_err(), err);
+        }
+    }
+
+    #[test]
+    fn main_entry_point() {
+        let mut main = vec![];
+        for word in [0x80000028, 24] {
+            main.extend_from_slice(&u32::to_ne_bytes(word));
+        }
+        main.extend_from_slice(&0x3f50u64.to_ne_bytes()); // entryoff
+        main.extend_from_slice(&0u64.to_ne_bytes());      // stacksize
+        let mut thread_state = [0u64; 21];
+        thread_state[16] = 0xdead;
+
+        // __TEXT is at 0x1000, from the start of the file.
+        let bytes = header_bytes(0x2, &[
+            segment64_bytes("__TEXT", &[]),
+            unixthread_bytes(4, &thread_state),
+            main.clone(),
+        ]);
+        let file = File::from(&bytes).unwrap();
+        let details = &file.load_commands[2].details;
+        assert_eq!(details.name(), "LC_MAIN");
+        assert_eq!(details.expected_size(), Some(24));
+        assert!(matches!(details, LoadCommandDetails::Main { entryoff: 0x3f50, stacksize: 0 }));
+        assert_eq!(file.entry_point(), Some(0x4f50));
+
+        // Without __TEXT, the offset can't be mapped.
+        let bytes = header_bytes(0x2, &[main.clone()]);
+        assert_eq!(File::from(&bytes).unwrap().entry_point(), None);
+
+        // Nor can one that overflows the address space.
+        main[8..16].copy_from_slice(&u64::MAX.to_ne_bytes());
+        let bytes = header_bytes(0x2, &[segment64_bytes("__TEXT", &[]), main]);
+        assert_eq!(File::from(&bytes).unwrap().entry_point(), None);
+    }
+
+    #[test]
+    fn unrecognized_load_command_warning() {
+        let mut unknown = vec![];
+        for word in [0x7777u32, 16, 0, 0] {
+            unknown.extend_from_slice(&word.to_ne_bytes());
+        }
+        let bytes = header_bytes(0x1, &[symtab_bytes(0, 0, 0, 0), unknown]);
+        let file = File::from(&bytes).unwrap();
+        assert!(matches!(file.load_commands[1].details, LoadCommandDetails::UnrecognizedLoad(0x7777)));
+        assert_eq!(file.warnings, ["skipped unrecognized load command 0x7777 at offset 0x38"]);
+
+        let file = File::from(&header_bytes(0x1, &[symtab_bytes(0, 0, 0, 0)])).unwrap();
+        assert_eq!(file.warnings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn non_utf8_names() {
+        let mut bytes = header_bytes(0x1, &[
+            segment64_bytes("__DWARF", &[("__debug_str", 0, 0, 0, 0)]),
+        ]);
+        bytes[32 + 8 + 2] = 0xff;  // segname
+        bytes[32 + 72 + 2] = 0xfe; // sectname
+        let file = File::from(&bytes).unwrap();
+        let seg = file.segments().next().unwrap();
+        assert_eq!(seg.segname, "__\u{fffd}WARF");
+        assert_eq!(seg.sections[0].sectname, "__\u{fffd}ebug_str");
+        assert_eq!(file.warnings, [
+            "name \"__\u{fffd}WARF\" in segment \"__\u{fffd}WARF\" isn't valid UTF-8",
+            "name \"__\u{fffd}ebug_str\" in segment \"__\u{fffd}WARF\" isn't valid UTF-8",
+        ]);
+    }
+
+    #[test]
+    fn file_type_kinds() {
+        assert!(FileType::DemandPagedExe.is_executable());
+        assert!(!FileType::DemandPagedExe.is_object());
+        assert!(FileType::RelocatableObj.is_object());
+        assert!(!FileType::RelocatableObj.is_executable());
+        assert!(FileType::DynamicallyBoundSharedLib.is_dylib());
+        assert!(!FileType::DynamicallyBoundBundle.is_dylib());
+        assert_eq!(FileType::DemandPagedExe.to_string(), "MH_EXECUTE");
+        assert_eq!(FileType::CompanionDebugOnly.to_string(), "MH_DSYM");
+        assert_eq!(FileType::from(0x06).unwrap().to_string(), "MH_DYLIB");
+    }
+
+    #[test]
+    fn file_display_summary() {
+        let bytes = header_bytes(0x2, &[
+            segment64_bytes("__TEXT", &[("__text", 0, 0x40, 0, 0), ("__cstring", 0, 0x10, 0, 0)]),
+            symtab_bytes(0, 0, 0, 0),
+            segment64_bytes("__LINKEDIT", &[]),
+        ]);
+        let file = File::from(&bytes).unwrap();
+        assert_eq!(file.to_string(), "\
+cpu type:      X86(AllX86)
+file type:     MH_EXECUTE
+64-bit:        yes
+load commands: 3 (328B)
+segments:
+  __TEXT           vmaddr 0x0000000000001000 vmsize 0x00002000 filesize 0x00002000 sections 2
+  __LINKEDIT       vmaddr 0x0000000000001000 vmsize 0x00002000 filesize 0x00002000 sections 0
+");
+    }
+
+    #[test]
+    fn from_path_parses_fixture() {
+        // Header, one segment with one section, then the section data.
+        let data_offset = 32 + 8 + SEGMENT64_SIZE + Section64::SIZE;
+        let mut bytes = header_bytes(0x1, &[
+            segment64_bytes("__DWARF", &[("__debug_str", data_offset as u32, 12, 0, 0)]),
+        ]);
+        bytes.extend_from_slice(b"hello\0world\0");
+        let path = std::env::temp_dir()
+            .join(format!("mecha-dwarf-from-path-{}.o", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let result = File::from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (file, mmap) = result.unwrap();
+        assert_eq!(mmap[..], bytes[..]);
+        assert!(file.header.is_64_bit);
+        assert!(matches!(file.header.file_type, FileType::RelocatableObj));
+        assert_eq!(file.load_commands.len(), 1);
+        match &file.load_commands[0].details {
+            LoadCommandDetails::Segment64(seg) => {
+                assert_eq!(seg.segname, "__DWARF");
+                assert_eq!(seg.sections[0].sectname, "__debug_str");
+            },
+            details => panic!("expected a segment, got {:?}", details),
+        }
+    }
+
+    #[test]
+    fn from_path_reports_missing_file() {
+        let err = File::from_path("/nonexistent/mecha-dwarf").unwrap_err();
+        assert!(err.starts_with("error opening file: "), "{}", err);
+    }
+}
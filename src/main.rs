@@ -1,90 +1,810 @@
-use std::fs::File;
+#![allow(clippy::upper_case_acronyms)]
 
-use memmap::{ Mmap, MmapOptions };
+use std::ops::Deref;
+
+use memmap::Mmap;
 
 mod dwarf;
+#[cfg(feature = "zlib")]
+mod inflate;
 mod leb;
 mod macho;
+mod reader;
+mod symbolize;
 
 struct Config {
-    path: String,
-    verbose: bool,
+    paths: Vec<String>,
+    // -v prints a summary of the Mach-O file; -vv dumps it in full.
+    verbosity: u8,
+    list: bool,
+    stats: bool,
+    line_csv: bool,
+    verify: bool,
+    // Report, on stderr, what the parsers skipped without understanding.
+    warnings: bool,
+    no_mmap: bool,
+    hexdump_len: usize,
+    // The most bytes any one hexdump may show.
+    max_dump_bytes: usize,
+    extract: Option<String>,
+    output: Option<String>,
+    diff: Option<String>,
+    version_min: bool,
+    abbrev_only: bool,
+    // Print nothing but errors, which go to stderr, so only the exit
+    // status says whether the files parsed.
+    quiet: bool,
+    info_offset: Option<usize>,
+    // Print only the unit with this index in __debug_info, counting from 0.
+    cu: Option<usize>,
+    // Print only the DIE at this offset in __debug_info, and its children.
+    die_at: Option<usize>,
+    // Print only the readable strings, like strings(1) but knowing where
+    // each comes from.
+    strings: bool,
+    // Print only the DIEs with these tags, without their children.
+    tags: Vec<dwarf::DIETag>,
 }
 
+// Exit statuses, so scripts can tell failures apart. Anything not
+// covered here, like corrupt DWARF or a failed write, exits with 1.
+const EXIT_FAILURE: i32 = 1;
+const EXIT_BAD_ARGS: i32 = 2;
+const EXIT_NOT_MACHO: i32 = 3;
+const EXIT_NO_DWARF: i32 = 4;
+
+// An exit status and the message explaining it.
+type Failure = (i32, String);
+
 fn main() {
-    let config = parse_config(std::env::args());
-    let mmap: Mmap = mmap_file(config.path)
-        .unwrap_or_else(|e| {
-            println!("{}", e);
-            std::process::exit(1);
-        });
-
-    // Parse the Mach-O file.
-    let macho = macho::File::from(&mmap[..])
-        .unwrap_or_else(|e| {
-            println!("error parsing macho: {}", e);
-            std::process::exit(1);
-        });
-    if config.verbose {
-        println!("{:#x?}", macho);
-    }
-
-    // Get the DWARF segment.
-    let dwarf_seg = macho.load_commands.into_iter()
-        .filter_map(|cmd| {
-            if let macho::LoadCommandDetails::Segment64(seg) = cmd.details {
-                if seg.segname.as_str() == "__DWARF" { return Some(seg); }
-            }
-            None
-        })
-        .next()
-        .unwrap_or_else(|| {
-            println!("error: file has no __DWARF segment");
-            std::process::exit(1);
-        });
-    if config.verbose {
+    if let Err((code, message)) = run(std::env::args()) {
+        eprintln!("{}", message);
+        std::process::exit(code);
+    }
+}
+
+fn run(args: impl IntoIterator<Item = String>) -> Result<(), Failure> {
+    let args: Vec<String> = args.into_iter().collect();
+    if args.get(1).map(String::as_str) == Some("leb") {
+        let output = decode_leb(&args).map_err(|e| (EXIT_BAD_ARGS, e))?;
+        println!("{}", output);
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("symbolize") {
+        print!("{}", symbolize(&args)?);
+        return Ok(());
+    }
+    let config = parse_config(args).map_err(|usage| (EXIT_BAD_ARGS, usage))?;
+    if let [path] = &config.paths[..] {
+        return inspect(&config, path);
+    }
+
+    // Keep going past files that fail, and exit with the first failure's
+    // status.
+    let mut first_failure = None;
+    let mut failures = 0;
+    for path in config.paths.iter() {
+        if !config.quiet {
+            println!("{}:", path);
+        }
+        if let Err((code, message)) = inspect(&config, path) {
+            eprintln!("{}: {}", path, message);
+            first_failure.get_or_insert(code);
+            failures += 1;
+        }
+    }
+    match first_failure {
+        Some(code) => Err((code, format!("{} of {} files failed", failures, config.paths.len()))),
+        None => Ok(()),
+    }
+}
+
+// Does whatever `config` asks for with the file at `path`.
+fn inspect(config: &Config, path: &str) -> Result<(), Failure> {
+    let buffer = read(path, config.no_mmap)?;
+    let report = analyze(&buffer, config)?;
+    print_report(config, &report, &buffer)
+}
+
+// What `analyze` found in one file, for `print_report` to show.
+struct Report {
+    macho: macho::File,
+    // The DWARF, when `config` asks for it. --abbrev-only leaves only
+    // __debug_abbrev parsed.
+    dwarf: Option<dwarf::File>,
+    // The one unit --info-offset or --cu picked out, parsed on its own.
+    unit: Option<dwarf::CompilationUnit>,
+    // What the parsers skipped without understanding, Mach-O first.
+    warnings: Vec<String>,
+    // Set when the file has no DWARF segment. The Mach-O output still
+    // prints, and then `print_report` fails with EXIT_NO_DWARF.
+    no_dwarf: bool,
+}
+
+// Parses as much of `bytes` as `config` needs, printing nothing.
+fn analyze(bytes: &[u8], config: &Config) -> Result<Report, Failure> {
+    let macho = macho::File::from(bytes)
+        .map_err(|e| (EXIT_NOT_MACHO, format!("error parsing macho: {}", e)))?;
+    let mut report = Report { warnings: macho.warnings.clone(), macho, dwarf: None, unit: None, no_dwarf: false };
+    if config.version_min || config.extract.is_some() {
+        return Ok(report);
+    }
+
+    let macho = &report.macho;
+    let Some(dwarf_seg) = macho.dwarf_segment() else {
+        report.no_dwarf = true;
+        return Ok(report);
+    };
+    let dwarf_error = |e| (EXIT_FAILURE, format!("error parsing dwarf: {}", e));
+    if config.abbrev_only {
+        report.dwarf = Some(dwarf::File::abbrevs_only(dwarf_seg, bytes).map_err(dwarf_error)?);
+    } else if let Some(offset) = config.info_offset {
+        report.unit = Some(dwarf::File::unit_at(macho, dwarf_seg, bytes, offset).map_err(dwarf_error)?);
+    } else if let Some(index) = config.cu {
+        report.unit = Some(dwarf::File::nth_unit(macho, dwarf_seg, bytes, index).map_err(dwarf_error)?);
+    } else {
+        let mut dwarf_file = dwarf::File::from(macho, dwarf_seg, bytes).map_err(dwarf_error)?;
+        dwarf_file.hexdump_len = config.hexdump_len;
+        dwarf_file.max_dump_bytes = config.max_dump_bytes;
+        report.warnings.extend(dwarf_file.warnings.iter().cloned());
+        report.dwarf = Some(dwarf_file);
+    }
+    Ok(report)
+}
+
+// Prints what `config` asks for from `report`, which `analyze` made from
+// `bytes`.
+fn print_report(config: &Config, report: &Report, bytes: &[u8]) -> Result<(), Failure> {
+    let macho = &report.macho;
+    if config.warnings && !config.quiet {
+        print_warnings(&report.warnings);
+    }
+    match config.verbosity {
+        _ if config.quiet => {},
+        0 => {},
+        1 => print!("{}", macho),
+        _ => println!("{:#x?}", macho),
+    }
+    if config.list && !config.quiet {
+        print_macho_listing(macho);
+    }
+    if config.version_min {
+        match macho.min_os() {
+            _ if config.quiet => {},
+            Some((platform, version)) =>
+                println!("{:?} {}", platform, macho::format_version(version)),
+            None => println!("no minimum OS version"),
+        }
+        return Ok(());
+    }
+    if let (Some(name), Some(output)) = (&config.extract, &config.output) {
+        return extract_section(macho, bytes, name, output).map_err(|e| (EXIT_FAILURE, e));
+    }
+    if report.no_dwarf {
+        return Err((EXIT_NO_DWARF, "error: file has no __DWARF segment".to_string()));
+    }
+    if let Some(dwarf_seg) = macho.dwarf_segment().filter(|_| config.verbosity >= 2 && !config.quiet) {
         println!("{:#x?}", dwarf_seg);
     }
 
-    // Parse the DWARF and print.
-    let dwarf_file = dwarf::File::from(dwarf_seg, &mmap)
-        .unwrap_or_else(|e| {
-            println!("error parsing dwarf: {}", e);
-            std::process::exit(1);
-        });
+    if let Some(unit) = &report.unit {
+        if !config.quiet {
+            print!("{}", unit);
+        }
+        return Ok(());
+    }
+    let Some(dwarf_file) = &report.dwarf else { return Ok(()) };
+    if config.abbrev_only {
+        if !config.quiet {
+            print!("{}", dwarf_file);
+        }
+        return Ok(());
+    }
+    if config.verify {
+        let problems = dwarf_file.verify();
+        if !config.quiet {
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+        }
+        if !problems.is_empty() {
+            return Err((EXIT_FAILURE, format!("verify found {} problems", problems.len())));
+        }
+        return Ok(());
+    }
+    if let Some(offset) = config.die_at {
+        let die = dwarf_file.die_at_offset(offset)
+            .ok_or_else(|| (EXIT_FAILURE, format!("error: no DIE at offset {:#x} in __debug_info", offset)))?;
+        if !config.quiet {
+            println!("{}", die);
+        }
+        return Ok(());
+    }
+    if config.quiet {
+        return Ok(());
+    }
+    if config.list {
+        println!("DWARF sections:");
+        for (name, size) in dwarf_file.listing() {
+            println!("  {:<16} {:#10x}", name, size);
+        }
+        return Ok(());
+    }
+    if config.stats {
+        print_stats(dwarf_file);
+        return Ok(());
+    }
+    if !config.tags.is_empty() {
+        print!("{}", dies_with_tags(dwarf_file, &config.tags));
+        return Ok(());
+    }
+    if config.strings {
+        print!("{}", strings(dwarf_file));
+        return Ok(());
+    }
+    if config.line_csv {
+        let lines = dwarf_file.line_table()
+            .ok_or((EXIT_NO_DWARF, "error: file has no __debug_line section".to_string()))?;
+        print!("{}", lines.to_csv(dwarf_file.comp_dir()));
+        return Ok(());
+    }
+    if let Some(other_path) = &config.diff {
+        let other = load_dwarf(other_path, config.no_mmap)?;
+        for difference in dwarf_file.diff(&other) {
+            println!("{}", difference);
+        }
+        return Ok(());
+    }
     println!("{}", dwarf_file);
+    Ok(())
+}
+
+fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+}
+
+// Prints how many DIEs have each tag, most common first.
+fn print_stats(dwarf_file: &dwarf::File) {
+    let mut counts: Vec<(dwarf::DIETag, usize)> =
+        dwarf_file.tag_histogram().into_iter().collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    for (tag, count) in counts {
+        println!("{:>8} {}", count, tag);
+    }
+}
+
+// Describes each DIE with one of `tags`, leaving out its children.
+fn dies_with_tags(dwarf_file: &dwarf::File, tags: &[dwarf::DIETag]) -> String {
+    dwarf_file.dies()
+        .filter(|die| tags.contains(&die.tag))
+        .map(|die| die.describe() + "\n")
+        .collect()
+}
+
+// A line for each of the file's readable strings, labeled with where it
+// came from.
+fn strings(dwarf_file: &dwarf::File) -> String {
+    dwarf_file.strings().into_iter()
+        .map(|(source, s)| format!("{:<16} {}\n", source, s))
+        .collect()
+}
+
+fn print_macho_listing(macho: &macho::File) {
+    println!("Mach-O segments:");
+    for seg in macho.segments() {
+        println!("  {:<16} vmaddr {:#018x} offset {:#010x} size {:#010x}",
+            seg.segname, seg.vmaddr, seg.fileoff, seg.filesize);
+        for sec in seg.sections.iter() {
+            println!("    {:<16} addr {:#018x} offset {:#010x} size {:#010x}",
+                sec.sectname, sec.addr, sec.offset, sec.size);
+        }
+    }
+}
+
+// The contents of the input file, either mapped or read into memory.
+enum Buffer {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Mapped(mmap) => mmap,
+            Buffer::Read(bytes) => bytes,
+        }
+    }
+}
+
+// Reads the file at `path`. Mapping fails on things like pipes, so
+// `no_mmap` reads the whole file into memory instead.
+fn read(path: &str, no_mmap: bool) -> Result<Buffer, Failure> {
+    if no_mmap {
+        std::fs::read(path)
+            .map(Buffer::Read)
+            .map_err(|e| format!("error reading file: {}", e))
+    } else {
+        macho::map_file(path).map(Buffer::Mapped)
+    }.map_err(|e| (EXIT_FAILURE, e))
+}
+
+// Loads and parses the Mach-O file at `path`.
+fn load(path: &str, no_mmap: bool) -> Result<(macho::File, Buffer), Failure> {
+    let buffer = read(path, no_mmap)?;
+    let macho = macho::File::from(&buffer)
+        .map_err(|e| (EXIT_NOT_MACHO, format!("error parsing macho: {}", e)))?;
+    Ok((macho, buffer))
 }
 
-fn usage(args: Vec<String>) {
-    println!("usage: {} [-v] FILENAME", args[0]);
+// Loads the file at `path` and parses its DWARF.
+fn load_dwarf(path: &str, no_mmap: bool) -> Result<dwarf::File, Failure> {
+    let (macho, buffer) = load(path, no_mmap)?;
+    let segment = macho.dwarf_segment()
+        .ok_or_else(|| (EXIT_NO_DWARF, format!("error: {} has no __DWARF segment", path)))?;
+    dwarf::File::from(&macho, segment, &buffer)
+        .map_err(|e| (EXIT_FAILURE, format!("error parsing dwarf in {}: {}", path, e)))
 }
 
-fn parse_config(args: std::env::Args) -> Config {
-    let mut args: Vec<String> = args.collect();
+// Writes the raw bytes of the first section named `name` to `output`.
+fn extract_section(
+    macho: &macho::File, bytes: &[u8], name: &str, output: &str
+) -> Result<(), String> {
+    let section = macho.segments()
+        .find_map(|seg| seg.section(name))
+        .ok_or_else(|| format!("error: file has no {} section", name))?;
+    std::fs::write(output, section.contents(bytes)?)
+        .map_err(|e| format!("error writing {}: {}", output, e))
+}
+
+// Runs `fantac leb [--signed|--unsigned] BYTE...`, which decodes the
+// LEB128 number at the start of BYTEs, given in hex. Returns the value and
+// how many bytes it took.
+fn decode_leb(args: &[String]) -> Result<String, String> {
+    let program = args.first().map_or("fantac", String::as_str);
+    let usage = format!("usage: {} leb [--signed|--unsigned] BYTE...", program);
+    let mut signed = false;
+    let mut bytes = vec![];
+    for arg in args.iter().skip(2) {
+        match arg.as_str() {
+            "--signed" => signed = true,
+            "--unsigned" => signed = false,
+            byte => {
+                let hex = byte.strip_prefix("0x").unwrap_or(byte);
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| usage.clone())?);
+            }
+        }
+    }
+    if bytes.is_empty() {
+        return Err(usage);
+    }
+    let (value, size) = if signed {
+        leb::ileb128_decode(&bytes).map(|(n, size)| (n.to_string(), size))
+    } else {
+        leb::uleb128_decode(&bytes).map(|(n, size)| (n.to_string(), size))
+    }.map_err(|e| format!("error: {}", String::from(e)))?;
+    Ok(format!("{} ({} of {} bytes)", value, size, bytes.len()))
+}
+
+// Runs `fantac symbolize FILENAME ADDRESS...`, which prints the function
+// and source line of each ADDRESS, a line each, or ?? for ones nothing in
+// the file covers.
+fn symbolize(args: &[String]) -> Result<String, Failure> {
+    let program = args.first().map_or("fantac", String::as_str);
+    let usage = || (EXIT_BAD_ARGS, format!("usage: {} symbolize FILENAME ADDRESS...", program));
+    let [_, _, path, addresses @ ..] = args else { return Err(usage()) };
+    let addresses = addresses.iter()
+        .map(|address| parse_offset(address).map(|a| a as u64))
+        .collect::<Option<Vec<u64>>>()
+        .filter(|addresses| !addresses.is_empty())
+        .ok_or_else(usage)?;
+    let (macho, buffer) = load(path, false)?;
+    let symbolizer = symbolize::Symbolizer::new(&macho, &buffer)
+        .map_err(|e| (EXIT_FAILURE, format!("error parsing dwarf: {}", e)))?;
+    Ok(addresses.into_iter()
+        .map(|address| match symbolizer.symbolize(address) {
+            Some(symbolication) => format!("{}\n", symbolication),
+            None => "??\n".to_string(),
+        })
+        .collect())
+}
+
+fn usage(program: &str) -> String {
+    format!("usage: {} [-v|-vv] [-q|--quiet] [--list] [--stats] [--strings] [--line-csv] [--verify] [--warnings] [--version-min] [--abbrev-only] [--no-mmap] [--hexdump-len N] [--max-dump-bytes N] [--info-offset N] [--cu N] [--die-at N] [--tag TAG]... [--extract SECTION -o OUTPUT] [--diff OTHER] FILENAME...", program)
+}
+
+// Parses a decimal or 0x-prefixed hex offset.
+fn parse_offset(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// Parses the command line, or returns the usage message if it's invalid.
+fn parse_config(args: impl IntoIterator<Item = String>) -> Result<Config, String> {
+    let mut args: Vec<String> = args.into_iter().collect();
+    let program = args.first().cloned().unwrap_or("fantac".to_string());
     let mut config = Config {
-        path: String::from(""),
-        verbose: false,
+        paths: vec![],
+        verbosity: 0,
+        list: false,
+        stats: false,
+        line_csv: false,
+        verify: false,
+        warnings: false,
+        no_mmap: false,
+        hexdump_len: 0,
+        max_dump_bytes: dwarf::DEFAULT_MAX_DUMP_BYTES,
+        extract: None,
+        output: None,
+        diff: None,
+        version_min: false,
+        abbrev_only: false,
+        quiet: false,
+        info_offset: None,
+        cu: None,
+        die_at: None,
+        strings: false,
+        tags: vec![],
     };
-    for i in 1..args.len() {
-        if args[i] == "-v" {
-            config.verbose = true;
-            args.swap_remove(i);
-            break;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-v" => {
+                config.verbosity += 1;
+                args.remove(i);
+            }
+            "-vv" => {
+                config.verbosity += 2;
+                args.remove(i);
+            }
+            "--list" => {
+                config.list = true;
+                args.remove(i);
+            }
+            "-q" | "--quiet" => {
+                config.quiet = true;
+                args.remove(i);
+            }
+            "--stats" => {
+                config.stats = true;
+                args.remove(i);
+            }
+            "--verify" => {
+                config.verify = true;
+                args.remove(i);
+            }
+            "--warnings" => {
+                config.warnings = true;
+                args.remove(i);
+            }
+            "--strings" => {
+                config.strings = true;
+                args.remove(i);
+            }
+            "--line-csv" => {
+                config.line_csv = true;
+                args.remove(i);
+            }
+            "--version-min" => {
+                config.version_min = true;
+                args.remove(i);
+            }
+            "--abbrev-only" => {
+                config.abbrev_only = true;
+                args.remove(i);
+            }
+            "--no-mmap" => {
+                config.no_mmap = true;
+                args.remove(i);
+            }
+            "--hexdump-len" => {
+                config.hexdump_len = match args.get(i+1).map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => return Err(usage(&program)),
+                };
+                args.drain(i..i+2);
+            }
+            "--max-dump-bytes" => {
+                config.max_dump_bytes = match args.get(i+1).map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => return Err(usage(&program)),
+                };
+                args.drain(i..i+2);
+            }
+            "--info-offset" => {
+                config.info_offset = match args.get(i+1).map(|n| parse_offset(n)) {
+                    Some(Some(n)) => Some(n),
+                    _ => return Err(usage(&program)),
+                };
+                args.drain(i..i+2);
+            }
+            "--die-at" => {
+                config.die_at = match args.get(i+1).map(|n| parse_offset(n)) {
+                    Some(Some(n)) => Some(n),
+                    _ => return Err(usage(&program)),
+                };
+                args.drain(i..i+2);
+            }
+            "--cu" => {
+                config.cu = match args.get(i+1).map(|n| n.parse()) {
+                    Some(Ok(n)) => Some(n),
+                    _ => return Err(usage(&program)),
+                };
+                args.drain(i..i+2);
+            }
+            "--tag" => {
+                let tag = args.get(i+1).and_then(|name| dwarf::DIETag::from_name(name));
+                config.tags.push(tag.ok_or_else(|| usage(&program))?);
+                args.drain(i..i+2);
+            }
+            "--extract" => {
+                config.extract = Some(args.get(i+1).ok_or_else(|| usage(&program))?.clone());
+                args.drain(i..i+2);
+            }
+            "--diff" => {
+                config.diff = Some(args.get(i+1).ok_or_else(|| usage(&program))?.clone());
+                args.drain(i..i+2);
+            }
+            "-o" => {
+                config.output = Some(args.get(i+1).ok_or_else(|| usage(&program))?.clone());
+                args.drain(i..i+2);
+            }
+            _ => i += 1,
         }
     }
-    if args.len() != 2 {
-        usage(args);
-        std::process::exit(1);
+    if args.len() < 2 || config.extract.is_some() != config.output.is_some() {
+        return Err(usage(&program));
     }
-    config.path = args.swap_remove(1);
-    config
+    config.paths = args.split_off(1);
+    Ok(config)
 }
 
-fn mmap_file(path: String) -> Result<Mmap, String> {
-    let file = File::open(path)
-        .map_err(|e| format!("error opening file: {}", e))?;
-    unsafe {
-        MmapOptions::new().map(&file)
-            .map_err(|e| format!("error mmaping file: {}", e))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macho::tests::*;
+
+    #[test]
+    fn load_with_and_without_mmap() {
+        let bytes = dwarf::tests::macho_with_dwarf(&dwarf::tests::sample_sections());
+        let path = std::env::temp_dir()
+            .join(format!("mecha-dwarf-load-{}.o", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let mapped = load(path.to_str().unwrap(), false);
+        let read = load(path.to_str().unwrap(), true);
+        std::fs::remove_file(&path).unwrap();
+
+        let outputs: Vec<String> = [mapped, read].into_iter()
+            .map(|loaded| {
+                let (macho, buffer) = loaded.unwrap();
+                assert!(buffer[..] == bytes[..]);
+                let segment = macho.segment("__DWARF").unwrap();
+                let dwarf_file = dwarf::File::from(&macho, segment, &buffer).unwrap();
+                format!("{:?}\n{}", macho, dwarf_file)
+            })
+            .collect();
+        assert!(outputs[0].contains("DW_TAG_Subprogram"));
+        assert_eq!(outputs[0], outputs[1]);
+    }
+
+    #[test]
+    fn analyze_sample() {
+        let bytes = dwarf::tests::macho_with_dwarf(&dwarf::tests::sample_sections());
+        let report = analyze(&bytes, &parse_config_ok(args(&["fantac", "a.o"]))).unwrap();
+        assert!(report.macho.segment("__DWARF").is_some());
+        assert_eq!(report.warnings, Vec::<String>::new());
+        assert!(report.unit.is_none());
+        let dwarf_file = report.dwarf.unwrap();
+        assert_eq!(dwarf_file.units().count(), 1);
+        assert_eq!(dies_with_tags(&dwarf_file, &[dwarf::DIETag::Subprogram]).lines().next(),
+            Some("DW_TAG_Subprogram"));
+
+        let report = analyze(&bytes, &parse_config_ok(args(&["fantac", "--cu", "0", "a.o"]))).unwrap();
+        assert!(report.dwarf.is_none());
+        assert_eq!(report.unit.unwrap().offset, 0);
+        let report = analyze(&bytes, &parse_config_ok(args(&["fantac", "--version-min", "a.o"]))).unwrap();
+        assert!(report.dwarf.is_none() && report.unit.is_none());
+
+        let no_dwarf = header_bytes(0x1, &[symtab_bytes(0, 0, 0, 0)]);
+        let config = parse_config_ok(args(&["fantac", "a.o"]));
+        let report = analyze(&no_dwarf, &config).unwrap();
+        assert!(report.no_dwarf && report.dwarf.is_none());
+        // The Mach-O output comes first; only then does the missing DWARF
+        // fail.
+        assert_eq!(print_report(&parse_config_ok(args(&["fantac", "-q", "-v", "a.o"])), &report, &no_dwarf).err(),
+            Some((EXIT_NO_DWARF, "error: file has no __DWARF segment".to_string())));
+        assert_eq!(analyze(b"#!/bin/sh\n", &config).err().map(|(code, _)| code), Some(EXIT_NOT_MACHO));
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parse_config_ok(args: Vec<String>) -> Config {
+        parse_config(args).unwrap_or_else(|usage| panic!("{}", usage))
+    }
+
+    #[test]
+    fn parse_config_rejects_bad_args() {
+        for bad in [
+            &["fantac"][..],
+            &["fantac", "--hexdump-len", "lots", "a.o"],
+            &["fantac", "--info-offset"],
+            &["fantac", "--cu", "-1", "a.o"],
+            &["fantac", "--die-at", "main", "a.o"],
+            &["fantac", "--max-dump-bytes", "a.o"],
+            &["fantac", "-o", "out.bin", "a.o"],
+            &["fantac", "a.o", "--extract"],
+            &["fantac", "--extract", "__debug_str", "a.o", "-o"],
+            &["fantac", "a.o", "--diff"],
+        ] {
+            let usage = parse_config(args(bad)).err().unwrap();
+            assert!(usage.starts_with("usage: fantac "), "{}", usage);
+        }
+        for bad in [&["fantac", "--hexdump-len"][..], &["fantac", "a.o", "--diff"]] {
+            let (code, message) = run(args(bad)).unwrap_err();
+            assert_eq!(code, EXIT_BAD_ARGS);
+            assert!(message.starts_with("usage:"));
+        }
+    }
+
+    #[test]
+    fn run_exit_codes() {
+        let dir = std::env::temp_dir();
+        let not_macho = dir.join(format!("mecha-dwarf-not-macho-{}", std::process::id()));
+        std::fs::write(&not_macho, b"#!/bin/sh\necho hello, world\n").unwrap();
+        let no_dwarf = dir.join(format!("mecha-dwarf-no-dwarf-{}.o", std::process::id()));
+        std::fs::write(&no_dwarf, header_bytes(0x1, &[symtab_bytes(0, 0, 0, 0)])).unwrap();
+        let code = |path: &std::path::Path| {
+            run(args(&["fantac", "-q", path.to_str().unwrap()])).err().map(|(code, _)| code)
+        };
+        let codes = [code(&not_macho), code(&no_dwarf), code(&dir.join("mecha-dwarf-missing.o"))];
+        std::fs::remove_file(&not_macho).unwrap();
+        std::fs::remove_file(&no_dwarf).unwrap();
+        assert_eq!(codes, [Some(EXIT_NOT_MACHO), Some(EXIT_NO_DWARF), Some(EXIT_FAILURE)]);
+    }
+
+    #[test]
+    fn leb_subcommand() {
+        // The examples from Wikipedia's LEB128 article.
+        assert_eq!(decode_leb(&args(&["fantac", "leb", "--unsigned", "0xe5", "0x8e", "0x26"])),
+            Ok("624485 (3 of 3 bytes)".to_string()));
+        assert_eq!(decode_leb(&args(&["fantac", "leb", "--signed", "c0", "bb", "78", "00"])),
+            Ok("-123456 (3 of 4 bytes)".to_string()));
+        // Padding past 64 bits adds nothing.
+        let mut overlong = vec!["fantac", "leb", "0x81"];
+        overlong.extend(["0x80"; 10]);
+        overlong.push("0x00");
+        assert_eq!(decode_leb(&args(&overlong)), Ok("1 (12 of 12 bytes)".to_string()));
+        assert_eq!(decode_leb(&args(&["fantac", "leb", "0x80"])),
+            Err("error: last byte in LEB has continue bit set".to_string()));
+        assert_eq!(decode_leb(&args(&["fantac", "leb", "0x100"])),
+            Err("usage: fantac leb [--signed|--unsigned] BYTE...".to_string()));
+        assert_eq!(run(args(&["fantac", "leb"])).unwrap_err().0, EXIT_BAD_ARGS);
+    }
+
+    #[test]
+    fn symbolize_subcommand() {
+        let bytes = dwarf::tests::macho_with_dwarf(&dwarf::tests::sample_sections());
+        let path = std::env::temp_dir()
+            .join(format!("mecha-dwarf-symbolize-{}.o", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let path = path.to_str().unwrap();
+        let output = symbolize(&args(&["fantac", "symbolize", path, "0x100003f20", "4096"]));
+        let bad = symbolize(&args(&["fantac", "symbolize", path, "main"]));
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(output, Ok("main\n??\n".to_string()));
+        assert_eq!(bad, Err((EXIT_BAD_ARGS, "usage: fantac symbolize FILENAME ADDRESS...".to_string())));
+        assert_eq!(run(args(&["fantac", "symbolize", path])).unwrap_err().0, EXIT_BAD_ARGS);
+    }
+
+    #[test]
+    fn filter_by_tag() {
+        let bytes = dwarf::tests::macho_with_dwarf(&dwarf::tests::sample_sections());
+        let macho = macho::File::from(&bytes).unwrap();
+        let dwarf_file = dwarf::File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+
+        let output = dies_with_tags(&dwarf_file, &[dwarf::DIETag::Subprogram]);
+        assert!(output.starts_with("DW_TAG_Subprogram\n"), "{}", output);
+        assert!(output.contains("\"main\""), "{}", output);
+        assert!(!output.contains("DW_TAG_CompileUnit"), "{}", output);
+        assert!(!output.contains("main.c"), "{}", output);
+        assert_eq!(dies_with_tags(&dwarf_file, &[dwarf::DIETag::BaseType]), "");
+
+        let config = parse_config_ok(args(&["fantac", "--tag", "subprogram", "a.o", "--tag", "variable"]));
+        assert_eq!(config.tags, [dwarf::DIETag::Subprogram, dwarf::DIETag::Variable]);
+        assert_eq!(config.paths, ["a.o"]);
+        assert!(parse_config(args(&["fantac", "--tag", "nonsense", "a.o"])).is_err());
+    }
+
+    #[test]
+    fn strings_only() {
+        let bytes = dwarf::tests::macho_with_dwarf(&dwarf::tests::sample_sections());
+        let macho = macho::File::from(&bytes).unwrap();
+        let dwarf_file = dwarf::File::from(&macho, macho.segment("__DWARF").unwrap(), &bytes).unwrap();
+
+        let output = strings(&dwarf_file);
+        assert!(output.contains("DW_AT_producer   clang version 17\n"), "{}", output);
+        assert!(output.contains("DW_AT_comp_dir   /tmp/src\n"), "{}", output);
+        assert!(output.contains("DW_AT_name       main\n"), "{}", output);
+        assert!(output.ends_with("__debug_str      /tmp/src\n__debug_str      main\n"), "{}", output);
+        assert!(!output.contains("DW_TAG"), "{}", output);
+        assert_eq!(output.lines().count(), 8);
+
+        assert!(parse_config_ok(args(&["fantac", "--strings", "a.o"])).strings);
+    }
+
+    #[test]
+    fn parse_config_paths() {
+        let config = parse_config_ok(args(&["fantac", "a.o"]));
+        assert_eq!(config.paths, ["a.o"]);
+        assert_eq!(config.verbosity, 0);
+
+        let config = parse_config_ok(args(&["fantac", "a.o", "-v", "b.o", "c.o"]));
+        assert_eq!(config.paths, ["a.o", "b.o", "c.o"]);
+        assert_eq!(config.verbosity, 1);
+
+        let config = parse_config_ok(args(&["fantac", "-vv", "--stats", "a.o", "--line-csv", "b.o"]));
+        assert_eq!(config.paths, ["a.o", "b.o"]);
+        assert_eq!(config.verbosity, 2);
+        assert!(config.stats);
+        assert!(config.line_csv);
+        assert!(!config.verify);
+        assert!(parse_config_ok(args(&["fantac", "--verify", "a.o"])).verify);
+        assert!(!config.warnings);
+        assert!(parse_config_ok(args(&["fantac", "a.o", "--warnings"])).warnings);
+        assert_eq!(parse_config_ok(args(&["fantac", "--cu", "1", "a.o"])).cu, Some(1));
+        assert_eq!(config.cu, None);
+        assert_eq!(parse_config_ok(args(&["fantac", "--die-at", "0x2b", "a.o"])).die_at, Some(0x2b));
+        assert_eq!(config.die_at, None);
+        assert_eq!(config.max_dump_bytes, 256);
+        assert_eq!(parse_config_ok(args(&["fantac", "--max-dump-bytes", "64", "a.o"])).max_dump_bytes, 64);
+    }
+
+    #[test]
+    fn parse_config_quiet() {
+        assert!(!parse_config_ok(args(&["fantac", "a.o"])).quiet);
+        let config = parse_config_ok(args(&["fantac", "--quiet", "a.o"]));
+        assert!(config.quiet);
+        assert_eq!(config.paths, ["a.o"]);
+        assert!(parse_config_ok(args(&["fantac", "a.o", "-q"])).quiet);
+    }
+
+    #[test]
+    fn parse_offsets() {
+        assert_eq!(parse_offset("42"), Some(42));
+        assert_eq!(parse_offset("0x2a"), Some(42));
+        assert_eq!(parse_offset("0xzz"), None);
+        assert_eq!(parse_offset(""), None);
+    }
+
+    #[test]
+    fn extract_section_writes_bytes() {
+        let data_offset = 32 + 8 + 64 + 2 * 80;
+        let mut bytes = header_bytes(0x1, &[
+            segment64_bytes("__DWARF", &[
+                ("__debug_abbrev", data_offset, 4, 0, 0),
+                ("__debug_str", data_offset + 4, 8, 0, 0),
+            ]),
+        ]);
+        bytes.extend_from_slice(b"\x01\x11\x00\x00main\0ab\0");
+        let macho = macho::File::from(&bytes).unwrap();
+
+        let path = std::env::temp_dir()
+            .join(format!("mecha-dwarf-extract-{}.bin", std::process::id()));
+        let output = path.to_str().unwrap();
+        extract_section(&macho, &bytes, "__debug_str", output).unwrap();
+        let extracted = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(extracted, b"main\0ab\0");
+
+        assert_eq!(extract_section(&macho, &bytes, "__debug_info", output).unwrap_err(),
+            "error: file has no __debug_info section");
     }
 }
@@ -0,0 +1,195 @@
+// A cursor over a byte slice for reading binary formats.
+//
+// Each read checks that enough bytes remain, advances past what it read,
+// and returns an error rather than panicking when the input runs out.
+
+#![allow(dead_code)]
+
+use crate::leb::*;
+
+use std::ffi::CStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Endian {
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Endian = Endian::Little;
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Endian = Endian::Big;
+}
+
+#[derive(Clone, Debug)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    endian: Endian,
+}
+
+impl<'a> Reader<'a> {
+    // Creates a reader over `bytes` that reads in host byte order.
+    pub fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Self::with_endian(bytes, Endian::NATIVE)
+    }
+
+    pub fn with_endian(bytes: &'a [u8], endian: Endian) -> Reader<'a> {
+        Reader { bytes, pos: 0, endian }
+    }
+
+    // The number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    // The bytes that haven't been read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<(), String> {
+        self.bytes(n).map(|_| ())
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() - self.pos < n {
+            return Err(format!("ran out of bytes reading {} bytes at offset {:#x}", n, self.pos));
+        }
+        let out = &self.bytes[self.pos .. self.pos+n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.array()?;
+        Ok(match self.endian {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big    => u16::from_be_bytes(bytes),
+        })
+    }
+
+    // Three bytes, as in DW_FORM_strx3 and addrx3.
+    pub fn u24(&mut self) -> Result<u32, String> {
+        let bytes: [u8; 3] = self.array()?;
+        Ok(match self.endian {
+            Endian::Little => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]),
+            Endian::Big    => u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
+        })
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.array()?;
+        Ok(match self.endian {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big    => u32::from_be_bytes(bytes),
+        })
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.array()?;
+        Ok(match self.endian {
+            Endian::Little => u64::from_le_bytes(bytes),
+            Endian::Big    => u64::from_be_bytes(bytes),
+        })
+    }
+
+    pub fn uleb(&mut self) -> Result<u64, String> {
+        if self.is_empty() {
+            return Err(format!("ran out of bytes reading ULEB128 at offset {:#x}", self.pos));
+        }
+        let (value, size) = uleb128_decode(self.rest())?;
+        self.pos += size;
+        Ok(value)
+    }
+
+    pub fn ileb(&mut self) -> Result<i64, String> {
+        if self.is_empty() {
+            return Err(format!("ran out of bytes reading SLEB128 at offset {:#x}", self.pos));
+        }
+        let (value, size) = ileb128_decode(self.rest())?;
+        self.pos += size;
+        Ok(value)
+    }
+
+    // Reads a null-terminated UTF-8 string, consuming the null.
+    pub fn cstr(&mut self) -> Result<&'a str, String> {
+        let s = CStr::from_bytes_until_nul(self.rest())
+            .map_err(|_| format!("unterminated string at offset {:#x}", self.pos))?
+            .to_str()
+            .map_err(|e| e.to_string())?;
+        self.pos += s.len() + 1;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_fixed_width_little_endian() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+                     0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f];
+        let mut reader = Reader::with_endian(&bytes, Endian::Little);
+        assert_eq!(reader.u8(), Ok(0x01));
+        assert_eq!(reader.u16(), Ok(0x0302));
+        assert_eq!(reader.u32(), Ok(0x07060504));
+        assert_eq!(reader.u64(), Ok(0x0f0e0d0c0b0a0908));
+        assert_eq!(reader.position(), 15);
+        assert!(reader.is_empty());
+        assert_eq!(Reader::with_endian(&bytes, Endian::Little).u24(), Ok(0x030201));
+    }
+
+    #[test]
+    fn reads_fixed_width_big_endian() {
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09];
+        let mut reader = Reader::with_endian(&bytes, Endian::Big);
+        assert_eq!(reader.u16(), Ok(0x0102));
+        assert_eq!(reader.u32(), Ok(0x03040506));
+        assert_eq!(reader.u24(), Ok(0x070809));
+    }
+
+    #[test]
+    fn reads_lebs() {
+        let bytes = [0xe5, 0x8e, 0x26, 0xc0, 0xbb, 0x78, 0x02];
+        let mut reader = Reader::new(&bytes);
+        assert_eq!(reader.uleb(), Ok(624485));
+        assert_eq!(reader.ileb(), Ok(-123456));
+        assert_eq!(reader.uleb(), Ok(2));
+        assert_eq!(reader.uleb(), Err("ran out of bytes reading ULEB128 at offset 0x7".to_string()));
+        assert_eq!(reader.ileb(), Err("ran out of bytes reading SLEB128 at offset 0x7".to_string()));
+    }
+
+    #[test]
+    fn reads_bytes_and_cstrs() {
+        let mut reader = Reader::new(b"abc\0de\0fg");
+        assert_eq!(reader.cstr(), Ok("abc"));
+        assert_eq!(reader.bytes(2), Ok(&b"de"[..]));
+        reader.skip(1).unwrap();
+        assert_eq!(reader.rest(), b"fg");
+        assert_eq!(reader.cstr(), Err("unterminated string at offset 0x7".to_string()));
+    }
+
+    #[test]
+    fn errors_without_advancing_past_end() {
+        let mut reader = Reader::new(&[0x01, 0x02, 0x03]);
+        assert_eq!(reader.u32(), Err("ran out of bytes reading 4 bytes at offset 0x0".to_string()));
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.u16(), Ok(u16::from_ne_bytes([0x01, 0x02])));
+        assert_eq!(reader.u16(), Err("ran out of bytes reading 2 bytes at offset 0x2".to_string()));
+    }
+}
@@ -0,0 +1,126 @@
+// Maps addresses back to the function and source line they belong to,
+// using the DWARF where the file has it and the symbol table otherwise.
+
+use std::fmt::{Display, Formatter};
+
+use crate::dwarf;
+use crate::macho;
+
+#[derive(Debug, PartialEq)]
+pub struct Symbolication {
+    pub function: Option<String>,
+    // The source file and line, from __debug_line.
+    pub location: Option<(String, u64)>,
+}
+
+impl Display for Symbolication {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.function.as_deref().unwrap_or("??"))?;
+        if let Some((file, line)) = &self.location {
+            write!(f, " at {}:{}", file, line)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Symbolizer<'a> {
+    macho: &'a macho::File,
+    bytes: &'a [u8],
+    dwarf: Option<dwarf::File>,
+}
+
+impl<'a> Symbolizer<'a> {
+    // Parses the file's DWARF up front, if it has any.
+    pub fn new(macho: &'a macho::File, bytes: &'a [u8]) -> Result<Symbolizer<'a>, String> {
+        let dwarf = macho.dwarf_segment()
+            .map(|segment| dwarf::File::from(macho, segment, bytes))
+            .transpose()?;
+        Ok(Symbolizer { macho, bytes, dwarf })
+    }
+
+    // What's known about `address`, or None if nothing covers it.
+    pub fn symbolize(&self, address: u64) -> Option<Symbolication> {
+        let subprogram = self.dwarf.as_ref().and_then(|dwarf| dwarf.subprogram_at(address));
+        let function = subprogram
+            .and_then(|(unit, die)| die.resolved_attr(unit, &dwarf::AttrName::Name))
+            .and_then(dwarf::AttrValue::as_str)
+            // A broken symbol table shouldn't stop the lookup; it just
+            // leaves nothing to fall back on.
+            .or_else(|| self.macho.symbol_at(self.bytes, address).ok().flatten())
+            .map(str::to_string);
+        let location = self.dwarf.as_ref()
+            .and_then(|dwarf| {
+                let lines = dwarf.line_table()?;
+                let row = lines.row_for(address)?;
+                let comp_dir = subprogram.map_or_else(|| dwarf.comp_dir(), |(unit, _)| unit.comp_dir());
+                let path = lines.file_path(row.file, comp_dir)?;
+                Some((path.to_string_lossy().into_owned(), row.line))
+            });
+        if function.is_none() && location.is_none() {
+            return None;
+        }
+        Some(Symbolication { function, location })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::tests::{debug_line_bytes, sample_sections};
+    use crate::macho::tests::*;
+
+    // sample_sections' main, from 0x100003f20 to 0x100003f40, with line
+    // info, followed by a symbol, _helper, that has no DWARF. Both are in
+    // __text, which ends at 0x100003f80.
+    fn fixture() -> Vec<u8> {
+        let mut program = vec![0x00, 9, 0x02]; // DW_LNE_set_address 0x100003f20
+        program.extend_from_slice(&0x100003f20u64.to_ne_bytes());
+        program.extend_from_slice(&[
+            0x01,                               // DW_LNS_copy: line 1
+            0x4c,                               // special: address += 4, line += 2
+            0x02, 0x1c,                         // DW_LNS_advance_pc 0x1c
+            0x00, 1, 0x01,                      // DW_LNE_end_sequence
+        ]);
+        let mut sections = sample_sections();
+        sections.push(("__debug_line", debug_line_bytes(&program)));
+        let strs = b"\0_helper\0";
+
+        let text = segment64_bytes("__TEXT", &[("__text", 0, 0x80, 0, 0)]);
+        let mut offset = 32 + text.len() + 8 + 64 + 80 * sections.len() + 24;
+        let mut specs = vec![];
+        for (name, contents) in sections.iter() {
+            specs.push((*name, offset as u32, contents.len() as u64, 0, 0));
+            offset += contents.len();
+        }
+        let symoff = offset as u32;
+        let stroff = symoff + 16;
+        let mut bytes = header_bytes(0x2, &[
+            text,
+            segment64_bytes("__DWARF", &specs),
+            symtab_bytes(symoff, 1, stroff, strs.len() as u32),
+        ]);
+        // segment64_bytes puts every section at 0x1000; move __text.
+        bytes[32 + 72 + 32..32 + 72 + 40].copy_from_slice(&0x100003f00u64.to_ne_bytes());
+        for (_, contents) in sections.iter() {
+            bytes.extend_from_slice(contents);
+        }
+        bytes.extend(symbol_bytes(1, 0x0f, 1, 0, 0x100003f40));
+        bytes.extend_from_slice(strs);
+        bytes
+    }
+
+    #[test]
+    fn symbolize_one_function() {
+        let bytes = fixture();
+        let macho = macho::File::from(&bytes).unwrap();
+        let symbolizer = Symbolizer::new(&macho, &bytes).unwrap();
+
+        let symbolicate = |address| symbolizer.symbolize(address).map(|s| s.to_string());
+        assert_eq!(symbolicate(0x100003f20).as_deref(), Some("main at /tmp/src/main.c:1"));
+        assert_eq!(symbolicate(0x100003f24).as_deref(), Some("main at /tmp/src/main.c:3"));
+        assert_eq!(symbolicate(0x100003f3f).as_deref(), Some("main at /tmp/src/main.c:3"));
+        assert_eq!(symbolicate(0x100003f48).as_deref(), Some("_helper"));
+        assert_eq!(symbolicate(0x100003f80), None);
+        assert_eq!(symbolicate(0x100003f00), None);
+    }
+}
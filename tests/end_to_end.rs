@@ -0,0 +1,268 @@
+// Runs the fantac binary over Mach-O files built here byte by byte, laid
+// out the way a compiler lays out a 64-bit object's DWARF, and checks what
+// it prints.
+
+use std::process::Command;
+
+// A compile unit for `hello.c`, with its name and producer in __debug_str.
+const PRODUCER: &str = "Apple clang version 15.0.0 (clang-1500.1.0.2.5)";
+const NAME: &str = "hello.c";
+
+fn abbrev() -> Vec<u8> {
+    vec![
+        0x01, 0x11, 0x00, // [1] DW_TAG_compile_unit, no children
+        0x25, 0x0e,       // DW_AT_producer, DW_FORM_strp
+        0x13, 0x05,       // DW_AT_language, DW_FORM_data2
+        0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+        0x00, 0x00,
+        0x00,
+    ]
+}
+
+fn strs() -> Vec<u8> {
+    let mut bytes = vec![0];
+    for s in [PRODUCER, NAME] {
+        bytes.extend_from_slice(s.as_bytes());
+        bytes.push(0);
+    }
+    bytes
+}
+
+fn info() -> Vec<u8> {
+    let mut body = vec![];
+    body.extend_from_slice(&4u16.to_ne_bytes());  // version
+    body.extend_from_slice(&0u32.to_ne_bytes());  // debug_abbrev_offset
+    body.push(8);                                 // address_size
+    body.push(0x01);                              // abbrev code
+    body.extend_from_slice(&1u32.to_ne_bytes());  // DW_AT_producer
+    body.extend_from_slice(&0x0cu16.to_ne_bytes()); // DW_AT_language: C99
+    body.extend_from_slice(&(2 + PRODUCER.len() as u32).to_ne_bytes()); // DW_AT_name
+    let mut bytes = (body.len() as u32).to_ne_bytes().to_vec();
+    bytes.extend(body);
+    bytes
+}
+
+fn name16(name: &str) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    bytes
+}
+
+fn word(bytes: &mut Vec<u8>, w: u32) {
+    bytes.extend_from_slice(&w.to_ne_bytes());
+}
+
+// Builds a 64-bit x86_64 object with one __DWARF segment holding the
+// given sections, whose contents follow the load commands.
+fn object(sections: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let loads_size = 72 + 80 * sections.len();
+    let data_start = 32 + loads_size;
+    let data_size: usize = sections.iter().map(|(_, data)| data.len()).sum();
+
+    let mut bytes = vec![];
+    for w in [0xfeedfacf, 0x01000007, 0x03, 0x1, 1, loads_size as u32, 0, 0] {
+        word(&mut bytes, w);
+    }
+
+    word(&mut bytes, 0x19);
+    word(&mut bytes, loads_size as u32);
+    bytes.extend_from_slice(&name16("__DWARF"));
+    for w in [0, data_size as u64, data_start as u64, data_size as u64] {
+        bytes.extend_from_slice(&w.to_ne_bytes());
+    }
+    for w in [7, 7, sections.len() as u32, 0] {
+        word(&mut bytes, w);
+    }
+    let mut offset = data_start;
+    for (name, data) in sections {
+        bytes.extend_from_slice(&name16(name));
+        bytes.extend_from_slice(&name16("__DWARF"));
+        bytes.extend_from_slice(&0u64.to_ne_bytes());
+        bytes.extend_from_slice(&(data.len() as u64).to_ne_bytes());
+        for w in [offset as u32, 0, 0, 0, 0x02000000, 0, 0, 0] {
+            word(&mut bytes, w);
+        }
+        offset += data.len();
+    }
+
+    for (_, data) in sections {
+        bytes.extend_from_slice(data);
+    }
+    bytes
+}
+
+// Writes `bytes` to a temporary file and runs fantac on it with `args`,
+// returning whether it succeeded and its stdout and stderr.
+fn run(test: &str, bytes: &[u8], args: &[&str]) -> (bool, String, String) {
+    let path = std::env::temp_dir()
+        .join(format!("mecha-dwarf-{}-{}.o", test, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fantac"))
+        .args(args)
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    (output.status.success(),
+     String::from_utf8(output.stdout).unwrap(),
+     String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn prints_compile_unit_name_and_producer() {
+    let bytes = object(&[
+        ("__debug_abbrev", abbrev()),
+        ("__debug_str", strs()),
+        ("__debug_info", info()),
+    ]);
+    let (ok, stdout, _) = run("cu", &bytes, &[]);
+    assert!(ok, "fantac failed:\n{}", stdout);
+    assert!(stdout.contains("DW_TAG_CompileUnit"), "{}", stdout);
+    assert!(stdout.contains(&format!("{:?}", NAME)), "{}", stdout);
+    assert!(stdout.contains(&format!("{:?}", PRODUCER)), "{}", stdout);
+}
+
+#[test]
+fn lists_dwarf_sections() {
+    let bytes = object(&[
+        ("__debug_abbrev", abbrev()),
+        ("__debug_str", strs()),
+        ("__debug_info", info()),
+    ]);
+    let (ok, stdout, _) = run("list", &bytes, &["--list"]);
+    assert!(ok, "fantac failed:\n{}", stdout);
+    assert!(stdout.contains("__DWARF"), "{}", stdout);
+    assert!(stdout.contains("__debug_info"), "{}", stdout);
+}
+
+#[test]
+fn reports_truncated_debug_info() {
+    let mut info = info();
+    info.truncate(info.len() - 2);
+    let bytes = object(&[
+        ("__debug_abbrev", abbrev()),
+        ("__debug_str", strs()),
+        ("__debug_info", info),
+    ]);
+    let (ok, stdout, stderr) = run("truncated", &bytes, &[]);
+    assert!(!ok);
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("error parsing dwarf:"), "{}", stderr);
+}
+
+#[test]
+fn quiet_prints_only_errors() {
+    let sections = [
+        ("__debug_abbrev", abbrev()),
+        ("__debug_str", strs()),
+        ("__debug_info", info()),
+    ];
+    let (ok, stdout, stderr) = run("quiet", &object(&sections), &["--quiet"]);
+    assert!(ok);
+    assert_eq!((stdout.as_str(), stderr.as_str()), ("", ""));
+
+    let (ok, stdout, stderr) = run("quiet-bad", &object(&sections[..1]), &["--quiet", "-v"]);
+    assert!(!ok);
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("error"), "{}", stderr);
+}
+
+#[test]
+fn continues_past_files_that_fail() {
+    let good = object(&[
+        ("__debug_abbrev", abbrev()),
+        ("__debug_str", strs()),
+        ("__debug_info", info()),
+    ]);
+    let dir = std::env::temp_dir();
+    let good_path = dir.join(format!("mecha-dwarf-good-{}.o", std::process::id()));
+    let bad_path = dir.join(format!("mecha-dwarf-bad-{}.o", std::process::id()));
+    std::fs::write(&good_path, &good).unwrap();
+    std::fs::write(&bad_path, b"not a mach-o file").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fantac"))
+        .args([&bad_path, &good_path])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&good_path).unwrap();
+    std::fs::remove_file(&bad_path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stderr.contains("error"), "{}", stderr);
+    assert!(stdout.contains(&format!("{}:", bad_path.display())), "{}", stdout);
+    assert!(stdout.contains(&format!("{}:", good_path.display())), "{}", stdout);
+    assert!(stdout.contains(&format!("{:?}", NAME)), "{}", stdout);
+}
+
+#[test]
+fn parses_past_unknown_tags() {
+    let abbrev = vec![
+        0x01, 0x11, 0x01, // [1] DW_TAG_compile_unit, has children
+        0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+        0x00, 0x00,
+        0x02, 0x89, 0x82, 0x01, 0x00, // [2] DW_TAG_GNU_call_site (0x4109), no children
+        0x00, 0x00,
+        0x03, 0x7f, 0x00, // [3] an unassigned standard tag, no children
+        0x00, 0x00,
+        0x04, 0x2e, 0x00, // [4] DW_TAG_subprogram, no children
+        0x03, 0x0e,       // DW_AT_name, DW_FORM_strp
+        0x00, 0x00,
+        0x00,
+    ];
+    let strs = b"\0hello.c\0main\0".to_vec();
+    let mut body = vec![];
+    body.extend_from_slice(&4u16.to_ne_bytes());  // version
+    body.extend_from_slice(&0u32.to_ne_bytes());  // debug_abbrev_offset
+    body.push(8);                                 // address_size
+    body.push(0x01);
+    body.extend_from_slice(&1u32.to_ne_bytes());  // DW_AT_name: hello.c
+    body.push(0x02);
+    body.push(0x03);
+    body.push(0x04);
+    body.extend_from_slice(&9u32.to_ne_bytes());  // DW_AT_name: main
+    body.push(0x00);
+    let mut info = (body.len() as u32).to_ne_bytes().to_vec();
+    info.extend(body);
+
+    let bytes = object(&[
+        ("__debug_abbrev", abbrev),
+        ("__debug_str", strs),
+        ("__debug_info", info),
+    ]);
+    let (ok, stdout, stderr) = run("unknown-tags", &bytes, &[]);
+    assert!(ok, "fantac failed:\n{}", stderr);
+    assert!(stdout.contains("DW_TAG_unknown_0x4109"), "{}", stdout);
+    assert!(stdout.contains("DW_TAG_unknown_0x7f"), "{}", stdout);
+    assert!(stdout.contains("DW_TAG_Subprogram"), "{}", stdout);
+    assert!(stdout.contains("\"main\""), "{}", stdout);
+    assert_eq!(stderr, "");
+
+    let (ok, _, stderr) = run("unknown-tags-warnings", &bytes, &["--warnings"]);
+    assert!(ok, "fantac failed:\n{}", stderr);
+    assert_eq!(stderr, "warning: unrecognized tag 0x7f on 1 DIEs\n\
+                        warning: unrecognized tag 0x4109 on 1 DIEs\n");
+}
+
+#[test]
+fn prints_macho_before_failing_without_dwarf() {
+    // A 64-bit x86_64 object with no load commands at all.
+    let mut bytes = vec![];
+    for w in [0xfeedfacf, 0x01000007, 0x03, 0x1, 0, 0, 0, 0] {
+        word(&mut bytes, w);
+    }
+    let path = std::env::temp_dir().join(format!("mecha-dwarf-no-dwarf-{}.o", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_fantac"))
+        .args(["-v", "--list"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout.contains("Mach-O segments:"), "{}", stdout);
+    assert!(stderr.contains("no __DWARF segment"), "{}", stderr);
+}